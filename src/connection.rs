@@ -1,19 +1,74 @@
 //! Sophia Dataset implementation for Oxigraph RepositoryConnection
+use crate::hash::{hash_quads, hash_str, isomorphic};
+use crate::inserter::Inserter;
+#[cfg(feature = "parallel")]
+use crate::parallel::ParQuads;
 use crate::quad::QuadBridge;
-use crate::term::{AsSophiaTerm, ConversionError, TryOxigraphize};
-use oxigraph::model::{NamedNode, NamedOrBlankNode, Quad as OQuad, Term as OTerm};
+use crate::read_only::ReadOnlyConnection;
+use crate::term::{
+    is_stored_by_value, oxigraphize_literal_unchecked, AsSophiaLiteral, AsSophiaTerm, BnodeCache,
+    ConversionError, ConversionMode, Interner, OxiTermConverter, TryOxigraphize,
+};
+use crate::transaction::Txn;
+use crate::triple::TripleBridge;
+use ouroboros::self_referencing;
+use oxigraph::model::{
+    BlankNode as OBlankNode, NamedNode, NamedOrBlankNode, Quad as OQuad, Term as OTerm,
+};
 use oxigraph::sparql::{PreparedQuery, QueryOptions, QueryResult};
-use oxigraph::{Error as OxigraphError, RepositoryConnection};
+use oxigraph::{
+    Error as OxigraphError, GraphSyntax, MemoryRepository, Repository, RepositoryConnection,
+    RepositoryTransaction,
+};
+use sophia::dataset::inmem::FastDataset;
 use sophia::dataset::{DQuadSource, DResult, DResultTermSet, Dataset, MDResult, MutableDataset};
+use sophia::graph::{GTripleSource, Graph, MGResult, MutableGraph};
+use sophia::ns::{rdf, rdfs};
+use sophia::quad::stream::QuadSource;
 use sophia::quad::streaming_mode::*;
-use sophia_term::{Term, TermData};
-use std::collections::HashSet;
-use std::iter::empty;
+use sophia::quad::Quad;
+use sophia::triple::stream::{SinkError, SourceError, StreamResult, TripleSource};
+use sophia::triple::streaming_mode::{ByValue as TByValue, StreamedTriple};
+use sophia_term::blank_node::BlankNode as SBlankNode;
+use sophia_term::matcher::{GraphNameMatcher, TermMatcher, ANY};
+use sophia_term::{Term, TermData, TermKind};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufWriter, Write};
+use std::iter::{empty, once};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 use thiserror::Error;
 
+/// SPARQL query selecting all the IRIs of a dataset
+const IRIS_QUERY: &str = "SELECT DISTINCT ?iri {{?iri ?p ?o} UNION {?s ?iri ?o} UNION {?s ?p ?iri} UNION {GRAPH ?iri {?s ?p ?o}} UNION {GRAPH ?s {?iri ?p ?o}} UNION {GRAPH ?g {?s ?iri ?o}} UNION {GRAPH ?g {?s ?p ?iri}} FILTER isIRI(?iri)}";
+
+/// SPARQL query selecting all the literals of a dataset
+const LITERALS_QUERY: &str =
+    "SELECT DISTINCT ?lit {{?s ?p ?lit} UNION { GRAPH ?g {?s ?p ?lit}} FILTER isLiteral(?lit)}";
+
+/// Returned by `explain` when the backend does not expose query plans
+const EXPLAIN_NOT_SUPPORTED: &str = "query plans are not supported by this Oxigraph backend";
+
 /// Expose an Oxigraph Connection as a Sophia Dataset
+///
+/// Options can be set on a newly created connection with the `with_*` methods,
+/// e.g. `SophiaConnection::new(conn).with_max_literal_len(1024)`.
 #[derive(Clone, Debug, Default)]
-pub struct SophiaConnection<C: RepositoryConnection>(C);
+pub struct SophiaConnection<C: RepositoryConnection> {
+    conn: C,
+    max_literal_len: Option<usize>,
+    dry_run: Option<DryRunReport>,
+    converter: Option<Arc<dyn OxiTermConverter>>,
+    placeholder_base: Option<String>,
+    substitutions: Vec<Substitution>,
+    bnode_cache: Option<Arc<Mutex<BnodeCache>>>,
+    unchecked_language_tags: bool,
+    prepared: QueryCache<C>,
+    interner: Option<Arc<Mutex<Interner>>>,
+    default_graph_iri: Option<NamedNode>,
+    conversion_mode: ConversionMode,
+}
 
 impl<C> SophiaConnection<C>
 where
@@ -22,543 +77,5377 @@ where
     /// Wrap `conn` as a Sophia Dataset
     #[inline]
     pub fn new(conn: C) -> Self {
-        SophiaConnection(conn)
+        SophiaConnection {
+            conn,
+            max_literal_len: None,
+            dry_run: None,
+            converter: None,
+            placeholder_base: None,
+            substitutions: vec![],
+            bnode_cache: None,
+            unchecked_language_tags: false,
+            prepared: QueryCache::default(),
+            interner: None,
+            default_graph_iri: None,
+            conversion_mode: ConversionMode::Strict,
+        }
+    }
+
+    /// Limit the length (in bytes) of literals accepted by `insert`.
+    ///
+    /// Inserting a longer literal fails with [`ConversionError::LiteralTooLong`].
+    /// By default, the length of literals is not limited.
+    ///
+    /// [`ConversionError::LiteralTooLong`]: ../term/enum.ConversionError.html#variant.LiteralTooLong
+    #[inline]
+    pub fn with_max_literal_len(mut self, max: usize) -> Self {
+        self.max_literal_len = Some(max);
+        self
+    }
+
+    /// The maximum length of literals accepted by `insert`, if any.
+    #[inline]
+    pub fn max_literal_len(&self) -> Option<usize> {
+        self.max_literal_len
+    }
+
+    /// Use `converter` to convert Sophia terms to Oxigraph terms,
+    /// both when mutating the dataset and when matching its quads.
+    #[inline]
+    pub fn with_converter<V>(mut self, converter: V) -> Self
+    where
+        V: OxiTermConverter + 'static,
+    {
+        self.converter = Some(Arc::new(converter));
+        self
+    }
+
+    /// Make `insert` substitute placeholders for the terms it can not convert.
+    ///
+    /// Instead of failing, `insert` replaces each unrepresentable term
+    /// by an IRI made of `base` followed by a 128-bit hash (in hexadecimal) of the N-Triples form of that term,
+    /// and records the substitution in [`substitutions`](#method.substitutions).
+    ///
+    /// Fails with [`ConversionError::InvalidIri`] if `base` is not a valid absolute IRI.
+    ///
+    /// [`ConversionError::InvalidIri`]: ../term/enum.ConversionError.html#variant.InvalidIri
+    pub fn with_placeholders(mut self, base: &str) -> Result<Self, ConversionError> {
+        NamedNode::new(base).map_err(|_| ConversionError::InvalidIri(base.to_string()))?;
+        self.placeholder_base = Some(base.to_string());
+        Ok(self)
+    }
+
+    /// The substitutions performed by `insert` since this connection was created.
+    ///
+    /// See [`with_placeholders`](#method.with_placeholders).
+    #[inline]
+    pub fn substitutions(&self) -> &[Substitution] {
+        &self.substitutions
+    }
+
+    /// Give a synthetic Oxigraph blank node to each blank node label that Oxigraph rejects,
+    /// instead of failing to convert it.
+    ///
+    /// The original labels are restored in the quads returned by this connection,
+    /// but not in the results of SPARQL queries (e.g. `bnodes()`).
+    /// The cache is shared with the clones of this connection.
+    #[inline]
+    pub fn with_bnode_cache(mut self) -> Self {
+        self.bnode_cache = Some(Arc::new(Mutex::new(BnodeCache::default())));
+        self
+    }
+
+    /// Share an [`Interner`] between all the calls to [`quads_interned`](#method.quads_interned),
+    /// so that their terms share the storage of their text.
+    ///
+    /// The interner is shared with the clones of this connection.
+    ///
+    /// [`Interner`]: ../term/struct.Interner.html
+    #[inline]
+    pub fn with_interner(mut self) -> Self {
+        self.interner = Some(Arc::new(Mutex::new(Interner::default())));
+        self
+    }
+
+    /// Treat `iri` as an alias of the default graph.
+    ///
+    /// Sophia's default graph (`None`) is always mapped to Oxigraph's default graph.
+    /// With this option, the graph name `iri` is mapped to it as well,
+    /// whether it is used to insert, remove or match quads;
+    /// quads of the default graph are still returned with no graph name.
+    /// Without it, `iri` is an ordinary named graph.
+    ///
+    /// Fails with [`ConversionError::InvalidIri`] if `iri` is not a valid absolute IRI.
+    ///
+    /// [`ConversionError::InvalidIri`]: ../term/enum.ConversionError.html#variant.InvalidIri
+    pub fn with_default_graph_iri(mut self, iri: &str) -> Result<Self, ConversionError> {
+        let iri = NamedNode::new(iri).map_err(|_| ConversionError::InvalidIri(iri.to_string()))?;
+        self.default_graph_iri = Some(iri);
+        Ok(self)
+    }
+
+    /// The IRI treated as an alias of the default graph, if any.
+    ///
+    /// See [`with_default_graph_iri`](#method.with_default_graph_iri).
+    #[inline]
+    pub fn default_graph_iri(&self) -> Option<Term<String>> {
+        self.default_graph_iri.as_ref().map(AsSophiaTerm::as_sophia)
+    }
+
+    /// Set how `insert_all` handles quads that are only valid in generalized RDF
+    /// (e.g. with a literal as subject).
+    ///
+    /// By default ([`ConversionMode::Strict`]), they make `insert_all` fail;
+    /// with [`ConversionMode::Lenient`], they are skipped with a logged warning,
+    /// so that one such quad does not abort the insertion of a whole stream.
+    /// Other conversion errors are not affected.
+    ///
+    /// [`ConversionMode::Strict`]: ../term/enum.ConversionMode.html#variant.Strict
+    /// [`ConversionMode::Lenient`]: ../term/enum.ConversionMode.html#variant.Lenient
+    #[inline]
+    pub fn with_conversion_mode(mut self, mode: ConversionMode) -> Self {
+        self.conversion_mode = mode;
+        self
+    }
+
+    /// Do not validate the language tags of literals, which are otherwise rejected with
+    /// [`ConversionError::InvalidLanguageTag`] if they are malformed.
+    ///
+    /// Only use this if the language tags of the data are known to be valid.
+    /// This has no effect if a [custom converter](#method.with_converter) is set,
+    /// as the converter is then responsible for converting literals.
+    ///
+    /// [`ConversionError::InvalidLanguageTag`]: ../term/enum.ConversionError.html#variant.InvalidLanguageTag
+    #[inline]
+    pub fn with_unchecked_language_tags(mut self) -> Self {
+        self.unchecked_language_tags = true;
+        self
+    }
+
+    /// Switch dry-run mode on or off.
+    ///
+    /// In dry-run mode, mutations are not applied to the underlying store;
+    /// instead, their effect is recorded in a [`DryRunReport`],
+    /// available through [`dry_run_report`](#method.dry_run_report).
+    /// Switching dry-run mode on discards any previous report.
+    ///
+    /// The recorded changes are taken into account by [`contains`](#method.contains)
+    /// and by subsequent mutations, but not by the other read methods
+    /// (e.g. [`quads`](#method.quads)), which still see the underlying store.
+    /// SPARQL updates ([`update`](#method.update), [`update_counted`](#method.update_counted))
+    /// are simulated on an in-memory copy of the store.
+    /// Mutations applied directly to the Oxigraph connection
+    /// (through [`as_oxi_mut`](#method.as_oxi_mut)) are not intercepted either.
+    ///
+    /// [`DryRunReport`]: struct.DryRunReport.html
+    #[inline]
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = if dry_run {
+            Some(DryRunReport::default())
+        } else {
+            None
+        };
+    }
+
+    /// Return this connection with dry-run mode switched on.
+    ///
+    /// See [`set_dry_run`](#method.set_dry_run).
+    #[inline]
+    pub fn with_dry_run(mut self) -> Self {
+        self.set_dry_run(true);
+        self
+    }
+
+    /// Whether this connection is in dry-run mode.
+    #[inline]
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run.is_some()
+    }
+
+    /// The changes that would have been applied since dry-run mode was switched on.
+    ///
+    /// Returns `None` if this connection is not in dry-run mode.
+    #[inline]
+    pub fn dry_run_report(&self) -> Option<&DryRunReport> {
+        self.dry_run.as_ref()
+    }
+
+    /// Turn this connection into a read-only view.
+    pub fn read_only(self) -> ReadOnlyConnection<C> {
+        ReadOnlyConnection::new(self)
+    }
+
+    /// Turn this connection into a Sophia Graph,
+    /// restricted to the graph named `g` (or to the default graph if `g` is `None`).
+    pub fn graph<T: TermData>(
+        self,
+        g: Option<&Term<T>>,
+    ) -> Result<SophiaGraph<C>, ConversionError> {
+        let name = self.oxi_g(g)?;
+        Ok(SophiaGraph { conn: self, name })
     }
 
     /// Borrow underlying Oxigraph connection
     #[inline]
     pub fn as_oxi(&self) -> &C {
-        &self.0
+        &self.conn
     }
 
     /// Borrow underlying Oxigraph connection mutably
     #[inline]
     pub fn as_oxi_mut(&mut self) -> &mut C {
-        &mut self.0
+        &mut self.conn
     }
-}
-
-impl<C> Dataset for SophiaConnection<C>
-where
-    C: RepositoryConnection,
-{
-    type Quad = ByValue<QuadBridge>;
-    type Error = OxigraphError;
 
-    fn quads(&self) -> DQuadSource<Self> {
-        Box::new(
-            self.0
-                .quads_for_pattern(None, None, None, None)
-                .map(|r| r.map(|q| StreamedQuad::by_value(QuadBridge::new(q)))),
-        )
+    /// Make sure that all the mutations applied through this connection are durable.
+    ///
+    /// With the version of Oxigraph this crate depends on,
+    /// every mutation is committed to the store before the call applying it returns,
+    /// and `RepositoryConnection` exposes no flush operation;
+    /// so this is currently a no-op for all backends (and always will be for memory repositories).
+    /// Calling it anyway before dropping a connection to a persistent store
+    /// keeps the code correct if Oxigraph starts buffering writes.
+    #[inline]
+    pub fn flush(&mut self) -> Result<(), OxigraphError> {
+        Ok(())
     }
 
-    fn quads_with_s<'s, T>(&'s self, s: &'s Term<T>) -> DQuadSource<'s, Self>
-    where
-        T: TermData,
-    {
-        match s.try_oxigraphize() {
-            Ok(s) => Box::new(
-                self.0
-                    .quads_for_pattern(Some(&s), None, None, None)
-                    .map(bridge),
-            ),
-            Err(_) => Box::new(empty()),
+    /// Graph names of this dataset, as Oxigraph terms.
+    ///
+    /// This is the counterpart of `Dataset::graph_names`,
+    /// for users who intend to issue further Oxigraph queries with them,
+    /// and can therefore skip the conversion to Sophia terms.
+    pub fn oxi_graph_names(&self) -> Result<HashSet<NamedOrBlankNode>, OxigraphError> {
+        let q = self.conn.prepare_query(
+            "SELECT DISTINCT ?g {GRAPH ?g {?s ?p ?o}}",
+            QueryOptions::default(),
+        )?;
+        let mut ret = HashSet::new();
+        if let QueryResult::Bindings(b) = q.exec()? {
+            for r in b.into_values_iter() {
+                match r?.pop() {
+                    Some(Some(OTerm::NamedNode(n))) => ret.insert(n.into()),
+                    Some(Some(OTerm::BlankNode(b))) => ret.insert(b.into()),
+                    _ => false,
+                };
+            }
         }
+        Ok(ret)
     }
 
-    fn quads_with_p<'s, T>(&'s self, p: &'s Term<T>) -> DQuadSource<'s, Self>
+    /// The number of graphs holding data in this dataset,
+    /// counting the default graph if it is not empty.
+    ///
+    /// Note that `graph_names()` only accounts for named graphs.
+    pub fn graph_count(&self) -> DResult<Self, usize> {
+        let default = match self
+            .conn
+            .quads_for_pattern(None, None, None, Some(None))
+            .next()
+        {
+            None => 0,
+            Some(r) => r.map(|_| 1)?,
+        };
+        Ok(self.oxi_graph_names()?.len() + default)
+    }
+
+    /// Named graphs of this dataset where the number of quads with predicate `p`
+    /// is not between `min` and `max` (inclusive),
+    /// with the actual number of such quads.
+    ///
+    /// For example, `check_cardinality(&foaf::primaryTopic, 1, 1)`
+    /// reports all graphs that do not have exactly one `foaf:primaryTopic`.
+    pub fn check_cardinality<T>(
+        &self,
+        p: &Term<T>,
+        min: usize,
+        max: usize,
+    ) -> DResult<Self, Vec<(Term<String>, usize)>>
     where
         T: TermData,
     {
-        match p.try_oxigraphize() {
-            Ok(p) => Box::new(
-                self.0
-                    .quads_for_pattern(None, Some(&p), None, None)
-                    .map(bridge),
+        let p = match self.oxi_p(p) {
+            Ok(p) => p,
+            Err(_) => return Ok(vec![]),
+        };
+        let q = self.conn.prepare_query(
+            &format!(
+                "SELECT ?g (COUNT(*) AS ?c) {{ GRAPH ?g {{ ?s {} ?o }} }} GROUP BY ?g HAVING (COUNT(*) < {} || COUNT(*) > {})",
+                p, min, max
             ),
-            Err(_) => Box::new(empty()),
+            QueryOptions::default(),
+        )?;
+        let mut ret = vec![];
+        let mut seen = HashSet::new();
+        if let QueryResult::Bindings(b) = q.exec()? {
+            for r in b.into_values_iter() {
+                let mut v = r?;
+                let count = parse_count(v.pop().flatten())?;
+                if let Some(Some(g)) = v.pop() {
+                    seen.insert(g.clone());
+                    ret.push((g.into_sophia(), count));
+                }
+            }
+        }
+        if min > 0 {
+            let q = self.conn.prepare_query(
+                &format!(
+                    "SELECT DISTINCT ?g {{ GRAPH ?g {{ ?s ?p ?o }} FILTER NOT EXISTS {{ GRAPH ?g {{ ?s2 {} ?o2 }} }} }}",
+                    p
+                ),
+                QueryOptions::default(),
+            )?;
+            if let QueryResult::Bindings(b) = q.exec()? {
+                for r in b.into_values_iter() {
+                    if let Some(Some(g)) = r?.pop() {
+                        if !seen.contains(&g) {
+                            ret.push((g.into_sophia(), 0));
+                        }
+                    }
+                }
+            }
         }
+        Ok(ret)
     }
 
-    fn quads_with_o<'s, T>(&'s self, o: &'s Term<T>) -> DQuadSource<'s, Self>
-    where
-        T: TermData,
-    {
-        match o.try_oxigraphize() {
-            Ok(o) => Box::new(
-                self.0
-                    .quads_for_pattern(None, None, Some(&o), None)
-                    .map(bridge),
-            ),
-            Err(_) => Box::new(empty()),
+    /// A summary of the content of this dataset, for diagnostic purposes.
+    pub fn diagnostics(&self) -> DResult<Self, Diagnostics> {
+        let count = |what: &str| {
+            self.count_query(&format!(
+                "SELECT (COUNT({}) AS ?c) {{{{?s ?p ?o}} UNION {{ GRAPH ?g {{?s ?p ?o}}}}}}",
+                what
+            ))
+        };
+        Ok(Diagnostics {
+            backend: std::any::type_name::<C>(),
+            quads: self.count()?,
+            graphs: self.graph_count()?,
+            subjects: count("DISTINCT ?s")?,
+            predicates: count("DISTINCT ?p")?,
+            objects: count("DISTINCT ?o")?,
+        })
+    }
+
+    /// The classes and properties used in this dataset.
+    ///
+    /// See [`Vocabulary`](struct.Vocabulary.html).
+    pub fn extract_vocabulary(&self) -> DResult<Self, Vocabulary> {
+        let q = self.conn.prepare_query(
+            "SELECT DISTINCT ?c {{?s a ?c} UNION { GRAPH ?g {?s a ?c}}}",
+            QueryOptions::default(),
+        )?;
+        let classes = sparql_result_as_term_set(q.exec()?)?;
+        let properties = self.predicates()?;
+        let q = self.conn.prepare_query(
+            "SELECT DISTINCT ?c ?p {{?s a ?c; ?p ?o} UNION { GRAPH ?g {?s a ?c; ?p ?o}} FILTER (?p != <http://www.w3.org/1999/02/22-rdf-syntax-ns#type>)}",
+            QueryOptions::default(),
+        )?;
+        let mut class_properties = HashMap::new();
+        if let QueryResult::Bindings(b) = q.exec()? {
+            for r in b.into_values_iter() {
+                if let [Some(c), Some(p)] = &r?[..] {
+                    class_properties
+                        .entry(c.clone().into_sophia())
+                        .or_insert_with(HashSet::new)
+                        .insert(p.clone().into_sophia());
+                }
+            }
         }
+        Ok(Vocabulary {
+            classes,
+            properties,
+            class_properties,
+        })
     }
 
-    fn quads_with_g<'s, T>(&'s self, g: Option<&'s Term<T>>) -> DQuadSource<'s, Self>
+    /// All the quads of this dataset, with interned terms.
+    ///
+    /// Unlike [`quads`](#method.quads), repeated IRIs, datatypes and literals
+    /// share the same storage, which reduces memory usage when quads are retained.
+    /// Terms are interned with the interner of this connection
+    /// (see [`with_interner`](#method.with_interner)), if any,
+    /// or with an interner specific to the returned iterator.
+    /// The synthetic blank node labels of the [blank node cache](#method.with_bnode_cache)
+    /// are not restored.
+    pub fn quads_interned(
+        &self,
+    ) -> impl Iterator<Item = Result<QuadBridge<Arc<str>>, OxigraphError>> + '_ {
+        let interner = self
+            .interner
+            .clone()
+            .unwrap_or_else(|| Arc::new(Mutex::new(Interner::default())));
+        self.conn
+            .quads_for_pattern(None, None, None, None)
+            .map(move |r| r.map(|q| QuadBridge::interned(q, &mut interner.lock().unwrap())))
+    }
+
+    /// All the quads of this dataset, streamed by reference.
+    ///
+    /// Unlike [`quads`](#method.quads), the terms of each quad are not copied,
+    /// but borrowed from the underlying Oxigraph quad,
+    /// for the duration of the callback only.
+    /// The synthetic blank node labels of the [blank node cache](#method.with_bnode_cache)
+    /// are not restored.
+    pub fn quads_ref(&self) -> QuadsRef<impl Iterator<Item = Result<OQuad, OxigraphError>> + '_> {
+        QuadsRef(self.conn.quads_for_pattern(None, None, None, None))
+    }
+
+    /// The quads of this dataset matching the given pattern, as Oxigraph quads.
+    ///
+    /// Each component of the pattern is either `None` (matching any term)
+    /// or a Sophia term; for the graph name, `Some(None)` matches the default graph.
+    /// Only the pattern is converted to Oxigraph terms,
+    /// so this is cheaper than `quads_with_*` when the results are not needed as Sophia quads
+    /// (e.g. to count them or serialize them with Oxigraph).
+    /// If a term of the pattern can not be represented in Oxigraph, nothing is yielded.
+    pub fn oxi_quads_with<T, U, V, W>(
+        &self,
+        s: Option<&Term<T>>,
+        p: Option<&Term<U>>,
+        o: Option<&Term<V>>,
+        g: Option<Option<&Term<W>>>,
+    ) -> Box<dyn Iterator<Item = Result<OQuad, OxigraphError>> + '_>
     where
         T: TermData,
+        U: TermData,
+        V: TermData,
+        W: TermData,
     {
-        match try_oxi_graphname(g) {
-            Ok(g) => Box::new(
-                self.0
-                    .quads_for_pattern(None, None, None, Some(g.as_ref()))
-                    .map(bridge),
-            ),
+        match self.oxi_pattern(s, p, o, g) {
+            Ok(pattern) => self.oxi_quads_for_pattern(&pattern),
             Err(_) => Box::new(empty()),
         }
     }
 
-    fn quads_with_sp<'s, T, U>(&'s self, s: &'s Term<T>, p: &'s Term<U>) -> DQuadSource<'s, Self>
+    /// The number of quads in this dataset.
+    ///
+    /// The quads are counted by Oxigraph, without being converted to Sophia quads.
+    pub fn count(&self) -> DResult<Self, usize> {
+        self.count_query("SELECT (COUNT(*) AS ?c) {{?s ?p ?o} UNION { GRAPH ?g {?s ?p ?o}}}")
+    }
+
+    /// Whether this dataset contains no quad.
+    pub fn is_empty(&self) -> DResult<Self, bool> {
+        Ok(!self.ask("ASK {{?s ?p ?o} UNION { GRAPH ?g {?s ?p ?o}}}")?)
+    }
+
+    /// Whether this dataset contains the given quad,
+    /// failing if one of the terms can not be represented in Oxigraph.
+    ///
+    /// `contains` returns `Ok(false)` in that case,
+    /// as such a quad can not be in the store;
+    /// this method allows to distinguish it from a quad that is merely absent.
+    pub fn try_contains<T, U, V, W>(
+        &self,
+        s: &Term<T>,
+        p: &Term<U>,
+        o: &Term<V>,
+        g: Option<&Term<W>>,
+    ) -> Result<bool, QueryError>
     where
         T: TermData,
         U: TermData,
+        V: TermData,
+        W: TermData,
     {
-        match (s.try_oxigraphize(), p.try_oxigraphize()) {
-            (Ok(s), Ok(p)) => Box::new(
-                self.0
-                    .quads_for_pattern(Some(&s), Some(&p), None, None)
-                    .map(bridge),
-            ),
-            _ => Box::new(empty()),
+        let quad = OQuad::new(
+            self.oxi_s(s)?,
+            self.oxi_p(p)?,
+            self.oxi_o(o)?,
+            self.oxi_g(g)?,
+        );
+        Ok(self.oxi_contains(&quad)?)
+    }
+
+    /// Run a constant SPARQL query selecting a set of terms,
+    /// preparing it only the first time.
+    fn cached_term_set(&self, query: &'static str) -> DResultTermSet<Self> {
+        let mut queries = self.prepared.queries.lock().unwrap();
+        if !queries.contains_key(query) {
+            let q = self.conn.prepare_query(query, QueryOptions::default())?;
+            queries.insert(query, q);
         }
+        Ok(sparql_result_as_term_set(queries[query].exec()?)?)
     }
 
-    fn quads_with_so<'s, T, U>(&'s self, s: &'s Term<T>, o: &'s Term<U>) -> DQuadSource<'s, Self>
+    /// Run a SPARQL query selecting a single count.
+    fn count_query(&self, query: &str) -> Result<usize, OxigraphError> {
+        let q = self.conn.prepare_query(query, QueryOptions::default())?;
+        if let QueryResult::Bindings(b) = q.exec()? {
+            if let Some(r) = b.into_values_iter().next() {
+                return Ok(parse_count(r?.pop().flatten())?);
+            }
+        }
+        Ok(0)
+    }
+
+    /// Each subject of this dataset, with the number of quads it is the subject of,
+    /// sorted by decreasing count.
+    pub fn subject_fanout(&self) -> DResult<Self, Vec<(Term<String>, usize)>> {
+        let q = self.conn.prepare_query(
+            "SELECT ?s (COUNT(*) AS ?c) {{?s ?p ?o} UNION { GRAPH ?g {?s ?p ?o}}} GROUP BY ?s ORDER BY DESC(?c)",
+            QueryOptions::default(),
+        )?;
+        let mut ret = vec![];
+        if let QueryResult::Bindings(b) = q.exec()? {
+            for r in b.into_values_iter() {
+                let mut v = r?;
+                let count = parse_count(v.pop().flatten())?;
+                if let Some(Some(s)) = v.pop() {
+                    ret.push((s.as_sophia(), count));
+                }
+            }
+        }
+        Ok(ret)
+    }
+
+    /// The number of quads in each graph of this dataset,
+    /// the default graph being `None` (and absent if it is empty).
+    pub fn graph_sizes(&self) -> DResult<Self, HashMap<Option<Term<String>>, usize>> {
+        let q = self.conn.prepare_query(
+            "SELECT ?g (COUNT(*) AS ?c) {{?s ?p ?o} UNION { GRAPH ?g {?s ?p ?o}}} GROUP BY ?g",
+            QueryOptions::default(),
+        )?;
+        let mut ret = HashMap::new();
+        for_each_sparql_row(q.exec()?, 2, |_, mut row| {
+            let count = parse_count(row.pop().flatten())?;
+            let g = row.pop().flatten().map(OTerm::into_sophia);
+            ret.insert(g, count);
+            Ok(())
+        })?;
+        Ok(ret)
+    }
+
+    /// Literals of this dataset whose datatype is `dt`.
+    pub fn literals_with_datatype<T>(&self, dt: &Term<T>) -> DResultTermSet<Self>
     where
         T: TermData,
-        U: TermData,
     {
-        match (s.try_oxigraphize(), o.try_oxigraphize()) {
-            (Ok(s), Ok(o)) => Box::new(
-                self.0
-                    .quads_for_pattern(Some(&s), None, Some(&o), None)
-                    .map(bridge),
+        let dt = match self.oxi_p(dt) {
+            Ok(dt) => dt,
+            Err(_) => return Ok(HashSet::new()),
+        };
+        let q = self.conn.prepare_query(
+            &format!(
+                "SELECT DISTINCT ?lit {{{{?s ?p ?lit}} UNION {{ GRAPH ?g {{?s ?p ?lit}}}} FILTER (isLiteral(?lit) && DATATYPE(?lit) = {})}}",
+                dt
             ),
-            _ => Box::new(empty()),
-        }
+            QueryOptions::default(),
+        )?;
+        let r = q.exec()?;
+        Ok(sparql_result_as_term_set(r)?)
     }
 
-    fn quads_with_sg<'s, T, U>(
-        &'s self,
-        s: &'s Term<T>,
-        g: Option<&'s Term<U>>,
-    ) -> DQuadSource<'s, Self>
+    /// The distinct objects of `p`, in any graph.
+    ///
+    /// The quads with predicate `p` are streamed from Oxigraph,
+    /// and their objects are only converted to Sophia terms when the returned iterator is consumed;
+    /// only the objects already yielded are kept in memory, to skip duplicates.
+    pub fn distinct_objects<T>(
+        &self,
+        p: &Term<T>,
+    ) -> impl Iterator<Item = DResult<Self, Term<String>>> + '_
     where
         T: TermData,
-        U: TermData,
     {
-        match (s.try_oxigraphize(), try_oxi_graphname(g)) {
-            (Ok(s), Ok(g)) => Box::new(
-                self.0
-                    .quads_for_pattern(Some(&s), None, None, Some(g.as_ref()))
-                    .map(bridge),
-            ),
-            _ => Box::new(empty()),
+        let quads: Box<dyn Iterator<Item = Result<OQuad, OxigraphError>> + '_> = match self.oxi_p(p)
+        {
+            Ok(p) => Box::new(self.conn.quads_for_pattern(None, Some(&p), None, None)),
+            Err(_) => Box::new(empty()),
+        };
+        let mut seen = HashSet::new();
+        quads.filter_map(move |r| match r {
+            Ok(q) => {
+                let (_, _, o, _) = q.destruct();
+                if seen.insert(o.clone()) {
+                    Some(Ok(o.into_sophia::<String>()))
+                } else {
+                    None
+                }
+            }
+            Err(err) => Some(Err(err)),
+        })
+    }
+
+    /// Predicates of this dataset that are not described,
+    /// i.e. that are not the subject of any quad.
+    pub fn undefined_predicates(&self) -> DResultTermSet<Self> {
+        let q = self.conn.prepare_query(
+            "SELECT DISTINCT ?p {{{?s ?p ?o} UNION { GRAPH ?g {?s ?p ?o}}} FILTER NOT EXISTS {{?p ?p2 ?o2} UNION { GRAPH ?g2 {?p ?p2 ?o2}}}}",
+            QueryOptions::default(),
+        )?;
+        let r = q.exec()?;
+        Ok(sparql_result_as_term_set(r)?)
+    }
+
+    /// IRIs of this dataset that fail strict IRI validation.
+    ///
+    /// Malformed IRIs may end up in the store when they are inserted unchecked,
+    /// so this method is intended for auditing the content of a store.
+    pub fn find_invalid_iris(&self) -> DResult<Self, HashSet<Term<String>>> {
+        let q = self
+            .conn
+            .prepare_query(IRIS_QUERY, QueryOptions::default())?;
+        let mut ret = HashSet::new();
+        if let QueryResult::Bindings(b) = q.exec()? {
+            for r in b.into_values_iter() {
+                if let Some(Some(OTerm::NamedNode(n))) = r?.pop() {
+                    if NamedNode::new(n.as_str()).is_err() {
+                        ret.insert(n.into_sophia());
+                    }
+                }
+            }
         }
+        Ok(ret)
     }
 
-    fn quads_with_po<'s, T, U>(&'s self, p: &'s Term<T>, o: &'s Term<U>) -> DQuadSource<'s, Self>
+    /// The lexical value of the single literal object of `s` and `p`, in any graph.
+    ///
+    /// Returns `None` if there is no such object,
+    /// and an error if there are several objects, or if the object is not a literal.
+    pub fn string_value<T, U>(&self, s: &Term<T>, p: &Term<U>) -> DResult<Self, Option<String>>
     where
         T: TermData,
         U: TermData,
     {
-        match (p.try_oxigraphize(), o.try_oxigraphize()) {
-            (Ok(p), Ok(o)) => Box::new(
-                self.0
-                    .quads_for_pattern(None, Some(&p), Some(&o), None)
-                    .map(bridge),
-            ),
-            _ => Box::new(empty()),
+        let (s, p) = match (self.oxi_s(s), self.oxi_p(p)) {
+            (Ok(s), Ok(p)) => (s, p),
+            _ => return Ok(None),
+        };
+        let mut ret: Option<OTerm> = None;
+        for q in self.conn.quads_for_pattern(Some(&s), Some(&p), None, None) {
+            let o = q?.destruct().2;
+            match &ret {
+                None => ret = Some(o),
+                Some(o2) if o2 == &o => (),
+                Some(_) => {
+                    return Err(OxigraphError::msg(format!(
+                        "{} has several values for {}",
+                        s, p
+                    )))
+                }
+            }
+        }
+        match ret {
+            None => Ok(None),
+            Some(OTerm::Literal(lit)) => Ok(Some(lit.destruct().0)),
+            Some(o) => Err(OxigraphError::msg(format!("{} is not a literal", o))),
         }
     }
 
-    fn quads_with_pg<'s, T, U>(
+    /// Iterate over the lexical values of all literal objects of `p`, in any graph.
+    ///
+    /// Objects that are not literals are skipped.
+    /// Unlike `quads_with_p`, this does not build any Sophia term.
+    pub fn object_strings<'s, T>(
         &'s self,
-        p: &'s Term<T>,
-        g: Option<&'s Term<U>>,
-    ) -> DQuadSource<'s, Self>
+        p: &Term<T>,
+    ) -> Box<dyn Iterator<Item = Result<String, OxigraphError>> + 's>
     where
         T: TermData,
-        U: TermData,
     {
-        match (p.try_oxigraphize(), try_oxi_graphname(g)) {
-            (Ok(p), Ok(g)) => Box::new(
-                self.0
-                    .quads_for_pattern(None, Some(&p), None, Some(g.as_ref()))
-                    .map(bridge),
+        match self.oxi_p(p) {
+            Ok(p) => Box::new(
+                self.conn
+                    .quads_for_pattern(None, Some(&p), None, None)
+                    .filter_map(|r| match r {
+                        Ok(q) => match q.destruct().2 {
+                            OTerm::Literal(lit) => Some(Ok(lit.destruct().0)),
+                            _ => None,
+                        },
+                        Err(err) => Some(Err(err)),
+                    }),
             ),
-            _ => Box::new(empty()),
+            Err(_) => Box::new(empty()),
         }
     }
 
-    fn quads_with_og<'s, T, U>(
-        &'s self,
-        o: &'s Term<T>,
-        g: Option<&'s Term<U>>,
-    ) -> DQuadSource<'s, Self>
+    /// The graphs in which `s` is the subject of at least one quad.
+    ///
+    /// The default graph is represented by `None`.
+    pub fn graphs_of<T>(&self, s: &Term<T>) -> DResult<Self, HashSet<Option<Term<String>>>>
     where
         T: TermData,
-        U: TermData,
     {
-        match (o.try_oxigraphize(), try_oxi_graphname(g)) {
-            (Ok(o), Ok(g)) => Box::new(
-                self.0
-                    .quads_for_pattern(None, None, Some(&o), Some(g.as_ref()))
-                    .map(bridge),
-            ),
-            _ => Box::new(empty()),
+        let mut ret = HashSet::new();
+        if let Ok(s) = self.oxi_s(s) {
+            for q in self.conn.quads_for_pattern(Some(&s), None, None, None) {
+                ret.insert(q?.destruct().3.map(AsSophiaTerm::into_sophia));
+            }
         }
+        Ok(ret)
     }
 
-    fn quads_with_spo<'s, T, U, V>(
-        &'s self,
-        s: &'s Term<T>,
-        p: &'s Term<U>,
-        o: &'s Term<V>,
-    ) -> DQuadSource<'s, Self>
+    /// The triples that appear in more than one graph, with the graphs they appear in.
+    ///
+    /// The default graph is represented by `None`.
+    pub fn cross_graph_duplicates(
+        &self,
+    ) -> DResult<Self, Vec<([Term<String>; 3], HashSet<Option<Term<String>>>)>> {
+        let q = self.conn.prepare_query(
+            "SELECT ?s ?p ?o {{?s ?p ?o} UNION { GRAPH ?g {?s ?p ?o}}} GROUP BY ?s ?p ?o HAVING (COUNT(*) > 1)",
+            QueryOptions::default(),
+        )?;
+        let mut ret = vec![];
+        if let QueryResult::Bindings(b) = q.exec()? {
+            for r in b.into_values_iter() {
+                let (s, p, o) = match &r?[..] {
+                    [Some(s), Some(OTerm::NamedNode(p)), Some(o)] => {
+                        (s.clone(), p.clone(), o.clone())
+                    }
+                    _ => continue,
+                };
+                let s: NamedOrBlankNode = match s {
+                    OTerm::NamedNode(n) => n.into(),
+                    OTerm::BlankNode(b) => b.into(),
+                    _ => continue,
+                };
+                let mut graphs = HashSet::new();
+                for q in self
+                    .conn
+                    .quads_for_pattern(Some(&s), Some(&p), Some(&o), None)
+                {
+                    graphs.insert(q?.destruct().3.map(AsSophiaTerm::into_sophia));
+                }
+                ret.push(([s.into_sophia(), p.into_sophia(), o.into_sophia()], graphs));
+            }
+        }
+        Ok(ret)
+    }
+
+    /// A 128-bit hash of the content of this dataset.
+    ///
+    /// The hash does not depend on blank node labels,
+    /// so two datasets that are isomorphic have the same hash.
+    /// The converse does not hold (see [`is_isomorphic_to`](#method.is_isomorphic_to)).
+    /// It is also stable across platforms, so it can be persisted for change detection.
+    pub fn content_hash(&self) -> DResult<Self, u128> {
+        let quads = self
+            .conn
+            .quads_for_pattern(None, None, None, None)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(hash_quads(quads))
+    }
+
+    /// Whether this dataset and `other` are equal, up to the labels of their blank nodes.
+    ///
+    /// Quads of `other` that can not be converted to Oxigraph make it different from this dataset.
+    pub fn is_isomorphic_to<D: Dataset>(
+        &self,
+        other: &D,
+    ) -> StreamResult<bool, D::Error, OxigraphError> {
+        let mut theirs = HashSet::new();
+        for q in other.quads() {
+            let q = q.map_err(SourceError)?;
+            match self.oxi_quad(q.s(), q.p(), q.o(), q.g()) {
+                Ok(q) => theirs.insert(q),
+                Err(_) => return Ok(false),
+            };
+        }
+        let ours = self
+            .conn
+            .quads_for_pattern(None, None, None, None)
+            .collect::<Result<HashSet<_>, _>>()
+            .map_err(SinkError)?;
+        Ok(isomorphic(ours, theirs))
+    }
+
+    /// The `rdfs:label` of `resource` that best matches the language preferences `langs`.
+    ///
+    /// See [`preferred_value`](#method.preferred_value).
+    pub fn preferred_label<T>(
+        &self,
+        resource: &Term<T>,
+        langs: &[&str],
+    ) -> DResult<Self, Option<String>>
     where
         T: TermData,
-        U: TermData,
-        V: TermData,
     {
-        match (
-            s.try_oxigraphize(),
-            p.try_oxigraphize(),
-            o.try_oxigraphize(),
-        ) {
-            (Ok(s), Ok(p), Ok(o)) => Box::new(
-                self.0
-                    .quads_for_pattern(Some(&s), Some(&p), Some(&o), None)
-                    .map(bridge),
-            ),
-            _ => Box::new(empty()),
-        }
+        self.preferred_value(resource, &rdfs::label, langs)
     }
 
-    fn quads_with_spg<'s, T, U, V>(
-        &'s self,
-        s: &'s Term<T>,
-        p: &'s Term<U>,
-        g: Option<&'s Term<V>>,
-    ) -> DQuadSource<'s, Self>
+    /// The value of `p` for `resource` (in any graph) that best matches
+    /// the language preferences `langs`.
+    ///
+    /// `langs` contains language ranges, in decreasing order of preference;
+    /// each range matches language tags that are equal to it or start with it,
+    /// followed by `-` (e.g. `en` matches `en-US`), case-insensitively.
+    /// If no value matches any range, any literal value is returned;
+    /// if there is none, the local name of `resource` (if it is an IRI) is returned.
+    pub fn preferred_value<T, U>(
+        &self,
+        resource: &Term<T>,
+        p: &Term<U>,
+        langs: &[&str],
+    ) -> DResult<Self, Option<String>>
     where
         T: TermData,
         U: TermData,
-        V: TermData,
     {
-        match (
-            s.try_oxigraphize(),
-            p.try_oxigraphize(),
-            try_oxi_graphname(g),
-        ) {
-            (Ok(s), Ok(p), Ok(g)) => Box::new(
-                self.0
-                    .quads_for_pattern(Some(&s), Some(&p), None, Some(g.as_ref()))
-                    .map(bridge),
-            ),
-            _ => Box::new(empty()),
+        let local_name = match resource {
+            Term::Iri(iri) => {
+                let iri = iri.value();
+                iri.rsplit(|c| c == '/' || c == '#')
+                    .next()
+                    .filter(|name| !name.is_empty())
+                    .map(str::to_string)
+            }
+            _ => None,
+        };
+        let (s, p) = match (self.oxi_s(resource), self.oxi_p(p)) {
+            (Ok(s), Ok(p)) => (s, p),
+            _ => return Ok(local_name),
+        };
+        // the best value so far, with the rank of its language range
+        let mut best: Option<(usize, String)> = None;
+        for q in self.conn.quads_for_pattern(Some(&s), Some(&p), None, None) {
+            if let OTerm::Literal(lit) = q?.destruct().2 {
+                let rank = lit
+                    .language()
+                    .and_then(|tag| langs.iter().position(|range| lang_matches(tag, range)))
+                    .unwrap_or(langs.len());
+                if best.as_ref().map(|(r, _)| rank < *r).unwrap_or(true) {
+                    best = Some((rank, lit.destruct().0));
+                }
+            }
         }
+        Ok(best.map(|(_, value)| value).or(local_name))
     }
 
-    fn quads_with_sog<'s, T, U, V>(
-        &'s self,
-        s: &'s Term<T>,
-        o: &'s Term<U>,
-        g: Option<&'s Term<V>>,
-    ) -> DQuadSource<'s, Self>
+    /// The lexical value and datatype of each literal object of `s` and `p`, in any graph.
+    ///
+    /// Objects that are not literals are skipped.
+    pub fn typed_objects<T, U>(
+        &self,
+        s: &Term<T>,
+        p: &Term<U>,
+    ) -> DResult<Self, Vec<(String, Term<String>)>>
     where
         T: TermData,
         U: TermData,
-        V: TermData,
     {
-        match (
-            s.try_oxigraphize(),
-            o.try_oxigraphize(),
-            try_oxi_graphname(g),
-        ) {
-            (Ok(s), Ok(o), Ok(g)) => Box::new(
-                self.0
-                    .quads_for_pattern(Some(&s), None, Some(&o), Some(g.as_ref()))
-                    .map(bridge),
-            ),
-            _ => Box::new(empty()),
+        let (s, p) = match (self.oxi_s(s), self.oxi_p(p)) {
+            (Ok(s), Ok(p)) => (s, p),
+            _ => return Ok(vec![]),
+        };
+        let mut ret = vec![];
+        for q in self.conn.quads_for_pattern(Some(&s), Some(&p), None, None) {
+            if let OTerm::Literal(lit) = q?.destruct().2 {
+                let dt = lit.datatype().as_sophia();
+                ret.push((lit.destruct().0, dt));
+            }
         }
+        Ok(ret)
     }
 
-    fn quads_with_pog<'s, T, U, V>(
-        &'s self,
-        p: &'s Term<T>,
-        o: &'s Term<U>,
-        g: Option<&'s Term<V>>,
-    ) -> DQuadSource<'s, Self>
+    /// Literals of this dataset that convert to a Sophia literal
+    /// with neither a language tag nor a datatype.
+    ///
+    /// This should never happen, so this method is intended for auditing
+    /// the conversion of literals.
+    pub fn find_untyped_literals(&self) -> DResult<Self, HashSet<Term<String>>> {
+        let q = self
+            .conn
+            .prepare_query(LITERALS_QUERY, QueryOptions::default())?;
+        let mut ret = HashSet::new();
+        if let QueryResult::Bindings(b) = q.exec()? {
+            for r in b.into_values_iter() {
+                if let Some(Some(OTerm::Literal(lit))) = r?.pop() {
+                    let slit = lit.as_sophia_l_ref();
+                    if slit.lang().is_none() && slit.dt().value().is_empty() {
+                        ret.insert(Term::Literal(lit.into_sophia_l()));
+                    }
+                }
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Run a SPARQL SELECT query, with some variables initially bound.
+    ///
+    /// The bindings are appended to `query` as a `VALUES` clause,
+    /// so `query` must not already end with one.
+    /// Blank nodes can not be bound.
+    ///
+    /// Each solution is returned as a map from variable names (without the `?`)
+    /// to their values; unbound variables are absent from the map.
+    pub fn query_select_bound<T>(
+        &self,
+        query: &str,
+        bindings: &HashMap<String, Term<T>>,
+    ) -> Result<Vec<HashMap<String, Term<String>>>, QueryError>
     where
         T: TermData,
-        U: TermData,
-        V: TermData,
     {
-        match (
-            p.try_oxigraphize(),
-            o.try_oxigraphize(),
-            try_oxi_graphname(g),
-        ) {
-            (Ok(p), Ok(o), Ok(g)) => Box::new(
-                self.0
-                    .quads_for_pattern(None, Some(&p), Some(&o), Some(g.as_ref()))
-                    .map(bridge),
-            ),
-            _ => Box::new(empty()),
+        let mut vars = String::new();
+        let mut values = String::new();
+        for (name, value) in bindings {
+            if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                return Err(QueryError::InvalidVariable(name.clone()));
+            }
+            let value = self.oxi_o(value)?;
+            if let OTerm::BlankNode(_) = value {
+                return Err(QueryError::BlankNodeBinding(name.clone()));
+            }
+            vars.push_str(&format!(" ?{}", name));
+            values.push_str(&format!(" {}", value));
         }
+        let query = if bindings.is_empty() {
+            query.to_string()
+        } else {
+            format!("{}\nVALUES ({} ) {{ ({} ) }}", query, vars, values)
+        };
+        self.run_select(&query)
     }
 
-    fn quads_with_spog<'s, T, U, V, W>(
-        &'s self,
-        s: &'s Term<T>,
-        p: &'s Term<U>,
-        o: &'s Term<V>,
-        g: Option<&'s Term<W>>,
-    ) -> DQuadSource<'s, Self>
+    /// Run a SPARQL SELECT query, prefixed with a `PREFIX` declaration for each entry of `prefixes`.
+    ///
+    /// Each entry is a pair (prefix, namespace);
+    /// the namespace must be an absolute IRI.
+    ///
+    /// Each solution is returned as a map from variable names (without the `?`)
+    /// to their values; unbound variables are absent from the map.
+    pub fn query_select(
+        &self,
+        query: &str,
+        prefixes: Option<&[(&str, &str)]>,
+    ) -> Result<Vec<HashMap<String, Term<String>>>, QueryError> {
+        let mut prelude = String::new();
+        for (prefix, ns) in prefixes.unwrap_or(&[]) {
+            let valid_prefix = prefix
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.');
+            if !valid_prefix || NamedNode::new(*ns).is_err() {
+                return Err(QueryError::InvalidPrefix(
+                    prefix.to_string(),
+                    ns.to_string(),
+                ));
+            }
+            prelude.push_str(&format!("PREFIX {}: <{}>\n", prefix, ns));
+        }
+        prelude.push_str(query);
+        self.run_select(&prelude)
+    }
+
+    /// Run a SPARQL SELECT query, and convert its solutions into maps.
+    fn run_select(&self, query: &str) -> Result<Vec<HashMap<String, Term<String>>>, QueryError> {
+        Ok(self.select(query)?.collect::<Result<_, _>>()?)
+    }
+
+    /// Run a SPARQL SELECT query, and iterate over its solutions as Sophia terms.
+    ///
+    /// Each solution is a map from variable names (without the `?`)
+    /// to their values; unbound variables are absent from the map.
+    /// The solutions are retrieved from Oxigraph and converted lazily,
+    /// so that only the consumed solutions are ever built;
+    /// errors occurring while retrieving a solution are yielded in its place.
+    pub fn select(&self, query: &str) -> Result<SophiaBindings<C>, OxigraphError> {
+        let q = self.conn.prepare_query(query, QueryOptions::default())?;
+        let mut variables = vec![];
+        let rows = BindingsCell::try_new(q, |q| match q.exec() {
+            Ok(QueryResult::Bindings(b)) => {
+                variables = b
+                    .variables()
+                    .iter()
+                    .map(|v| v.to_string().trim_start_matches('?').to_string())
+                    .collect();
+                let rows: Box<dyn Iterator<Item = Result<Vec<Option<OTerm>>, OxigraphError>> + '_> =
+                    Box::new(b.into_values_iter());
+                Ok(rows)
+            }
+            Ok(_) => Err(OxigraphError::msg("not a SELECT query")),
+            Err(err) => Err(err),
+        })?;
+        Ok(SophiaBindings { variables, rows })
+    }
+
+    /// Run a SPARQL CONSTRUCT query, and iterate over the constructed triples as Sophia quads.
+    ///
+    /// All the constructed triples are in the default graph.
+    /// They are retrieved eagerly, but converted lazily.
+    pub fn construct(&self, query: &str) -> Result<DQuadSource<Self>, OxigraphError> {
+        let q = self.conn.prepare_query(query, QueryOptions::default())?;
+        match q.exec()? {
+            QueryResult::Graph(triples) => {
+                let quads = triples
+                    .map(|r| r.map(|t| t.in_graph(None)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Box::new(quads.into_iter().map(move |q| self.bridge(Ok(q)))))
+            }
+            _ => Err(OxigraphError::msg("not a CONSTRUCT query")),
+        }
+    }
+
+    /// A description of how the backend evaluates the SPARQL query `query`.
+    ///
+    /// The query is prepared (so syntax errors are reported),
+    /// but the version of Oxigraph this crate depends on exposes
+    /// neither query plans nor statistics, for any backend;
+    /// so a message saying so is returned instead.
+    pub fn explain(&self, query: &str) -> Result<String, OxigraphError> {
+        self.conn.prepare_query(query, QueryOptions::default())?;
+        Ok(EXPLAIN_NOT_SUPPORTED.to_string())
+    }
+
+    /// Run a SPARQL ASK query.
+    pub fn ask(&self, query: &str) -> Result<bool, OxigraphError> {
+        let q = self.conn.prepare_query(query, QueryOptions::default())?;
+        match q.exec()? {
+            QueryResult::Boolean(b) => Ok(b),
+            _ => Err(OxigraphError::msg("not an ASK query")),
+        }
+    }
+
+    /// Insert in the default graph the reification of triple `s p o`,
+    /// using `statement` as the statement node.
+    ///
+    /// Return the number of quads that were actually inserted.
+    pub fn reify<T, U, V, W>(
+        &mut self,
+        s: &Term<T>,
+        p: &Term<U>,
+        o: &Term<V>,
+        statement: &Term<W>,
+    ) -> MDResult<Self, usize>
     where
         T: TermData,
         U: TermData,
         V: TermData,
         W: TermData,
     {
-        match (
-            s.try_oxigraphize(),
-            p.try_oxigraphize(),
-            o.try_oxigraphize(),
-            try_oxi_graphname(g),
-        ) {
-            (Ok(s), Ok(p), Ok(o), Ok(g)) => Box::new(
-                self.0
-                    .quads_for_pattern(Some(&s), Some(&p), Some(&o), Some(g.as_ref()))
-                    .map(bridge),
-            ),
-            _ => Box::new(empty()),
-        }
+        let dg: Option<&Term<&'static str>> = None;
+        let mut count = 0;
+        count += self.insert(statement, &rdf::type_, &rdf::Statement, dg)? as usize;
+        count += self.insert(statement, &rdf::subject, s, dg)? as usize;
+        count += self.insert(statement, &rdf::predicate, p, dg)? as usize;
+        count += self.insert(statement, &rdf::object, o, dg)? as usize;
+        Ok(count)
     }
 
-    fn contains<T, U, V, W>(
-        &self,
+    /// Insert triple `s p o` in each of the given `graphs`, in a single transaction.
+    ///
+    /// The default graph is represented by `None`.
+    /// Return the number of graphs where the triple was not already present.
+    pub fn insert_into_graphs<T, U, V, W>(
+        &mut self,
         s: &Term<T>,
         p: &Term<U>,
         o: &Term<V>,
-        g: Option<&Term<W>>,
-    ) -> DResult<Self, bool>
+        graphs: &[Option<&Term<W>>],
+    ) -> MDResult<Self, usize>
     where
         T: TermData,
         U: TermData,
         V: TermData,
         W: TermData,
     {
-        match (
-            TryOxigraphize::<NamedOrBlankNode>::try_oxigraphize(s),
-            TryOxigraphize::<NamedNode>::try_oxigraphize(p),
-            TryOxigraphize::<OTerm>::try_oxigraphize(o),
-            try_oxi_graphname(g),
-        ) {
-            (Ok(s), Ok(p), Ok(o), Ok(g)) => self.0.contains(&OQuad::new(s, p, o, g)),
-            _ => Ok(false),
+        let quads = graphs
+            .iter()
+            .map(|g| self.oxi_quad(s, p, o, *g))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(self.insert_oxi_batch(quads)?)
+    }
+
+    /// Retrieve, from the default graph, the triple reified by `statement`.
+    ///
+    /// Return `None` if `statement` does not have exactly one subject,
+    /// one predicate and one object.
+    pub fn dereify<T>(&self, statement: &Term<T>) -> DResult<Self, Option<[Term<String>; 3]>>
+    where
+        T: TermData,
+    {
+        let statement = match self.oxi_s(statement) {
+            Ok(statement) => statement,
+            Err(_) => return Ok(None),
+        };
+        let mut ret = [None, None, None];
+        for (i, prop) in [&rdf::subject, &rdf::predicate, &rdf::object]
+            .iter()
+            .enumerate()
+        {
+            let prop = match self.oxi_p(prop) {
+                Ok(prop) => prop,
+                Err(_) => return Ok(None),
+            };
+            let mut values =
+                self.conn
+                    .quads_for_pattern(Some(&statement), Some(&prop), None, Some(None));
+            ret[i] = match (values.next(), values.next()) {
+                (Some(q), None) => Some(q?.destruct().2.into_sophia()),
+                _ => return Ok(None),
+            };
+        }
+        match ret {
+            [Some(s), Some(p), Some(o)] => Ok(Some([s, p, o])),
+            _ => Ok(None),
         }
     }
 
-    fn subjects(&self) -> DResultTermSet<Self> {
-        let q = self.0.prepare_query(
-            "SELECT DISTINCT ?s {{?s ?p ?o} UNION { GRAPH ?g {?s ?p ?o}}}",
-            QueryOptions::default(),
-        )?;
-        let r = q.exec()?;
-        sparql_result_as_term_set(r)
+    /// Call `per_graph` once for each non-empty graph of this dataset,
+    /// with the graph name and an iterator over the triples of that graph.
+    ///
+    /// The default graph (represented by `None`) comes first,
+    /// followed by named graphs in lexicographic order.
+    pub fn export_by_graph<F>(&self, mut per_graph: F) -> DResult<Self, ()>
+    where
+        F: FnMut(Option<&Term<String>>, &mut dyn Iterator<Item = DResult<Self, [Term<String>; 3]>>),
+    {
+        let mut graphs: Vec<Option<NamedOrBlankNode>> =
+            self.oxi_graph_names()?.into_iter().map(Some).collect();
+        graphs.push(None);
+        graphs.sort_by_key(|g| g.as_ref().map(ToString::to_string));
+        for g in graphs {
+            let mut triples = self
+                .conn
+                .quads_for_pattern(None, None, None, Some(g.as_ref()))
+                .map(|r| {
+                    r.map(|q| {
+                        let (s, p, o, _) = q.destruct();
+                        [s.into_sophia(), p.into_sophia(), o.into_sophia()]
+                    })
+                })
+                .peekable();
+            if triples.peek().is_none() {
+                continue;
+            }
+            let g = g.map(AsSophiaTerm::into_sophia);
+            per_graph(g.as_ref(), &mut triples);
+        }
+        Ok(())
     }
 
-    fn predicates(&self) -> DResultTermSet<Self> {
-        let q = self.0.prepare_query(
-            "SELECT DISTINCT ?p {{?s ?p ?o} UNION { GRAPH ?g {?s ?p ?o}}}",
-            QueryOptions::default(),
-        )?;
-        let r = q.exec()?;
-        sparql_result_as_term_set(r)
+    /// A triple source for each graph of this dataset, paired with the graph name.
+    ///
+    /// The default graph (represented by `None`) is always included, and comes first,
+    /// followed by named graphs in lexicographic order.
+    /// Each source can be fed to a Sophia triple serializer.
+    pub fn graph_triple_sources(
+        &self,
+    ) -> DResult<Self, impl Iterator<Item = (Option<Term<String>>, impl TripleSource + '_)> + '_>
+    {
+        let mut graphs: Vec<Option<NamedOrBlankNode>> =
+            self.oxi_graph_names()?.into_iter().map(Some).collect();
+        graphs.sort_by_key(|g| g.as_ref().map(ToString::to_string));
+        graphs.insert(0, None);
+        Ok(graphs.into_iter().map(move |g| {
+            let triples = self
+                .conn
+                .quads_for_pattern(None, None, None, Some(g.as_ref()))
+                .map(|r| {
+                    r.map(|q| {
+                        let (s, p, o, _) = q.destruct();
+                        [s.into_sophia::<String>(), p.into_sophia(), o.into_sophia()]
+                    })
+                });
+            (g.map(AsSophiaTerm::into_sophia), triples)
+        }))
     }
 
-    fn objects(&self) -> DResultTermSet<Self> {
-        let q = self.0.prepare_query(
-            "SELECT DISTINCT ?o {{?s ?p ?o} UNION { GRAPH ?g {?s ?p ?o}}}",
-            QueryOptions::default(),
-        )?;
-        let r = q.exec()?;
-        sparql_result_as_term_set(r)
+    /// Compare the ground quads of `qs` with the ground quads of this dataset.
+    ///
+    /// Quads containing blank nodes are ignored on both sides,
+    /// as there is no well-defined way to match them.
+    pub fn delta_against<QS>(&self, mut qs: QS) -> StreamResult<Delta, QS::Error, QueryError>
+    where
+        QS: QuadSource,
+    {
+        let mut source = HashSet::new();
+        qs.try_for_each_quad(|q| -> Result<(), QueryError> {
+            let ground = ![q.s(), q.p(), q.o()]
+                .iter()
+                .chain(q.g().iter())
+                .any(|t| t.kind() == TermKind::BlankNode);
+            if ground {
+                source.insert(self.oxi_quad(q.s(), q.p(), q.o(), q.g())?);
+            }
+            Ok(())
+        })?;
+        let mut missing = vec![];
+        for q in self.conn.quads_for_pattern(None, None, None, None) {
+            let q = q.map_err(|err| SinkError(err.into()))?;
+            if is_ground(&q) && !source.remove(&q) {
+                missing.push(QuadBridge::new(q));
+            }
+        }
+        let new = source.into_iter().map(QuadBridge::new).collect();
+        Ok(Delta { new, missing })
     }
 
-    fn graph_names(&self) -> DResultTermSet<Self> {
-        let q = self.0.prepare_query(
-            "SELECT DISTINCT ?g {GRAPH ?g {?s ?p ?o}}",
-            QueryOptions::default(),
-        )?;
-        let r = q.exec()?;
-        sparql_result_as_term_set(r)
+    /// Copy all the quads of this dataset into a Sophia in-memory dataset.
+    ///
+    /// The returned dataset is a snapshot, independent of the underlying store.
+    pub fn to_inmem_dataset(&self) -> DResult<Self, FastDataset> {
+        let mut ret = FastDataset::default();
+        for q in self.quads() {
+            let q = q?;
+            ret.insert(q.s(), q.p(), q.o(), q.g())
+                .map_err(|err| OxigraphError::msg(err.to_string()))?;
+        }
+        Ok(ret)
     }
 
-    fn iris(&self) -> DResultTermSet<Self> {
-        let q = self.0.prepare_query("SELECT DISTINCT ?iri {{?iri ?p ?o} UNION {?s ?iri ?o} UNION {?s ?p ?iri} UNION {GRAPH ?iri {?s ?p ?o}} UNION {GRAPH ?s {?iri ?p ?o}} UNION {GRAPH ?g {?s ?iri ?o}} UNION {GRAPH ?g {?s ?p ?iri}} FILTER isIRI(?iri)}", QueryOptions::default())?;
-        let r = q.exec()?;
-        sparql_result_as_term_set(r)
+    /// Serialize this dataset into the file at `path`,
+    /// in one of the line-based formats described by [`LineFormat`].
+    ///
+    /// If `format` is `None`, it is guessed from the extension of `path`.
+    ///
+    /// N-Triples can only represent the default graph.
+    /// If this dataset has named graphs,
+    /// dumping it as N-Triples fails unless `default_only` is true,
+    /// in which case only the default graph is written.
+    ///
+    /// [`LineFormat`]: enum.LineFormat.html
+    pub fn dump_to_file<P: AsRef<Path>>(
+        &self,
+        path: P,
+        format: Option<LineFormat>,
+        default_only: bool,
+    ) -> Result<(), OxigraphError> {
+        let path = path.as_ref();
+        let format = match format.or_else(|| LineFormat::from_path(path)) {
+            Some(format) => format,
+            None => {
+                return Err(OxigraphError::msg(format!(
+                    "can not guess RDF syntax of {}",
+                    path.display()
+                )))
+            }
+        };
+        let graph = match format {
+            LineFormat::NTriples if default_only => Some(None),
+            _ => None,
+        };
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write_quads(&mut w, format, graph)?;
+        w.flush()?;
+        Ok(())
     }
 
-    fn bnodes(&self) -> DResultTermSet<Self> {
-        let q = self.0.prepare_query("SELECT DISTINCT ?bn {{?bn ?p ?o} UNION {?s ?p ?bn} UNION {GRAPH ?bn {?s ?p ?o}} UNION {GRAPH ?s {?bn ?p ?o}} UNION {GRAPH ?g {?s ?p ?bn}} FILTER isBlank(?bn)}", QueryOptions::default())?;
-        let r = q.exec()?;
-        sparql_result_as_term_set(r)
+    /// Serialize this dataset into `writer`,
+    /// in one of the line-based formats described by [`LineFormat`].
+    ///
+    /// Terms are serialized by Oxigraph, without being converted to Sophia terms.
+    /// Dumping a dataset with named graphs as N-Triples fails.
+    ///
+    /// [`LineFormat`]: enum.LineFormat.html
+    pub fn dump_to_writer<W: Write>(
+        &self,
+        mut writer: W,
+        format: LineFormat,
+    ) -> Result<(), OxigraphError> {
+        self.write_quads(&mut writer, format, None)
     }
 
-    fn literals(&self) -> DResultTermSet<Self> {
-        let q = self.0.prepare_query("SELECT DISTINCT ?lit {{?s ?p ?lit} UNION { GRAPH ?g {?s ?p ?lit}} FILTER isLiteral(?lit)}", QueryOptions::default())?;
-        let r = q.exec()?;
-        sparql_result_as_term_set(r)
+    /// Serialize the graph named `g` (or the default graph if `g` is `None`) into `writer`.
+    ///
+    /// As in [`dump_to_writer`](#method.dump_to_writer),
+    /// terms are serialized by Oxigraph.
+    pub fn dump_graph_to_writer<W: Write, T: TermData>(
+        &self,
+        mut writer: W,
+        format: LineFormat,
+        g: Option<&Term<T>>,
+    ) -> Result<(), OxigraphError> {
+        let g = self
+            .oxi_g(g)
+            .map_err(|err| OxigraphError::msg(err.to_string()))?;
+        self.write_quads(&mut writer, format, Some(g.as_ref()))
     }
 
-    fn variables(&self) -> DResultTermSet<Self> {
-        Ok(HashSet::new())
+    /// Write the quads of `graph` (all of them if `None`) in the given format,
+    /// one per line, each term in its N-Triples form (as displayed by Oxigraph).
+    fn write_quads<W: Write>(
+        &self,
+        w: &mut W,
+        format: LineFormat,
+        graph: Option<Option<&NamedOrBlankNode>>,
+    ) -> Result<(), OxigraphError> {
+        if format == LineFormat::NTriples && graph.is_none() && !self.oxi_graph_names()?.is_empty()
+        {
+            return Err(OxigraphError::msg(
+                "N-Triples can not represent named graphs",
+            ));
+        }
+        for q in self.conn.quads_for_pattern(None, None, None, graph) {
+            let (s, p, o, g) = q?.destruct();
+            match g {
+                Some(g) if format == LineFormat::NQuads => {
+                    writeln!(w, "{} {} {} {} .", s, p, o, g)?
+                }
+                _ => writeln!(w, "{} {} {} .", s, p, o)?,
+            }
+        }
+        Ok(())
     }
-}
 
-impl<C> MutableDataset for SophiaConnection<C>
-where
-    C: RepositoryConnection,
-{
-    type MutationError = MutationError;
+    /// Get an [`Inserter`] committing quads to this dataset by batches of `batch_size`.
+    ///
+    /// [`Inserter`]: ../inserter/struct.Inserter.html
+    pub fn inserter(&mut self, batch_size: usize) -> Inserter<C> {
+        Inserter::new(self, batch_size)
+    }
 
-    fn insert<T, U, V, W>(
+    /// Apply the mutations performed by `f` as a unit.
+    ///
+    /// The mutations are buffered by the [`Txn`] handle passed to `f`.
+    /// If `f` succeeds, they are applied in a single Oxigraph transaction;
+    /// if it fails, none of them is applied.
+    ///
+    /// [`Txn`]: ../transaction/struct.Txn.html
+    pub fn transaction<F>(&mut self, f: F) -> Result<(), MutationError>
+    where
+        F: FnOnce(&mut Txn<C>) -> Result<(), MutationError>,
+    {
+        let mut txn = Txn::new(self);
+        f(&mut txn)?;
+        txn.commit()
+    }
+
+    /// Iterate over all the quads of this dataset,
+    /// converting them to Sophia quads in parallel with `workers` threads.
+    ///
+    /// If `ordered` is true, quads are yielded in the same order as by `quads`;
+    /// otherwise, they are yielded as soon as they are converted.
+    /// This is only worth it for conversion-heavy datasets (e.g. with big literals).
+    ///
+    /// Only available with the `parallel` feature.
+    #[cfg(feature = "parallel")]
+    pub fn par_quads_buffered(&self, workers: usize, ordered: bool) -> ParQuads {
+        ParQuads::new(
+            self.conn.quads_for_pattern(None, None, None, None),
+            workers,
+            ordered,
+            self.bnode_cache.clone(),
+        )
+    }
+
+    /// Load a graph into the default graph, with Oxigraph's native parser.
+    ///
+    /// Parsed triples never go through the Sophia conversion layer,
+    /// so this is much faster than parsing with Sophia and inserting quads one by one,
+    /// and blank nodes are handled by Oxigraph.
+    /// Any syntax supported by Oxigraph can be used
+    /// (`GraphSyntax::NTriples`, `GraphSyntax::Turtle`, `GraphSyntax::RdfXml`).
+    ///
+    /// In dry-run mode, the graph is parsed into a scratch memory store,
+    /// and its triples are recorded as added.
+    pub fn load_from_reader<R: BufRead>(
+        &mut self,
+        reader: R,
+        format: GraphSyntax,
+        base: Option<&str>,
+    ) -> Result<(), OxigraphError> {
+        if self.dry_run.is_some() {
+            let tmp = MemoryRepository::default();
+            let mut tmp_conn = tmp.connection()?;
+            tmp_conn.load_graph(reader, format, None, base)?;
+            let quads = tmp_conn
+                .quads_for_pattern(None, None, None, None)
+                .collect::<Result<Vec<_>, _>>()?;
+            self.insert_oxi_batch(quads)?;
+            return Ok(());
+        }
+        self.conn.load_graph(reader, format, None, base)
+    }
+
+    /// Load a graph with Oxigraph's native parser, skipping triples that contain blank nodes.
+    ///
+    /// The other triples are inserted in the default graph, in a single transaction.
+    /// Return the number of skipped triples.
+    pub fn load_skip_bnodes<R: BufRead>(
+        &mut self,
+        reader: R,
+        syntax: GraphSyntax,
+        base_iri: Option<&str>,
+    ) -> Result<usize, OxigraphError> {
+        let tmp = MemoryRepository::default();
+        let mut tmp_conn = tmp.connection()?;
+        tmp_conn.load_graph(reader, syntax, None, base_iri)?;
+        let mut skipped = 0;
+        let mut ground = vec![];
+        for q in tmp_conn.quads_for_pattern(None, None, None, None) {
+            let q = q?;
+            if is_ground(&q) {
+                ground.push(q);
+            } else {
+                skipped += 1;
+            }
+        }
+        self.insert_oxi_batch(ground)?;
+        Ok(skipped)
+    }
+
+    /// Run a SPARQL update (e.g. `INSERT DATA`, `DELETE WHERE`...).
+    ///
+    /// This is much faster than a loop of `insert` or `remove` for bulk edits.
+    /// In dry-run mode, the update is run against an in-memory copy of the store,
+    /// and its effect is recorded in the [`DryRunReport`].
+    ///
+    /// [`DryRunReport`]: struct.DryRunReport.html
+    pub fn update(&mut self, update: &str) -> MDResult<Self, ()> {
+        if self.dry_run.is_some() {
+            self.dry_run_update(update)?;
+            return Ok(());
+        }
+        Ok(self.conn.update(update)?)
+    }
+
+    /// Remove all the quads of the graph named `g` (or of the default graph if `g` is `None`).
+    ///
+    /// The quads are removed in a single transaction,
+    /// or recorded as removed in dry-run mode.
+    pub fn clear_graph<T: TermData>(&mut self, g: Option<&Term<T>>) -> MDResult<Self, ()> {
+        let g = self.oxi_g(g)?;
+        let quads = self
+            .oxi_current_quads(&(None, None, None, Some(g)))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.remove_oxi_batch(quads)?;
+        Ok(())
+    }
+
+    /// Run a SPARQL update, and return the number of quads it (inserted, deleted).
+    ///
+    /// The counts are computed by comparing the content of the store before and after the update,
+    /// so this is more costly than [`update`](#method.update) on large stores.
+    /// In dry-run mode, the counts are those of the simulated update.
+    pub fn update_counted(&mut self, update: &str) -> MDResult<Self, (usize, usize)> {
+        if self.dry_run.is_some() {
+            return Ok(self.dry_run_update(update)?);
+        }
+        let before = self
+            .conn
+            .quads_for_pattern(None, None, None, None)
+            .collect::<Result<HashSet<_>, _>>()?;
+        self.update(update)?;
+        let mut deleted = before.len();
+        let mut inserted = 0;
+        for q in self.conn.quads_for_pattern(None, None, None, None) {
+            if before.contains(&q?) {
+                deleted -= 1;
+            } else {
+                inserted += 1;
+            }
+        }
+        Ok((inserted, deleted))
+    }
+
+    /// Replace every blank node of this dataset by a skolem IRI.
+    ///
+    /// The skolem IRI of a blank node is `{base}/.well-known/genid/{id}`,
+    /// where `id` is the identifier of the blank node in Oxigraph.
+    /// Return the number of rewritten quads.
+    /// Nothing is rewritten if `base` does not produce valid IRIs,
+    /// in which case [`ConversionError::InvalidIri`] is returned.
+    ///
+    /// See also [`deskolemize`](#method.deskolemize).
+    ///
+    /// [`ConversionError::InvalidIri`]: ../term/enum.ConversionError.html#variant.InvalidIri
+    pub fn skolemize(&mut self, base: &str) -> Result<usize, MutationError> {
+        let prefix = skolem_prefix(base)?;
+        // the prefix is a valid IRI, and so is its concatenation with a blank node id
+        let skolem = |b: &OBlankNode| NamedNode::new_unchecked(format!("{}{}", prefix, b.as_str()));
+        Ok(self.rewrite_quads(|q| {
+            if is_ground(q) {
+                return None;
+            }
+            let (s, p, o, g) = q.clone().destruct();
+            let s = match s {
+                NamedOrBlankNode::BlankNode(b) => skolem(&b).into(),
+                s => s,
+            };
+            let o = match o {
+                OTerm::BlankNode(b) => skolem(&b).into(),
+                o => o,
+            };
+            let g = g.map(|g| match g {
+                NamedOrBlankNode::BlankNode(b) => skolem(&b).into(),
+                g => g,
+            });
+            Some(OQuad::new(s, p, o, g))
+        })?)
+    }
+
+    /// Insert all the quads of `src`, replacing their blank nodes by fresh skolem IRIs.
+    ///
+    /// Each blank node label of `src` is mapped to a new IRI of the form
+    /// `{base}/.well-known/genid/{id}`, where `id` is random.
+    /// The mapping only holds for this call,
+    /// so importing several sources that reuse the same labels (e.g. `_:b0`)
+    /// never merges their blank nodes.
+    ///
+    /// As with [`insert_all`](#method.insert_all), quads are inserted in a single transaction.
+    /// Nothing is inserted if `base` does not produce valid IRIs,
+    /// in which case [`ConversionError::InvalidIri`] is returned.
+    ///
+    /// [`ConversionError::InvalidIri`]: ../term/enum.ConversionError.html#variant.InvalidIri
+    pub fn insert_skolemized<QS>(
+        &mut self,
+        mut src: QS,
+        base: &str,
+    ) -> StreamResult<usize, QS::Error, MutationError>
+    where
+        QS: QuadSource,
+    {
+        let prefix = skolem_prefix(base).map_err(|err| SinkError(err.into()))?;
+        let mut mapping: HashMap<String, NamedNode> = HashMap::new();
+        let mut quads = vec![];
+        src.try_for_each_quad(|q| -> MDResult<Self, ()> {
+            let mut skolem = |t: &Term<_>| match t {
+                Term::BNode(_) => Some(
+                    mapping
+                        .entry(t.value().to_string())
+                        .or_insert_with(|| {
+                            // the prefix is a valid IRI, and so is its concatenation with a blank node id
+                            let id = OBlankNode::default();
+                            NamedNode::new_unchecked(format!("{}{}", prefix, id.as_str()))
+                        })
+                        .clone(),
+                ),
+                _ => None,
+            };
+            let s = match skolem(q.s()) {
+                Some(n) => n.into(),
+                None => self.oxi_s(q.s())?,
+            };
+            let p = self.oxi_p(q.p())?;
+            let o = match skolem(q.o()) {
+                Some(n) => n.into(),
+                None => {
+                    let o = self.oxi_o(q.o())?;
+                    self.check_literal_len(&o)?;
+                    o
+                }
+            };
+            let g = match q.g() {
+                Some(g) => match skolem(g) {
+                    Some(n) => Some(n.into()),
+                    None => self.oxi_g(Some(g))?,
+                },
+                None => None,
+            };
+            quads.push(OQuad::new(s, p, o, g));
+            Ok(())
+        })?;
+        self.insert_oxi_batch(quads)
+            .map_err(|err| SinkError(err.into()))
+    }
+
+    /// Replace every skolem IRI produced by [`skolemize`](#method.skolemize) with `base`
+    /// by the corresponding blank node.
+    ///
+    /// Return the number of rewritten quads.
+    /// Nothing is rewritten if `base` does not produce valid IRIs,
+    /// in which case [`ConversionError::InvalidIri`] is returned.
+    ///
+    /// [`ConversionError::InvalidIri`]: ../term/enum.ConversionError.html#variant.InvalidIri
+    pub fn deskolemize(&mut self, base: &str) -> Result<usize, MutationError> {
+        let prefix = skolem_prefix(base)?;
+        let bnode = |n: &NamedNode| -> Option<OBlankNode> {
+            if n.as_str().starts_with(&prefix) {
+                SBlankNode::new_unchecked(&n.as_str()[prefix.len()..])
+                    .try_oxigraphize()
+                    .ok()
+            } else {
+                None
+            }
+        };
+        Ok(self.rewrite_quads(|q| {
+            let (s, p, o, g) = q.clone().destruct();
+            let mut changed = false;
+            let s = match s {
+                NamedOrBlankNode::NamedNode(n) => match bnode(&n) {
+                    Some(b) => {
+                        changed = true;
+                        b.into()
+                    }
+                    None => n.into(),
+                },
+                s => s,
+            };
+            let o = match o {
+                OTerm::NamedNode(n) => match bnode(&n) {
+                    Some(b) => {
+                        changed = true;
+                        b.into()
+                    }
+                    None => n.into(),
+                },
+                o => o,
+            };
+            let g = g.map(|g| match g {
+                NamedOrBlankNode::NamedNode(n) => match bnode(&n) {
+                    Some(b) => {
+                        changed = true;
+                        b.into()
+                    }
+                    None => n.into(),
+                },
+                g => g,
+            });
+            if changed {
+                Some(OQuad::new(s, p, o, g))
+            } else {
+                None
+            }
+        })?)
+    }
+
+    /// Replace, in a single transaction, each quad for which `f` returns a new quad.
+    ///
+    /// Return the number of replaced quads.
+    fn rewrite_quads<F>(&mut self, f: F) -> Result<usize, OxigraphError>
+    where
+        F: Fn(&OQuad) -> Option<OQuad>,
+    {
+        let mut changes = vec![];
+        for q in self.oxi_current_quads(&(None, None, None, None)) {
+            let q = q?;
+            if let Some(new) = f(&q) {
+                changes.push((q, new));
+            }
+        }
+        let count = changes.len();
+        if let Some(report) = &mut self.dry_run {
+            for (old, new) in changes {
+                report.remove(&self.conn, old)?;
+                report.insert(&self.conn, new)?;
+            }
+        } else if count > 0 {
+            self.conn.transaction(|t| {
+                for (old, new) in &changes {
+                    t.remove(old)?;
+                    t.insert(new)?;
+                }
+                Ok(())
+            })?;
+        }
+        Ok(count)
+    }
+
+    /// Turn this connection into an iterator over all its quads.
+    ///
+    /// Unlike [`quads`](#method.quads), the returned iterator owns the connection,
+    /// so it can be returned from a function or stored in a struct.
+    pub fn into_quads(self) -> IntoQuads<C> {
+        IntoQuads(IntoQuadsCell::new(self, |conn| {
+            let quads: Box<dyn Iterator<Item = Result<OQuad, OxigraphError>> + '_> =
+                Box::new(conn.conn.quads_for_pattern(None, None, None, None));
+            quads
+        }))
+    }
+
+    /// Remove all the quads of this dataset, yielding each of them.
+    ///
+    /// See [`drain_matching`](#method.drain_matching).
+    pub fn drain(&mut self) -> Result<Drain<C>, OxigraphError> {
+        self.drain_matching(&ANY, &ANY, &ANY, &ANY)
+    }
+
+    /// Remove all the quads matching the given matchers, yielding each of them.
+    ///
+    /// The matching quads are collected before the returned iterator is created,
+    /// and each of them is removed from the store when it is yielded.
+    /// Quads that are not yielded (e.g. because the iterator is dropped early)
+    /// are therefore not removed.
+    pub fn drain_matching<S, P, O, G>(
+        &mut self,
+        ms: &S,
+        mp: &P,
+        mo: &O,
+        mg: &G,
+    ) -> Result<Drain<C>, OxigraphError>
+    where
+        S: TermMatcher + ?Sized,
+        P: TermMatcher + ?Sized,
+        O: TermMatcher + ?Sized,
+        G: GraphNameMatcher + ?Sized,
+    {
+        let quads = self
+            .oxi_quads_matching(ms, mp, mo, mg)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Drain {
+            conn: self,
+            quads: quads.into_iter(),
+        })
+    }
+
+    /// Iterate over the Oxigraph quads matching the given matchers.
+    ///
+    /// Constant matchers are converted and passed to `quads_for_pattern`;
+    /// other matchers are used to filter its results.
+    pub(crate) fn oxi_quads_matching<'s, S, P, O, G>(
+        &'s self,
+        ms: &'s S,
+        mp: &'s P,
+        mo: &'s O,
+        mg: &'s G,
+    ) -> Box<dyn Iterator<Item = Result<OQuad, OxigraphError>> + 's>
+    where
+        S: TermMatcher + ?Sized,
+        P: TermMatcher + ?Sized,
+        O: TermMatcher + ?Sized,
+        G: GraphNameMatcher + ?Sized,
+    {
+        match self.oxi_matching_pattern(ms, mp, mo, mg) {
+            Ok(pattern) => {
+                let matches = oxi_quad_matcher(&pattern, ms, mp, mo, mg);
+                Box::new(
+                    self.oxi_quads_for_pattern(&pattern)
+                        .filter(move |r| match r {
+                            Ok(q) => matches(q),
+                            Err(_) => true,
+                        }),
+                )
+            }
+            Err(_) => Box::new(empty()),
+        }
+    }
+
+    /// Convert a pattern (as accepted by `oxi_quads_with`) into Oxigraph terms.
+    pub(crate) fn oxi_pattern<T, U, V, W>(
+        &self,
+        s: Option<&Term<T>>,
+        p: Option<&Term<U>>,
+        o: Option<&Term<V>>,
+        g: Option<Option<&Term<W>>>,
+    ) -> Result<OxiQuadPattern, ConversionError>
+    where
+        T: TermData,
+        U: TermData,
+        V: TermData,
+        W: TermData,
+    {
+        Ok((
+            s.map(|s| self.oxi_s(s)).transpose()?,
+            p.map(|p| self.oxi_p(p)).transpose()?,
+            o.map(|o| self.oxi_o(o)).transpose()?,
+            g.map(|g| self.oxi_g(g)).transpose()?,
+        ))
+    }
+
+    /// Convert the constant matchers among the given ones into an Oxigraph pattern.
+    ///
+    /// The quads matching that pattern must then be filtered with `oxi_quad_matcher`.
+    pub(crate) fn oxi_matching_pattern<S, P, O, G>(
+        &self,
+        ms: &S,
+        mp: &P,
+        mo: &O,
+        mg: &G,
+    ) -> Result<OxiQuadPattern, ConversionError>
+    where
+        S: TermMatcher + ?Sized,
+        P: TermMatcher + ?Sized,
+        O: TermMatcher + ?Sized,
+        G: GraphNameMatcher + ?Sized,
+    {
+        self.oxi_pattern(ms.constant(), mp.constant(), mo.constant(), mg.constant())
+    }
+
+    /// Iterate over the Oxigraph quads matching `pattern`.
+    pub(crate) fn oxi_quads_for_pattern(
+        &self,
+        pattern: &OxiQuadPattern,
+    ) -> Box<dyn Iterator<Item = Result<OQuad, OxigraphError>> + '_> {
+        let (s, p, o, g) = pattern;
+        Box::new(self.conn.quads_for_pattern(
+            s.as_ref(),
+            p.as_ref(),
+            o.as_ref(),
+            g.as_ref().map(Option::as_ref),
+        ))
+    }
+
+    /// Iterate over the Oxigraph quads matching `pattern`,
+    /// taking into account the changes recorded in dry-run mode.
+    fn oxi_current_quads(
+        &self,
+        pattern: &OxiQuadPattern,
+    ) -> Box<dyn Iterator<Item = Result<OQuad, OxigraphError>> + '_> {
+        let stored = self.oxi_quads_for_pattern(pattern);
+        match &self.dry_run {
+            None => stored,
+            Some(report) => {
+                let added: Vec<OQuad> = report
+                    .added
+                    .iter()
+                    .filter(|q| oxi_pattern_matches(pattern, q))
+                    .cloned()
+                    .collect();
+                Box::new(
+                    stored
+                        .filter(move |r| match r {
+                            Ok(q) => !report.removed.contains(q),
+                            Err(_) => true,
+                        })
+                        .chain(added.into_iter().map(Ok)),
+                )
+            }
+        }
+    }
+
+    /// Whether the store contains `quad`,
+    /// taking into account the changes recorded in dry-run mode.
+    fn oxi_contains(&self, quad: &OQuad) -> Result<bool, OxigraphError> {
+        match &self.dry_run {
+            Some(report) => report.contains(&self.conn, quad),
+            None => self.conn.contains(quad),
+        }
+    }
+
+    /// Simulate a SPARQL update on an in-memory copy of the store,
+    /// and record its effect in the dry-run report.
+    ///
+    /// Return the number of quads it (inserted, deleted).
+    fn dry_run_update(&mut self, update: &str) -> Result<(usize, usize), OxigraphError> {
+        let before = self
+            .oxi_current_quads(&(None, None, None, None))
+            .collect::<Result<HashSet<_>, _>>()?;
+        let scratch = MemoryRepository::default();
+        let mut scratch_conn = scratch.connection()?;
+        scratch_conn.transaction(|t| {
+            for quad in &before {
+                t.insert(quad)?;
+            }
+            Ok(())
+        })?;
+        scratch_conn.update(update)?;
+        let after = scratch_conn
+            .quads_for_pattern(None, None, None, None)
+            .collect::<Result<HashSet<_>, _>>()?;
+        let mut changes: Vec<(OQuad, bool)> = after
+            .difference(&before)
+            .map(|q| (q.clone(), true))
+            .collect();
+        let inserted = changes.len();
+        changes.extend(before.difference(&after).map(|q| (q.clone(), false)));
+        let deleted = changes.len() - inserted;
+        self.apply_oxi_changes(changes)?;
+        Ok((inserted, deleted))
+    }
+
+    /// Insert an Oxigraph quad, or record it in dry-run mode.
+    ///
+    /// Return `false` if the quad was already in the store.
+    fn insert_oxi(&mut self, quad: OQuad) -> Result<bool, OxigraphError> {
+        if let Some(report) = &mut self.dry_run {
+            return report.insert(&self.conn, quad);
+        }
+        if self.conn.contains(&quad)? {
+            return Ok(false);
+        }
+        self.conn.insert(&quad)?;
+        Ok(true)
+    }
+
+    /// Insert a batch of Oxigraph quads in a single transaction,
+    /// or record them in dry-run mode.
+    ///
+    /// Return the number of quads that were not already in the store.
+    pub(crate) fn insert_oxi_batch<I>(&mut self, quads: I) -> Result<usize, OxigraphError>
+    where
+        I: IntoIterator<Item = OQuad>,
+    {
+        if let Some(report) = &mut self.dry_run {
+            let mut count = 0;
+            for quad in quads {
+                if report.insert(&self.conn, quad)? {
+                    count += 1;
+                }
+            }
+            return Ok(count);
+        }
+        let mut new = HashSet::new();
+        for quad in quads {
+            if !self.conn.contains(&quad)? {
+                new.insert(quad);
+            }
+        }
+        let count = new.len();
+        if count > 0 {
+            self.conn.transaction(|t| {
+                for quad in &new {
+                    t.insert(quad)?;
+                }
+                Ok(())
+            })?;
+        }
+        Ok(count)
+    }
+
+    /// Remove a batch of Oxigraph quads in a single transaction,
+    /// or record their removal in dry-run mode.
+    ///
+    /// Return the number of quads that were actually in the store.
+    fn remove_oxi_batch<I>(&mut self, quads: I) -> Result<usize, OxigraphError>
+    where
+        I: IntoIterator<Item = OQuad>,
+    {
+        if let Some(report) = &mut self.dry_run {
+            let mut count = 0;
+            for quad in quads {
+                if report.remove(&self.conn, quad)? {
+                    count += 1;
+                }
+            }
+            return Ok(count);
+        }
+        let mut present = HashSet::new();
+        for quad in quads {
+            if self.conn.contains(&quad)? {
+                present.insert(quad);
+            }
+        }
+        let count = present.len();
+        if count > 0 {
+            self.conn.transaction(|t| {
+                for quad in &present {
+                    t.remove(quad)?;
+                }
+                Ok(())
+            })?;
+        }
+        Ok(count)
+    }
+
+    /// Apply a sequence of insertions (`true`) and removals (`false`)
+    /// in a single transaction, or record them in dry-run mode.
+    pub(crate) fn apply_oxi_changes(
         &mut self,
+        changes: Vec<(OQuad, bool)>,
+    ) -> Result<(), OxigraphError> {
+        if let Some(report) = &mut self.dry_run {
+            for (quad, insert) in changes {
+                if insert {
+                    report.insert(&self.conn, quad)?;
+                } else {
+                    report.remove(&self.conn, quad)?;
+                }
+            }
+        } else if !changes.is_empty() {
+            self.conn.transaction(|t| {
+                for (quad, insert) in &changes {
+                    if *insert {
+                        t.insert(quad)?;
+                    } else {
+                        t.remove(quad)?;
+                    }
+                }
+                Ok(())
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Remove an Oxigraph quad, or record its removal in dry-run mode.
+    ///
+    /// Return `false` if the quad was not in the store.
+    fn remove_oxi(&mut self, quad: OQuad) -> Result<bool, OxigraphError> {
+        if let Some(report) = &mut self.dry_run {
+            return report.remove(&self.conn, quad);
+        }
+        if !self.conn.contains(&quad)? {
+            return Ok(false);
+        }
+        self.conn.remove(&quad)?;
+        Ok(true)
+    }
+
+    /// Convert Sophia terms into an Oxigraph quad to be inserted.
+    pub(crate) fn oxi_quad<T, U, V, W>(
+        &self,
         s: &Term<T>,
         p: &Term<U>,
         o: &Term<V>,
         g: Option<&Term<W>>,
-    ) -> MDResult<Self, bool>
+    ) -> Result<OQuad, ConversionError>
     where
         T: TermData,
         U: TermData,
         V: TermData,
         W: TermData,
     {
-        let s: NamedOrBlankNode = s.try_oxigraphize()?;
-        let p: NamedNode = p.try_oxigraphize()?;
-        let o: OTerm = o.try_oxigraphize()?;
-        let g = try_oxi_graphname(g)?;
-        self.0.insert(&OQuad::new(s, p, o, g))?;
-        Ok(true) // TODO: this may not be accurate
+        let s = self.oxi_s(s)?;
+        let p = self.oxi_p(p)?;
+        let o = self.oxi_o(o)?;
+        self.check_literal_len(&o)?;
+        let g = self.oxi_g(g)?;
+        Ok(OQuad::new(s, p, o, g))
     }
 
-    fn remove<T, U, V, W>(
+    /// Convert Sophia terms into an Oxigraph quad to be inserted,
+    /// substituting placeholders for unrepresentable terms if required.
+    ///
+    /// Variables are always rejected.
+    pub(crate) fn oxi_quad_or_placeholders<T, U, V, W>(
         &mut self,
         s: &Term<T>,
         p: &Term<U>,
         o: &Term<V>,
         g: Option<&Term<W>>,
-    ) -> MDResult<Self, bool>
+    ) -> Result<OQuad, ConversionError>
     where
         T: TermData,
         U: TermData,
         V: TermData,
         W: TermData,
     {
-        let s: Result<NamedOrBlankNode, _> = s.try_oxigraphize();
-        let p: Result<NamedNode, _> = p.try_oxigraphize();
-        let o: Result<OTerm, _> = o.try_oxigraphize();
-        let g = try_oxi_graphname(g);
-        if let (Ok(s), Ok(p), Ok(o), Ok(g)) = (s, p, o, g) {
-            self.0.remove(&OQuad::new(s, p, o, g))?;
-            Ok(true) // TODO: this may not be accurate
-        } else {
-            Ok(false)
+        // variables can not be stored, whatever the converter or placeholder settings
+        let terms = [
+            Some(s.as_ref_str()),
+            Some(p.as_ref_str()),
+            Some(o.as_ref_str()),
+            g.map(Term::as_ref_str),
+        ];
+        for t in terms.iter().flatten() {
+            if let Term::Variable(v) = t {
+                return Err(ConversionError::Variable(v.as_str().to_string()));
+            }
         }
+        let base = match &self.placeholder_base {
+            None => return self.oxi_quad(s, p, o, g),
+            Some(base) => base.clone(),
+        };
+        let mut substitutions = vec![];
+        let mut placeholder = |original: String| -> Result<NamedNode, ConversionError> {
+            let iri = format!("{}{:032x}", base, hash_str(&original));
+            let iri = NamedNode::new(iri.as_str()).map_err(|_| ConversionError::InvalidIri(iri))?;
+            substitutions.push(Substitution {
+                original,
+                placeholder: iri.clone().into_sophia(),
+            });
+            Ok(iri)
+        };
+        let s = match self.oxi_s(s) {
+            Ok(s) => s,
+            Err(_) => placeholder(s.to_string())?.into(),
+        };
+        let p = match self.oxi_p(p) {
+            Ok(p) => p,
+            Err(_) => placeholder(p.to_string())?,
+        };
+        let o = match self
+            .oxi_o(o)
+            .and_then(|o| self.check_literal_len(&o).map(|_| o))
+        {
+            Ok(o) => o,
+            Err(_) => placeholder(o.to_string())?.into(),
+        };
+        let g = match (self.oxi_g(g), g) {
+            (Ok(g), _) => g,
+            (Err(_), Some(g)) => Some(placeholder(g.to_string())?.into()),
+            (Err(err), None) => return Err(err),
+        };
+        self.substitutions.extend(substitutions);
+        Ok(OQuad::new(s, p, o, g))
     }
 
-    // TODO implement other methods (using SPARQL under the hood)
-}
+    /// Convert Oxigraph Quad to Sophia Quad
+    #[inline]
+    fn bridge<'a>(
+        &self,
+        r: Result<OQuad, OxigraphError>,
+    ) -> Result<StreamedQuad<'a, ByValue<QuadBridge>>, OxigraphError> {
+        r.map(|q| StreamedQuad::by_value(self.quad_bridge(q)))
+    }
 
-#[inline]
-/// Shortcut function to convert Oxigraph Quad to Sophia Quad
-fn bridge<'a>(
-    r: Result<OQuad, OxigraphError>,
-) -> Result<StreamedQuad<'a, ByValue<QuadBridge>>, OxigraphError> {
-    r.map(|q| StreamedQuad::by_value(QuadBridge::new(q)))
-}
+    /// Wrap an Oxigraph quad, restoring blank node labels from the cache if any.
+    pub(crate) fn quad_bridge(&self, q: OQuad) -> QuadBridge {
+        match &self.bnode_cache {
+            None => QuadBridge::new(q),
+            Some(cache) => QuadBridge::with_bnode_labels(q, &cache.lock().unwrap()),
+        }
+    }
 
-#[inline]
-/// Shortcut function to convert Sophia graph name to Oxigraph graph name
-fn try_oxi_graphname<T: TermData>(
-    g: Option<&Term<T>>,
-) -> Result<Option<NamedOrBlankNode>, ConversionError> {
-    g.map(|g| g.try_oxigraphize()).transpose()
-}
+    /// Convert a blank node through the blank node cache, if any.
+    fn cached_bnode<T: TermData>(&self, t: &Term<T>) -> Option<OBlankNode> {
+        match (&self.bnode_cache, t) {
+            (Some(cache), Term::BNode(b)) => Some(cache.lock().unwrap().oxigraphize(b)),
+            _ => None,
+        }
+    }
 
-#[inline]
-/// Convert the result of a SPARQL query into a term set
-///
-/// # Precondition
-/// + the query must be a SELECT query with a single selected variable
-/// + it must not produce NULL results
-fn sparql_result_as_term_set(r: QueryResult) -> Result<HashSet<Term<String>>, OxigraphError> {
-    if let QueryResult::Bindings(b) = r {
-        b.into_values_iter()
-            .map(|r| r.map(|mut v| v.pop().unwrap().unwrap().as_sophia()))
-            .collect()
-    } else {
-        unreachable!()
+    /// Convert a Sophia term in subject position into an Oxigraph term.
+    fn oxi_s<T: TermData>(&self, s: &Term<T>) -> Result<NamedOrBlankNode, ConversionError> {
+        if let Some(b) = self.cached_bnode(s) {
+            return Ok(b.into());
+        }
+        match &self.converter {
+            None => s.try_oxigraphize(),
+            Some(c) => c.subject(&s.as_ref_str()),
+        }
     }
-}
 
-/// Mutation error for the Oxigraph-to-Sophia adapter
-#[derive(Debug, Error)]
-pub enum MutationError {
-    /// Error from Oxigraph
-    #[error("{source}")]
-    Oxigraph {
-        /// The source of this error
-        #[from]
-        source: OxigraphError,
-    },
-    /// Error from term conversion
-    #[error("Conversion: {source}")]
-    Conversion {
-        /// The source of this error
-        #[from]
-        source: ConversionError,
-    },
-}
+    /// Convert a Sophia term in predicate position into an Oxigraph term.
+    fn oxi_p<T: TermData>(&self, p: &Term<T>) -> Result<NamedNode, ConversionError> {
+        match &self.converter {
+            None => p.try_oxigraphize(),
+            Some(c) => c.predicate(&p.as_ref_str()),
+        }
+    }
 
-impl From<std::convert::Infallible> for MutationError {
-    fn from(_: std::convert::Infallible) -> Self {
-        unreachable!()
+    /// Convert a Sophia term in object position into an Oxigraph term.
+    fn oxi_o<T: TermData>(&self, o: &Term<T>) -> Result<OTerm, ConversionError> {
+        if let Some(b) = self.cached_bnode(o) {
+            return Ok(b.into());
+        }
+        match (&self.converter, o) {
+            (None, Term::Literal(lit)) if self.unchecked_language_tags && lit.lang().is_some() => {
+                Ok(oxigraphize_literal_unchecked(lit)?.into())
+            }
+            (None, _) => o.try_oxigraphize(),
+            (Some(c), _) => c.object(&o.as_ref_str()),
+        }
+    }
+
+    /// Convert a Sophia graph name into an Oxigraph graph name.
+    ///
+    /// The default graph IRI, if any, is mapped to the default graph.
+    fn oxi_g<T: TermData>(
+        &self,
+        g: Option<&Term<T>>,
+    ) -> Result<Option<NamedOrBlankNode>, ConversionError> {
+        if let Some(b) = g.and_then(|g| self.cached_bnode(g)) {
+            return Ok(Some(b.into()));
+        }
+        let g = match &self.converter {
+            None => try_oxi_graphname(g)?,
+            Some(c) => g.map(|g| c.graph_name(&g.as_ref_str())).transpose()?,
+        };
+        match (&self.default_graph_iri, g) {
+            (Some(dg), Some(NamedOrBlankNode::NamedNode(n))) if &n == dg => Ok(None),
+            (_, g) => Ok(g),
+        }
+    }
+
+    /// Check that `o`, if it is a literal, does not exceed `max_literal_len`.
+    fn check_literal_len(&self, o: &OTerm) -> Result<(), ConversionError> {
+        if let (Some(max), OTerm::Literal(lit)) = (self.max_literal_len, o) {
+            let len = lit.value().len();
+            if len > max {
+                return Err(ConversionError::LiteralTooLong(len, max));
+            }
+        }
+        Ok(())
     }
 }
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use oxigraph::{MemoryRepository, Repository};
-    use sophia_term::matcher::ANY;
+impl<C> Dataset for SophiaConnection<C>
+where
+    C: RepositoryConnection,
+{
+    type Quad = ByValue<QuadBridge>;
+    type Error = OxigraphError;
 
-    lazy_static::lazy_static! {
-        pub static ref REP: MemoryRepository = MemoryRepository::default();
+    fn quads(&self) -> DQuadSource<Self> {
+        Box::new(
+            self.conn
+                .quads_for_pattern(None, None, None, None)
+                .map(move |r| self.bridge(r)),
+        )
     }
 
-    type MemRepRef = &'static MemoryRepository;
-    type ConDataset = SophiaConnection<<MemRepRef as Repository>::Connection>;
+    fn quads_with_s<'s, T>(&'s self, s: &'s Term<T>) -> DQuadSource<'s, Self>
+    where
+        T: TermData,
+    {
+        match self.oxi_s(s) {
+            Ok(s) => Box::new(
+                self.conn
+                    .quads_for_pattern(Some(&s), None, None, None)
+                    .map(move |r| self.bridge(r)),
+            ),
+            Err(_) => Box::new(empty()),
+        }
+    }
 
-    #[allow(dead_code)]
-    fn make_dataset() -> ConDataset {
-        let mut conn = SophiaConnection(REP.connection().unwrap());
-        conn.remove_matching(&ANY, &ANY, &ANY, &ANY).unwrap();
-        conn
+    fn quads_with_p<'s, T>(&'s self, p: &'s Term<T>) -> DQuadSource<'s, Self>
+    where
+        T: TermData,
+    {
+        match self.oxi_p(p) {
+            Ok(p) => Box::new(
+                self.conn
+                    .quads_for_pattern(None, Some(&p), None, None)
+                    .map(move |r| self.bridge(r)),
+            ),
+            Err(_) => Box::new(empty()),
+        }
     }
 
-    // These tests only work if options "-- --test-threads 1" is provided to cargo test,
-    // because they share a single repository REP.
-    //sophia::test_dataset_impl!(auto, ConDataset, false, make_dataset, false);
+    fn quads_with_o<'s, T>(&'s self, o: &'s Term<T>) -> DQuadSource<'s, Self>
+    where
+        T: TermData,
+    {
+        match self.oxi_o(o) {
+            Ok(o) => Box::new(
+                self.conn
+                    .quads_for_pattern(None, None, Some(&o), None)
+                    .map(move |r| self.bridge(r)),
+            ),
+            Err(_) => Box::new(empty()),
+        }
+    }
 
-    // Anyway, they are not strictly required:
-    // SophiaConnection is tested trough SophiaRepository,
-    // which simply delegates all Dataset methods to the underlying SophiaConnection.
+    fn quads_with_g<'s, T>(&'s self, g: Option<&'s Term<T>>) -> DQuadSource<'s, Self>
+    where
+        T: TermData,
+    {
+        match self.oxi_g(g) {
+            Ok(g) => Box::new(
+                self.conn
+                    .quads_for_pattern(None, None, None, Some(g.as_ref()))
+                    .map(move |r| self.bridge(r)),
+            ),
+            Err(_) => Box::new(empty()),
+        }
+    }
+
+    fn quads_with_sp<'s, T, U>(&'s self, s: &'s Term<T>, p: &'s Term<U>) -> DQuadSource<'s, Self>
+    where
+        T: TermData,
+        U: TermData,
+    {
+        match (self.oxi_s(s), self.oxi_p(p)) {
+            (Ok(s), Ok(p)) => Box::new(
+                self.conn
+                    .quads_for_pattern(Some(&s), Some(&p), None, None)
+                    .map(move |r| self.bridge(r)),
+            ),
+            _ => Box::new(empty()),
+        }
+    }
+
+    fn quads_with_so<'s, T, U>(&'s self, s: &'s Term<T>, o: &'s Term<U>) -> DQuadSource<'s, Self>
+    where
+        T: TermData,
+        U: TermData,
+    {
+        match (self.oxi_s(s), self.oxi_o(o)) {
+            (Ok(s), Ok(o)) => Box::new(
+                self.conn
+                    .quads_for_pattern(Some(&s), None, Some(&o), None)
+                    .map(move |r| self.bridge(r)),
+            ),
+            _ => Box::new(empty()),
+        }
+    }
+
+    fn quads_with_sg<'s, T, U>(
+        &'s self,
+        s: &'s Term<T>,
+        g: Option<&'s Term<U>>,
+    ) -> DQuadSource<'s, Self>
+    where
+        T: TermData,
+        U: TermData,
+    {
+        match (self.oxi_s(s), self.oxi_g(g)) {
+            (Ok(s), Ok(g)) => Box::new(
+                self.conn
+                    .quads_for_pattern(Some(&s), None, None, Some(g.as_ref()))
+                    .map(move |r| self.bridge(r)),
+            ),
+            _ => Box::new(empty()),
+        }
+    }
+
+    fn quads_with_po<'s, T, U>(&'s self, p: &'s Term<T>, o: &'s Term<U>) -> DQuadSource<'s, Self>
+    where
+        T: TermData,
+        U: TermData,
+    {
+        match (self.oxi_p(p), self.oxi_o(o)) {
+            (Ok(p), Ok(o)) => Box::new(
+                self.conn
+                    .quads_for_pattern(None, Some(&p), Some(&o), None)
+                    .map(move |r| self.bridge(r)),
+            ),
+            _ => Box::new(empty()),
+        }
+    }
+
+    fn quads_with_pg<'s, T, U>(
+        &'s self,
+        p: &'s Term<T>,
+        g: Option<&'s Term<U>>,
+    ) -> DQuadSource<'s, Self>
+    where
+        T: TermData,
+        U: TermData,
+    {
+        match (self.oxi_p(p), self.oxi_g(g)) {
+            (Ok(p), Ok(g)) => Box::new(
+                self.conn
+                    .quads_for_pattern(None, Some(&p), None, Some(g.as_ref()))
+                    .map(move |r| self.bridge(r)),
+            ),
+            _ => Box::new(empty()),
+        }
+    }
+
+    fn quads_with_og<'s, T, U>(
+        &'s self,
+        o: &'s Term<T>,
+        g: Option<&'s Term<U>>,
+    ) -> DQuadSource<'s, Self>
+    where
+        T: TermData,
+        U: TermData,
+    {
+        match (self.oxi_o(o), self.oxi_g(g)) {
+            (Ok(o), Ok(g)) => Box::new(
+                self.conn
+                    .quads_for_pattern(None, None, Some(&o), Some(g.as_ref()))
+                    .map(move |r| self.bridge(r)),
+            ),
+            _ => Box::new(empty()),
+        }
+    }
+
+    fn quads_with_spo<'s, T, U, V>(
+        &'s self,
+        s: &'s Term<T>,
+        p: &'s Term<U>,
+        o: &'s Term<V>,
+    ) -> DQuadSource<'s, Self>
+    where
+        T: TermData,
+        U: TermData,
+        V: TermData,
+    {
+        match (self.oxi_s(s), self.oxi_p(p), self.oxi_o(o)) {
+            (Ok(s), Ok(p), Ok(o)) => Box::new(
+                self.conn
+                    .quads_for_pattern(Some(&s), Some(&p), Some(&o), None)
+                    .map(move |r| self.bridge(r)),
+            ),
+            _ => Box::new(empty()),
+        }
+    }
+
+    fn quads_with_spg<'s, T, U, V>(
+        &'s self,
+        s: &'s Term<T>,
+        p: &'s Term<U>,
+        g: Option<&'s Term<V>>,
+    ) -> DQuadSource<'s, Self>
+    where
+        T: TermData,
+        U: TermData,
+        V: TermData,
+    {
+        match (self.oxi_s(s), self.oxi_p(p), self.oxi_g(g)) {
+            (Ok(s), Ok(p), Ok(g)) => Box::new(
+                self.conn
+                    .quads_for_pattern(Some(&s), Some(&p), None, Some(g.as_ref()))
+                    .map(move |r| self.bridge(r)),
+            ),
+            _ => Box::new(empty()),
+        }
+    }
+
+    fn quads_with_sog<'s, T, U, V>(
+        &'s self,
+        s: &'s Term<T>,
+        o: &'s Term<U>,
+        g: Option<&'s Term<V>>,
+    ) -> DQuadSource<'s, Self>
+    where
+        T: TermData,
+        U: TermData,
+        V: TermData,
+    {
+        match (self.oxi_s(s), self.oxi_o(o), self.oxi_g(g)) {
+            (Ok(s), Ok(o), Ok(g)) => Box::new(
+                self.conn
+                    .quads_for_pattern(Some(&s), None, Some(&o), Some(g.as_ref()))
+                    .map(move |r| self.bridge(r)),
+            ),
+            _ => Box::new(empty()),
+        }
+    }
+
+    fn quads_with_pog<'s, T, U, V>(
+        &'s self,
+        p: &'s Term<T>,
+        o: &'s Term<U>,
+        g: Option<&'s Term<V>>,
+    ) -> DQuadSource<'s, Self>
+    where
+        T: TermData,
+        U: TermData,
+        V: TermData,
+    {
+        match (self.oxi_p(p), self.oxi_o(o), self.oxi_g(g)) {
+            (Ok(p), Ok(o), Ok(g)) => Box::new(
+                self.conn
+                    .quads_for_pattern(None, Some(&p), Some(&o), Some(g.as_ref()))
+                    .map(move |r| self.bridge(r)),
+            ),
+            _ => Box::new(empty()),
+        }
+    }
+
+    fn quads_with_spog<'s, T, U, V, W>(
+        &'s self,
+        s: &'s Term<T>,
+        p: &'s Term<U>,
+        o: &'s Term<V>,
+        g: Option<&'s Term<W>>,
+    ) -> DQuadSource<'s, Self>
+    where
+        T: TermData,
+        U: TermData,
+        V: TermData,
+        W: TermData,
+    {
+        match (self.oxi_s(s), self.oxi_p(p), self.oxi_o(o), self.oxi_g(g)) {
+            // literals stored by value may be read back in a different (canonical) form,
+            // so the stored quad is retrieved rather than rebuilt from the converted terms
+            (Ok(s), Ok(p), Ok(o), Ok(g)) if is_stored_by_value(&o) => Box::new(
+                self.conn
+                    .quads_for_pattern(Some(&s), Some(&p), Some(&o), Some(g.as_ref()))
+                    .map(move |r| self.bridge(r)),
+            ),
+            (Ok(s), Ok(p), Ok(o), Ok(g)) => {
+                let quad = OQuad::new(s, p, o, g);
+                match self.conn.contains(&quad) {
+                    Ok(true) => Box::new(once(self.bridge(Ok(quad)))),
+                    Ok(false) => Box::new(empty()),
+                    Err(err) => Box::new(once(Err(err))),
+                }
+            }
+            _ => Box::new(empty()),
+        }
+    }
+
+    /// Constant matchers are pushed down to Oxigraph,
+    /// so that only the quads matching them are retrieved;
+    /// other matchers are used to filter those quads.
+    fn quads_matching<'s, S, P, O, G>(
+        &'s self,
+        ms: &'s S,
+        mp: &'s P,
+        mo: &'s O,
+        mg: &'s G,
+    ) -> DQuadSource<'s, Self>
+    where
+        S: TermMatcher + ?Sized,
+        P: TermMatcher + ?Sized,
+        O: TermMatcher + ?Sized,
+        G: GraphNameMatcher + ?Sized,
+    {
+        Box::new(
+            self.oxi_quads_matching(ms, mp, mo, mg)
+                .map(move |r| self.bridge(r)),
+        )
+    }
+
+    fn contains<T, U, V, W>(
+        &self,
+        s: &Term<T>,
+        p: &Term<U>,
+        o: &Term<V>,
+        g: Option<&Term<W>>,
+    ) -> DResult<Self, bool>
+    where
+        T: TermData,
+        U: TermData,
+        V: TermData,
+        W: TermData,
+    {
+        match (self.oxi_s(s), self.oxi_p(p), self.oxi_o(o), self.oxi_g(g)) {
+            (Ok(s), Ok(p), Ok(o), Ok(g)) => self.oxi_contains(&OQuad::new(s, p, o, g)),
+            _ => Ok(false),
+        }
+    }
+
+    fn subjects(&self) -> DResultTermSet<Self> {
+        self.cached_term_set("SELECT DISTINCT ?s {{?s ?p ?o} UNION { GRAPH ?g {?s ?p ?o}}}")
+    }
+
+    fn predicates(&self) -> DResultTermSet<Self> {
+        self.cached_term_set("SELECT DISTINCT ?p {{?s ?p ?o} UNION { GRAPH ?g {?s ?p ?o}}}")
+    }
+
+    fn objects(&self) -> DResultTermSet<Self> {
+        self.cached_term_set("SELECT DISTINCT ?o {{?s ?p ?o} UNION { GRAPH ?g {?s ?p ?o}}}")
+    }
+
+    fn graph_names(&self) -> DResultTermSet<Self> {
+        self.cached_term_set("SELECT DISTINCT ?g {GRAPH ?g {?s ?p ?o}}")
+    }
+
+    fn iris(&self) -> DResultTermSet<Self> {
+        self.cached_term_set(IRIS_QUERY)
+    }
+
+    fn bnodes(&self) -> DResultTermSet<Self> {
+        self.cached_term_set("SELECT DISTINCT ?bn {{?bn ?p ?o} UNION {?s ?p ?bn} UNION {GRAPH ?bn {?s ?p ?o}} UNION {GRAPH ?s {?bn ?p ?o}} UNION {GRAPH ?g {?s ?p ?bn}} FILTER isBlank(?bn)}")
+    }
+
+    fn literals(&self) -> DResultTermSet<Self> {
+        self.cached_term_set(LITERALS_QUERY)
+    }
+
+    /// Oxigraph can not store variables (and `insert` rejects them),
+    /// so this is always empty.
+    fn variables(&self) -> DResultTermSet<Self> {
+        Ok(HashSet::new())
+    }
+}
+
+impl<C> MutableDataset for SophiaConnection<C>
+where
+    C: RepositoryConnection,
+{
+    type MutationError = MutationError;
+
+    fn insert<T, U, V, W>(
+        &mut self,
+        s: &Term<T>,
+        p: &Term<U>,
+        o: &Term<V>,
+        g: Option<&Term<W>>,
+    ) -> MDResult<Self, bool>
+    where
+        T: TermData,
+        U: TermData,
+        V: TermData,
+        W: TermData,
+    {
+        let quad = self.oxi_quad_or_placeholders(s, p, o, g)?;
+        Ok(self.insert_oxi(quad)?)
+    }
+
+    fn remove<T, U, V, W>(
+        &mut self,
+        s: &Term<T>,
+        p: &Term<U>,
+        o: &Term<V>,
+        g: Option<&Term<W>>,
+    ) -> MDResult<Self, bool>
+    where
+        T: TermData,
+        U: TermData,
+        V: TermData,
+        W: TermData,
+    {
+        let s = self.oxi_s(s);
+        let p = self.oxi_p(p);
+        let o = self.oxi_o(o);
+        let g = self.oxi_g(g);
+        if let (Ok(s), Ok(p), Ok(o), Ok(g)) = (s, p, o, g) {
+            Ok(self.remove_oxi(OQuad::new(s, p, o, g))?)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// All the quads of `src` are converted before any of them is inserted,
+    /// so a conversion error leaves the store unchanged.
+    /// It is reported as [`MutationError::QuadConversion`],
+    /// with the index of the offending quad in `src`.
+    /// The quads are then inserted in a single transaction.
+    ///
+    /// This is also what `QuadSource::add_to_dataset` relies on,
+    /// so the output of a Sophia parser can be loaded with a single call.
+    ///
+    /// In [lenient mode](struct.SophiaConnection.html#method.with_conversion_mode),
+    /// quads that are only valid in generalized RDF are skipped instead.
+    ///
+    /// [`MutationError::QuadConversion`]: enum.MutationError.html#variant.QuadConversion
+    fn insert_all<QS>(&mut self, mut src: QS) -> StreamResult<usize, QS::Error, MutationError>
+    where
+        QS: QuadSource,
+    {
+        let mut quads = vec![];
+        let mut index = 0;
+        src.try_for_each_quad(|q| -> MDResult<Self, ()> {
+            let res = self.oxi_quad_or_placeholders(q.s(), q.p(), q.o(), q.g());
+            index += 1;
+            match res {
+                Ok(quad) => quads.push(quad),
+                Err(source)
+                    if source.is_generalized()
+                        && self.conversion_mode == ConversionMode::Lenient =>
+                {
+                    log::warn!("Skipping quad #{}: {}", index - 1, source);
+                }
+                Err(source) => {
+                    return Err(MutationError::QuadConversion {
+                        index: index - 1,
+                        source,
+                    })
+                }
+            }
+            Ok(())
+        })?;
+        self.insert_oxi_batch(quads)
+            .map_err(|err| SinkError(err.into()))
+    }
+
+    /// All the quads of `src` are converted before any of them is removed,
+    /// so a conversion error leaves the store unchanged.
+    /// The quads are then removed in a single transaction.
+    fn remove_all<QS>(&mut self, mut src: QS) -> StreamResult<usize, QS::Error, MutationError>
+    where
+        QS: QuadSource,
+    {
+        let mut quads = vec![];
+        src.try_for_each_quad(|q| -> MDResult<Self, ()> {
+            quads.push(OQuad::new(
+                self.oxi_s(q.s())?,
+                self.oxi_p(q.p())?,
+                self.oxi_o(q.o())?,
+                self.oxi_g(q.g())?,
+            ));
+            Ok(())
+        })?;
+        self.remove_oxi_batch(quads)
+            .map_err(|err| SinkError(err.into()))
+    }
+
+    /// The matching quads are retrieved as in `quads_matching`
+    /// (constant matchers are pushed down to Oxigraph),
+    /// then removed in a single transaction.
+    ///
+    /// NB: a SPARQL `DELETE WHERE` could only express constant matchers,
+    /// and would not report the number of removed quads.
+    fn remove_matching<S, P, O, G>(
+        &mut self,
+        ms: &S,
+        mp: &P,
+        mo: &O,
+        mg: &G,
+    ) -> MDResult<Self, usize>
+    where
+        S: TermMatcher + ?Sized,
+        P: TermMatcher + ?Sized,
+        O: TermMatcher + ?Sized,
+        G: GraphNameMatcher + ?Sized,
+    {
+        let pattern = match self.oxi_matching_pattern(ms, mp, mo, mg) {
+            Ok(pattern) => pattern,
+            Err(_) => return Ok(0),
+        };
+        let matches = oxi_quad_matcher(&pattern, ms, mp, mo, mg);
+        let quads = self
+            .oxi_current_quads(&pattern)
+            .filter(|r| match r {
+                Ok(q) => matches(q),
+                Err(_) => true,
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(self.remove_oxi_batch(quads)?)
+    }
+
+    // TODO implement other methods (using SPARQL under the hood)
+}
+
+// Shared references to a connection are datasets as well,
+// so that `&conn` can be passed where an `impl Dataset` is expected.
+delegate_dataset!(
+    impl['a, C] &'a SophiaConnection<C> where [C: RepositoryConnection],
+    deref(|this| *this)
+);
+
+/// Expose a single graph of an Oxigraph Connection as a Sophia Graph
+///
+/// It is obtained with [`SophiaConnection::graph`].
+/// All the quads read or written through it belong to the graph it was created with.
+///
+/// [`SophiaConnection::graph`]: struct.SophiaConnection.html#method.graph
+#[derive(Clone, Debug)]
+pub struct SophiaGraph<C: RepositoryConnection> {
+    conn: SophiaConnection<C>,
+    name: Option<NamedOrBlankNode>,
+}
+
+type OxiPattern = (Option<NamedOrBlankNode>, Option<NamedNode>, Option<OTerm>);
+
+impl<C> SophiaGraph<C>
+where
+    C: RepositoryConnection,
+{
+    /// The name of this graph (`None` for the default graph).
+    pub fn name(&self) -> Option<Term<String>> {
+        self.name.as_ref().map(AsSophiaTerm::as_sophia)
+    }
+
+    /// Borrow the underlying connection
+    #[inline]
+    pub fn as_connection(&self) -> &SophiaConnection<C> {
+        &self.conn
+    }
+
+    /// Unwrap the underlying connection
+    #[inline]
+    pub fn into_connection(self) -> SophiaConnection<C> {
+        self.conn
+    }
+
+    fn oxi_triples(&self, pattern: Result<OxiPattern, ConversionError>) -> GTripleSource<Self> {
+        match pattern {
+            Ok((s, p, o)) => Box::new(
+                self.conn
+                    .conn
+                    .quads_for_pattern(s.as_ref(), p.as_ref(), o.as_ref(), Some(self.name.as_ref()))
+                    .map(move |r| {
+                        r.map(|q| StreamedTriple::by_value(self.conn.quad_bridge(q).into()))
+                    }),
+            ),
+            Err(_) => Box::new(empty()),
+        }
+    }
+
+    /// Convert Sophia terms into an Oxigraph quad in this graph.
+    fn oxi_quad<T, U, V>(
+        &mut self,
+        s: &Term<T>,
+        p: &Term<U>,
+        o: &Term<V>,
+    ) -> Result<OQuad, ConversionError>
+    where
+        T: TermData,
+        U: TermData,
+        V: TermData,
+    {
+        let (s, p, o, _) = self
+            .conn
+            .oxi_quad_or_placeholders(s, p, o, None::<&Term<&str>>)?
+            .destruct();
+        Ok(OQuad::new(s, p, o, self.name.clone()))
+    }
+}
+
+impl<C> Graph for SophiaGraph<C>
+where
+    C: RepositoryConnection,
+{
+    type Triple = TByValue<TripleBridge>;
+    type Error = OxigraphError;
+
+    fn triples(&self) -> GTripleSource<Self> {
+        self.oxi_triples(Ok((None, None, None)))
+    }
+
+    fn triples_with_s<'s, T>(&'s self, s: &'s Term<T>) -> GTripleSource<'s, Self>
+    where
+        T: TermData,
+    {
+        self.oxi_triples(self.conn.oxi_s(s).map(|s| (Some(s), None, None)))
+    }
+
+    fn triples_with_p<'s, T>(&'s self, p: &'s Term<T>) -> GTripleSource<'s, Self>
+    where
+        T: TermData,
+    {
+        self.oxi_triples(self.conn.oxi_p(p).map(|p| (None, Some(p), None)))
+    }
+
+    fn triples_with_o<'s, T>(&'s self, o: &'s Term<T>) -> GTripleSource<'s, Self>
+    where
+        T: TermData,
+    {
+        self.oxi_triples(self.conn.oxi_o(o).map(|o| (None, None, Some(o))))
+    }
+
+    fn triples_with_sp<'s, T, U>(
+        &'s self,
+        s: &'s Term<T>,
+        p: &'s Term<U>,
+    ) -> GTripleSource<'s, Self>
+    where
+        T: TermData,
+        U: TermData,
+    {
+        self.oxi_triples(
+            self.conn
+                .oxi_s(s)
+                .and_then(|s| Ok((Some(s), Some(self.conn.oxi_p(p)?), None))),
+        )
+    }
+
+    fn triples_with_so<'s, T, U>(
+        &'s self,
+        s: &'s Term<T>,
+        o: &'s Term<U>,
+    ) -> GTripleSource<'s, Self>
+    where
+        T: TermData,
+        U: TermData,
+    {
+        self.oxi_triples(
+            self.conn
+                .oxi_s(s)
+                .and_then(|s| Ok((Some(s), None, Some(self.conn.oxi_o(o)?)))),
+        )
+    }
+
+    fn triples_with_po<'s, T, U>(
+        &'s self,
+        p: &'s Term<T>,
+        o: &'s Term<U>,
+    ) -> GTripleSource<'s, Self>
+    where
+        T: TermData,
+        U: TermData,
+    {
+        self.oxi_triples(
+            self.conn
+                .oxi_p(p)
+                .and_then(|p| Ok((None, Some(p), Some(self.conn.oxi_o(o)?)))),
+        )
+    }
+
+    fn triples_with_spo<'s, T, U, V>(
+        &'s self,
+        s: &'s Term<T>,
+        p: &'s Term<U>,
+        o: &'s Term<V>,
+    ) -> GTripleSource<'s, Self>
+    where
+        T: TermData,
+        U: TermData,
+        V: TermData,
+    {
+        self.oxi_triples(self.conn.oxi_s(s).and_then(|s| {
+            Ok((
+                Some(s),
+                Some(self.conn.oxi_p(p)?),
+                Some(self.conn.oxi_o(o)?),
+            ))
+        }))
+    }
+}
+
+impl<C> MutableGraph for SophiaGraph<C>
+where
+    C: RepositoryConnection,
+{
+    type MutationError = MutationError;
+
+    fn insert<T, U, V>(&mut self, s: &Term<T>, p: &Term<U>, o: &Term<V>) -> MGResult<Self, bool>
+    where
+        T: TermData,
+        U: TermData,
+        V: TermData,
+    {
+        let quad = self.oxi_quad(s, p, o)?;
+        Ok(self.conn.insert_oxi(quad)?)
+    }
+
+    fn remove<T, U, V>(&mut self, s: &Term<T>, p: &Term<U>, o: &Term<V>) -> MGResult<Self, bool>
+    where
+        T: TermData,
+        U: TermData,
+        V: TermData,
+    {
+        let s = self.conn.oxi_s(s);
+        let p = self.conn.oxi_p(p);
+        let o = self.conn.oxi_o(o);
+        if let (Ok(s), Ok(p), Ok(o)) = (s, p, o) {
+            let quad = OQuad::new(s, p, o, self.name.clone());
+            Ok(self.conn.remove_oxi(quad)?)
+        } else {
+            Ok(false)
+        }
+    }
+}
+
+/// The result of [`SophiaConnection::diagnostics`].
+///
+/// [`SophiaConnection::diagnostics`]: struct.SophiaConnection.html#method.diagnostics
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostics {
+    /// The type of the underlying Oxigraph connection
+    pub backend: &'static str,
+    /// The number of quads
+    pub quads: usize,
+    /// The number of non-empty graphs, including the default graph
+    pub graphs: usize,
+    /// The number of distinct subjects
+    pub subjects: usize,
+    /// The number of distinct predicates
+    pub predicates: usize,
+    /// The number of distinct objects
+    pub objects: usize,
+}
+
+impl std::fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "backend:    {}", self.backend)?;
+        writeln!(f, "quads:      {}", self.quads)?;
+        writeln!(f, "graphs:     {}", self.graphs)?;
+        writeln!(f, "subjects:   {}", self.subjects)?;
+        writeln!(f, "predicates: {}", self.predicates)?;
+        writeln!(f, "objects:    {}", self.objects)
+    }
+}
+
+/// A term replaced by a placeholder, see [`SophiaConnection::with_placeholders`].
+///
+/// [`SophiaConnection::with_placeholders`]: struct.SophiaConnection.html#method.with_placeholders
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Substitution {
+    /// The N-Triples form of the original term
+    pub original: String,
+    /// The placeholder IRI
+    pub placeholder: Term<String>,
+}
+
+/// Iterator returned by [`SophiaConnection::select`].
+///
+/// [`SophiaConnection::select`]: struct.SophiaConnection.html#method.select
+pub struct SophiaBindings<C: RepositoryConnection> {
+    variables: Vec<String>,
+    rows: BindingsCell<C>,
+}
+
+/// A prepared query, together with an iterator over its solutions borrowing it
+#[self_referencing]
+struct BindingsCell<C: RepositoryConnection> {
+    query: C::PreparedQuery,
+    #[borrows(query)]
+    #[covariant]
+    rows: Box<dyn Iterator<Item = Result<Vec<Option<OTerm>>, OxigraphError>> + 'this>,
+}
+
+impl<C: RepositoryConnection> SophiaBindings<C> {
+    /// The names of the selected variables (without the `?`).
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+}
+
+impl<C: RepositoryConnection> Iterator for SophiaBindings<C> {
+    type Item = Result<HashMap<String, Term<String>>, OxigraphError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let variables = &self.variables;
+        self.rows.with_rows_mut(|rows| rows.next()).map(|r| {
+            r.map(|row| {
+                variables
+                    .iter()
+                    .zip(row)
+                    .filter_map(|(n, v)| v.map(|v| (n.clone(), v.into_sophia())))
+                    .collect()
+            })
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.rows.with_rows(|rows| rows.size_hint())
+    }
+}
+
+/// The result of [`SophiaConnection::extract_vocabulary`].
+///
+/// [`SophiaConnection::extract_vocabulary`]: struct.SophiaConnection.html#method.extract_vocabulary
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Vocabulary {
+    /// The objects of `rdf:type`
+    pub classes: HashSet<Term<String>>,
+    /// The distinct predicates
+    pub properties: HashSet<Term<String>>,
+    /// For each class, the properties (other than `rdf:type`) used by its instances
+    pub class_properties: HashMap<Term<String>, HashSet<Term<String>>>,
+}
+
+/// The result of [`SophiaConnection::delta_against`].
+///
+/// [`SophiaConnection::delta_against`]: struct.SophiaConnection.html#method.delta_against
+pub struct Delta {
+    /// Quads of the source that are not in the dataset
+    pub new: Vec<QuadBridge>,
+    /// Quads of the dataset that are not in the source
+    pub missing: Vec<QuadBridge>,
+}
+
+/// The line-based RDF syntaxes supported by [`SophiaConnection::dump_to_file`]
+/// and [`SophiaConnection::dump_to_writer`].
+///
+/// These methods write one triple or quad per line,
+/// so only N-Triples and N-Quads are supported;
+/// other syntaxes (e.g. Turtle or TriG) require a Sophia serializer,
+/// fed with [`graph_triple_sources`] or with the quads of the dataset.
+///
+/// [`graph_triple_sources`]: struct.SophiaConnection.html#method.graph_triple_sources
+/// [`SophiaConnection::dump_to_file`]: struct.SophiaConnection.html#method.dump_to_file
+/// [`SophiaConnection::dump_to_writer`]: struct.SophiaConnection.html#method.dump_to_writer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineFormat {
+    /// [N-Triples](https://www.w3.org/TR/n-triples/)
+    NTriples,
+    /// [N-Quads](https://www.w3.org/TR/n-quads/)
+    NQuads,
+}
+
+impl LineFormat {
+    /// Guess the format from the extension of `path`.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "nt" => Some(LineFormat::NTriples),
+            "nq" => Some(LineFormat::NQuads),
+            _ => None,
+        }
+    }
+}
+
+/// Iterator returned by [`SophiaConnection::drain`] and [`SophiaConnection::drain_matching`].
+///
+/// [`SophiaConnection::drain`]: struct.SophiaConnection.html#method.drain
+/// [`SophiaConnection::drain_matching`]: struct.SophiaConnection.html#method.drain_matching
+pub struct Drain<'a, C: RepositoryConnection> {
+    conn: &'a mut SophiaConnection<C>,
+    quads: std::vec::IntoIter<OQuad>,
+}
+
+impl<'a, C> Iterator for Drain<'a, C>
+where
+    C: RepositoryConnection,
+{
+    type Item = Result<QuadBridge, OxigraphError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let quad = self.quads.next()?;
+        Some(
+            self.conn
+                .remove_oxi(quad.clone())
+                .map(|_| self.conn.quad_bridge(quad)),
+        )
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.quads.size_hint()
+    }
+}
+
+/// The prepared queries of a [`SophiaConnection`], keyed by their text.
+///
+/// Cloning a connection does not clone its prepared queries.
+///
+/// [`SophiaConnection`]: struct.SophiaConnection.html
+struct QueryCache<C: RepositoryConnection> {
+    queries: Mutex<HashMap<&'static str, C::PreparedQuery>>,
+}
+
+impl<C: RepositoryConnection> Default for QueryCache<C> {
+    fn default() -> Self {
+        QueryCache {
+            queries: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<C: RepositoryConnection> Clone for QueryCache<C> {
+    fn clone(&self) -> Self {
+        QueryCache::default()
+    }
+}
+
+impl<C: RepositoryConnection> std::fmt::Debug for QueryCache<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("QueryCache")
+            .field(
+                "queries",
+                &self
+                    .queries
+                    .lock()
+                    .map(|q| q.keys().copied().collect::<Vec<_>>())
+                    .unwrap_or_default(),
+            )
+            .finish()
+    }
+}
+
+/// Iterator returned by [`SophiaConnection::into_quads`].
+///
+/// [`SophiaConnection::into_quads`]: struct.SophiaConnection.html#method.into_quads
+pub struct IntoQuads<C: RepositoryConnection>(IntoQuadsCell<C>);
+
+/// A connection, together with an iterator borrowing it
+#[self_referencing]
+struct IntoQuadsCell<C: RepositoryConnection> {
+    conn: SophiaConnection<C>,
+    #[borrows(conn)]
+    #[covariant]
+    quads: Box<dyn Iterator<Item = Result<OQuad, OxigraphError>> + 'this>,
+}
+
+impl<C> Iterator for IntoQuads<C>
+where
+    C: RepositoryConnection,
+{
+    type Item = Result<QuadBridge, OxigraphError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.with_mut(|fields| {
+            let conn = fields.conn;
+            fields.quads.next().map(|r| r.map(|q| conn.quad_bridge(q)))
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.with_quads(|quads| quads.size_hint())
+    }
+}
+
+/// Quad source returned by [`SophiaConnection::quads_ref`].
+///
+/// [`SophiaConnection::quads_ref`]: struct.SophiaConnection.html#method.quads_ref
+pub struct QuadsRef<I>(I);
+
+impl<I> QuadSource for QuadsRef<I>
+where
+    I: Iterator<Item = Result<OQuad, OxigraphError>>,
+{
+    type Error = OxigraphError;
+    type Quad = ByTermRefs<str>;
+
+    fn try_for_some_quad<F, E>(&mut self, f: &mut F) -> StreamResult<bool, OxigraphError, E>
+    where
+        F: FnMut(StreamedQuad<Self::Quad>) -> Result<(), E>,
+        E: std::error::Error,
+    {
+        match self.0.next() {
+            None => Ok(false),
+            Some(Err(err)) => Err(SourceError(err)),
+            Some(Ok(q)) => {
+                let g = q.graph_name().as_ref().map(AsSophiaTerm::as_sophia_ref);
+                f(StreamedQuad::by_term_refs(
+                    q.subject().as_sophia_ref(),
+                    q.predicate().as_sophia_ref(),
+                    q.object().as_sophia_ref(),
+                    g,
+                ))
+                .map_err(SinkError)?;
+                Ok(true)
+            }
+        }
+    }
+}
+
+/// The changes recorded by a [`SophiaConnection`] in dry-run mode.
+///
+/// Quads are recorded as they would have been stored,
+/// and the recorded changes are cumulative:
+/// inserting then removing the same quad leaves the report unchanged.
+///
+/// [`SophiaConnection`]: struct.SophiaConnection.html
+#[derive(Clone, Debug, Default)]
+pub struct DryRunReport {
+    added: HashSet<OQuad>,
+    removed: HashSet<OQuad>,
+}
+
+impl DryRunReport {
+    /// The quads that would have been added to the store.
+    pub fn added(&self) -> &HashSet<OQuad> {
+        &self.added
+    }
+
+    /// The quads that would have been removed from the store.
+    pub fn removed(&self) -> &HashSet<OQuad> {
+        &self.removed
+    }
+
+    /// Whether the store would contain `quad`, given the recorded changes.
+    fn contains<C: RepositoryConnection>(
+        &self,
+        conn: &C,
+        quad: &OQuad,
+    ) -> Result<bool, OxigraphError> {
+        Ok(self.added.contains(quad) || (!self.removed.contains(quad) && conn.contains(quad)?))
+    }
+
+    /// Record the insertion of `quad`, returning whether it would change the store.
+    fn insert<C: RepositoryConnection>(
+        &mut self,
+        conn: &C,
+        quad: OQuad,
+    ) -> Result<bool, OxigraphError> {
+        if self.contains(conn, &quad)? {
+            return Ok(false);
+        }
+        if !self.removed.remove(&quad) {
+            self.added.insert(quad);
+        }
+        Ok(true)
+    }
+
+    /// Record the removal of `quad`, returning whether it would change the store.
+    fn remove<C: RepositoryConnection>(
+        &mut self,
+        conn: &C,
+        quad: OQuad,
+    ) -> Result<bool, OxigraphError> {
+        if !self.contains(conn, &quad)? {
+            return Ok(false);
+        }
+        if !self.added.remove(&quad) {
+            self.removed.insert(quad);
+        }
+        Ok(true)
+    }
+}
+
+/// An Oxigraph quad pattern, where `None` matches any term
+/// (and `Some(None)` matches the default graph).
+pub(crate) type OxiQuadPattern = (
+    Option<NamedOrBlankNode>,
+    Option<NamedNode>,
+    Option<OTerm>,
+    Option<Option<NamedOrBlankNode>>,
+);
+
+/// Whether an Oxigraph quad matches `pattern`.
+fn oxi_pattern_matches(pattern: &OxiQuadPattern, q: &OQuad) -> bool {
+    let (s, p, o, g) = pattern;
+    s.as_ref().map_or(true, |s| s == q.subject())
+        && p.as_ref().map_or(true, |p| p == q.predicate())
+        && o.as_ref().map_or(true, |o| o == q.object())
+        && g.as_ref().map_or(true, |g| g == q.graph_name())
+}
+
+/// Build a predicate checking whether an Oxigraph quad matches the given matchers.
+///
+/// The matchers that were turned into `pattern` (see `SophiaConnection::oxi_matching_pattern`)
+/// are not checked, as the quads are assumed to have been retrieved with that pattern.
+pub(crate) fn oxi_quad_matcher<'s, S, P, O, G>(
+    pattern: &OxiQuadPattern,
+    ms: &'s S,
+    mp: &'s P,
+    mo: &'s O,
+    mg: &'s G,
+) -> impl Fn(&OQuad) -> bool + 's
+where
+    S: TermMatcher + ?Sized,
+    P: TermMatcher + ?Sized,
+    O: TermMatcher + ?Sized,
+    G: GraphNameMatcher + ?Sized,
+{
+    let (s, p, o, g) = pattern;
+    let (check_s, check_p, check_o, check_g) = (s.is_none(), p.is_none(), o.is_none(), g.is_none());
+    move |q| {
+        (!check_s || ms.matches(&q.subject().as_sophia_ref()))
+            && (!check_p || mp.matches(&q.predicate().as_sophia_ref()))
+            && (!check_o || mo.matches(&q.object().as_sophia_ref()))
+            && (!check_g
+                || mg.matches(
+                    q.graph_name()
+                        .as_ref()
+                        .map(AsSophiaTerm::as_sophia_ref)
+                        .as_ref(),
+                ))
+    }
+}
+
+/// Whether language `tag` matches language `range` (RFC 4647 basic filtering)
+fn lang_matches(tag: &str, range: &str) -> bool {
+    range == "*"
+        || tag.eq_ignore_ascii_case(range)
+        || (tag.len() > range.len()
+            && tag.as_bytes()[range.len()] == b'-'
+            && tag[..range.len()].eq_ignore_ascii_case(range))
+}
+
+/// Whether an Oxigraph quad contains no blank node
+fn is_ground(q: &OQuad) -> bool {
+    !(q.subject().is_blank_node()
+        || q.object().is_blank_node()
+        || q.graph_name()
+            .as_ref()
+            .map(NamedOrBlankNode::is_blank_node)
+            .unwrap_or(false))
+}
+
+#[inline]
+/// Shortcut function to convert Sophia graph name to Oxigraph graph name
+fn try_oxi_graphname<T: TermData>(
+    g: Option<&Term<T>>,
+) -> Result<Option<NamedOrBlankNode>, ConversionError> {
+    g.map(|g| g.try_oxigraphize()).transpose()
+}
+
+/// The prefix of the skolem IRIs minted for `base`, checked to be a valid IRI.
+fn skolem_prefix(base: &str) -> Result<String, ConversionError> {
+    let prefix = format!("{}/.well-known/genid/", base);
+    match NamedNode::new(prefix.as_str()) {
+        Ok(_) => Ok(prefix),
+        Err(_) => Err(ConversionError::InvalidIri(base.to_string())),
+    }
+}
+
+/// Extract a count from a SPARQL result value.
+///
+/// # Errors
+/// Anything else than a non-negative integer literal is reported as a `QueryError::InvalidCount`.
+fn parse_count(t: Option<OTerm>) -> Result<usize, QueryError> {
+    match t {
+        Some(OTerm::Literal(lit)) => lit
+            .value()
+            .parse()
+            .map_err(|_| QueryError::InvalidCount(lit.to_string())),
+        Some(t) => Err(QueryError::InvalidCount(t.to_string())),
+        None => Err(QueryError::InvalidCount("unbound".to_string())),
+    }
+}
+
+#[inline]
+/// Convert the result of a SPARQL query into a term set
+///
+/// # Errors
+/// + the query must be a SELECT query with a single selected variable
+/// + it must not produce NULL results
+fn sparql_result_as_term_set(r: QueryResult) -> Result<HashSet<Term<String>>, QueryError> {
+    let mut ret = HashSet::new();
+    for_each_sparql_row(r, 1, |vars, mut row| match row.pop() {
+        Some(Some(t)) => {
+            ret.insert(t.into_sophia());
+            Ok(())
+        }
+        _ => Err(QueryError::NullBinding(vars[0].clone())),
+    })?;
+    Ok(ret)
+}
+
+/// Call `f` on each row of the result of a SPARQL query,
+/// with the names of the selected variables.
+///
+/// # Errors
+/// + the query must be a SELECT query with `arity` selected variables
+/// + errors from Oxigraph or from `f` are propagated
+fn for_each_sparql_row<F>(r: QueryResult, arity: usize, mut f: F) -> Result<(), QueryError>
+where
+    F: FnMut(&[String], Vec<Option<OTerm>>) -> Result<(), QueryError>,
+{
+    match r {
+        QueryResult::Bindings(b) => {
+            let actual = b.variables().len();
+            if actual != arity {
+                return Err(QueryError::UnexpectedArity(actual));
+            }
+            let vars: Vec<String> = b.variables().iter().map(|v| v.to_string()).collect();
+            for row in b.into_values_iter() {
+                f(&vars, row?)?;
+            }
+            Ok(())
+        }
+        QueryResult::Boolean(_) => Err(QueryError::WrongResultKind("boolean")),
+        QueryResult::Graph(_) => Err(QueryError::WrongResultKind("graph")),
+    }
+}
+
+/// Mutation error for the Oxigraph-to-Sophia adapter
+#[derive(Debug, Error)]
+pub enum MutationError {
+    /// Error from Oxigraph
+    #[error("{source}")]
+    Oxigraph {
+        /// The source of this error
+        #[from]
+        source: OxigraphError,
+    },
+    /// Error from term conversion
+    #[error("Conversion: {source}")]
+    Conversion {
+        /// The source of this error
+        #[from]
+        source: ConversionError,
+    },
+    /// Error from term conversion, in a quad of a quad source
+    #[error("Conversion of quad #{index}: {source}")]
+    QuadConversion {
+        /// The index of the offending quad in the source
+        index: usize,
+        /// The source of this error
+        source: ConversionError,
+    },
+}
+
+impl From<std::convert::Infallible> for MutationError {
+    fn from(_: std::convert::Infallible) -> Self {
+        unreachable!()
+    }
+}
+
+/// Query error for the Oxigraph-to-Sophia adapter
+#[derive(Debug, Error)]
+pub enum QueryError {
+    /// Error from Oxigraph
+    #[error("{source}")]
+    Oxigraph {
+        /// The source of this error
+        #[from]
+        source: OxigraphError,
+    },
+    /// Error from term conversion
+    #[error("Conversion: {source}")]
+    Conversion {
+        /// The source of this error
+        #[from]
+        source: ConversionError,
+    },
+    /// Invalid variable name
+    #[error("Invalid variable name '{0}'")]
+    InvalidVariable(String),
+    /// Blank nodes can not be bound to variables in a query
+    #[error("Blank node can not be bound to variable '{0}'")]
+    BlankNodeBinding(String),
+    /// The query result is not of the expected kind
+    #[error("Unexpected {0} query result")]
+    WrongResultKind(&'static str),
+    /// A variable is unbound where a value is required
+    #[error("Variable {0} is unbound")]
+    NullBinding(String),
+    /// The query does not select the expected number of variables
+    #[error("Unexpected number of selected variables: {0}")]
+    UnexpectedArity(usize),
+    /// Invalid prefix declaration
+    #[error("Invalid prefix declaration '{0}: <{1}>'")]
+    InvalidPrefix(String, String),
+    /// A value that should be a count is not a non-negative integer
+    #[error("Invalid count {0}")]
+    InvalidCount(String),
+}
+
+/// Lose the detail of a query error, for methods that can only return Oxigraph errors
+impl From<QueryError> for OxigraphError {
+    fn from(err: QueryError) -> Self {
+        match err {
+            QueryError::Oxigraph { source } => source,
+            err => OxigraphError::msg(err.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{ex, lit, DG};
+    use sophia::ns::xsd;
+    use sophia::quad::stream::AsQuadSource;
+    use sophia_term::iri::Iri as SIri;
+
+    lazy_static::lazy_static! {
+        pub static ref REP: MemoryRepository = MemoryRepository::default();
+    }
+
+    type MemRepRef = &'static MemoryRepository;
+    type ConDataset = SophiaConnection<<MemRepRef as Repository>::Connection>;
+
+    #[allow(dead_code)]
+    fn make_dataset() -> ConDataset {
+        let mut conn = SophiaConnection::new(REP.connection().unwrap());
+        conn.remove_matching(&ANY, &ANY, &ANY, &ANY).unwrap();
+        conn
+    }
+
+    // These tests only work if options "-- --test-threads 1" is provided to cargo test,
+    // because they share a single repository REP.
+    //sophia::test_dataset_impl!(auto, ConDataset, false, make_dataset, false);
+
+    // Anyway, they are not strictly required:
+    // SophiaConnection is tested trough SophiaRepository,
+    // which simply delegates all Dataset methods to the underlying SophiaConnection.
+
+    // The tests below each use their own repository,
+    // so they are not subject to the restriction above.
+
+    #[test]
+    fn max_literal_len() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap()).with_max_literal_len(5);
+        assert!(d.insert(&ex("s"), &ex("p"), &lit("12345"), DG).is_ok());
+        match d.insert(&ex("s"), &ex("p"), &lit("123456"), DG) {
+            Err(MutationError::Conversion {
+                source: ConversionError::LiteralTooLong(6, 5),
+            }) => (),
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert_eq!(d.quads().count(), 1);
+    }
+
+    #[test]
+    fn oxi_graph_names() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.insert(&ex("s"), &ex("p"), &ex("o"), DG).unwrap();
+        d.insert(&ex("s"), &ex("p"), &ex("o"), Some(&ex("g1")))
+            .unwrap();
+        d.insert(&ex("s"), &ex("p"), &ex("o"), Some(&ex("g2")))
+            .unwrap();
+        let names = d.oxi_graph_names().unwrap();
+        assert_eq!(names.len(), 2);
+        assert_eq!(names.len(), d.graph_names().unwrap().len());
+        assert!(names.contains(&NamedNode::new_unchecked("http://example.org/g1").into()));
+    }
+
+    #[test]
+    fn dry_run() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap()).with_dry_run();
+        assert!(d.insert(&ex("s"), &ex("p"), &ex("o"), DG).unwrap());
+        assert!(!d.insert(&ex("s"), &ex("p"), &ex("o"), DG).unwrap());
+        let report = d.dry_run_report().unwrap();
+        assert_eq!(report.added().len(), 1);
+        assert_eq!(report.removed().len(), 0);
+        assert_eq!(d.quads().count(), 0);
+
+        d.set_dry_run(false);
+        d.insert(&ex("s"), &ex("p"), &ex("o"), DG).unwrap();
+        d.set_dry_run(true);
+        assert!(d.remove(&ex("s"), &ex("p"), &ex("o"), DG).unwrap());
+        assert_eq!(d.dry_run_report().unwrap().removed().len(), 1);
+        assert_eq!(d.quads().count(), 1);
+    }
+
+    #[test]
+    fn datetime_roundtrip() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        for (i, value) in ["2020-01-01T00:00:00+02:00", "2020-01-01T00:00:00Z"]
+            .iter()
+            .enumerate()
+        {
+            let s = ex(&format!("s{}", i));
+            let o = Term::<String>::new_literal_dt(value.to_string(), xsd::dateTime).unwrap();
+            d.insert(&s, &ex("p"), &o, DG).unwrap();
+            assert!(d.contains(&s, &ex("p"), &o, DG).unwrap());
+            let objects: Vec<_> = d.quads_with_s(&s).map(|q| q.unwrap().o().clone()).collect();
+            assert_eq!(objects, vec![o]);
+        }
+    }
+
+    #[test]
+    fn subject_fanout() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.insert(&ex("s1"), &ex("p"), &ex("o1"), DG).unwrap();
+        d.insert(&ex("s2"), &ex("p"), &ex("o1"), DG).unwrap();
+        d.insert(&ex("s2"), &ex("p"), &ex("o2"), DG).unwrap();
+        d.insert(&ex("s2"), &ex("p"), &ex("o3"), Some(&ex("g")))
+            .unwrap();
+        let fanout = d.subject_fanout().unwrap();
+        assert_eq!(fanout, vec![(ex("s2"), 3), (ex("s1"), 1)]);
+    }
+
+    #[test]
+    fn find_invalid_iris() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.insert(&ex("s"), &ex("p"), &ex("o"), DG).unwrap();
+        let bad = NamedNode::new_unchecked("http://example.org/a b");
+        d.as_oxi_mut()
+            .insert(&OQuad::new(bad.clone(), bad.clone(), bad, None))
+            .unwrap();
+        let invalid = d.find_invalid_iris().unwrap();
+        assert_eq!(invalid.len(), 1);
+        assert!(invalid.contains(&Term::Iri(SIri::new_unchecked(
+            "http://example.org/a b",
+            true
+        ))));
+    }
+
+    #[test]
+    fn string_value() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.insert(&ex("s"), &rdfs::label, &lit("hello"), DG).unwrap();
+        d.insert(&ex("s"), &rdfs::label, &lit("hello"), Some(&ex("g")))
+            .unwrap();
+        d.insert(&ex("s"), &ex("p"), &ex("o"), DG).unwrap();
+        assert_eq!(
+            d.string_value(&ex("s"), &rdfs::label).unwrap(),
+            Some("hello".to_string())
+        );
+        assert_eq!(d.string_value(&ex("s"), &rdfs::comment).unwrap(), None);
+        assert!(d.string_value(&ex("s"), &ex("p")).is_err());
+        d.insert(&ex("s"), &rdfs::label, &lit("bonjour"), DG)
+            .unwrap();
+        assert!(d.string_value(&ex("s"), &rdfs::label).is_err());
+    }
+
+    #[test]
+    fn object_strings() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.insert(&ex("s1"), &ex("p"), &lit("foo"), DG).unwrap();
+        d.insert(&ex("s2"), &ex("p"), &lit("bar"), Some(&ex("g")))
+            .unwrap();
+        d.insert(&ex("s3"), &ex("p"), &ex("o"), DG).unwrap();
+        d.insert(&ex("s4"), &ex("q"), &lit("baz"), DG).unwrap();
+        let mut strings: Vec<_> = d
+            .object_strings(&ex("p"))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        strings.sort();
+        assert_eq!(strings, vec!["bar", "foo"]);
+    }
+
+    #[test]
+    fn graphs_of() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.insert(&ex("s"), &ex("p"), &ex("o1"), DG).unwrap();
+        d.insert(&ex("s"), &ex("p"), &ex("o2"), DG).unwrap();
+        d.insert(&ex("s"), &ex("p"), &ex("o"), Some(&ex("g1")))
+            .unwrap();
+        d.insert(&ex("s"), &ex("p"), &ex("o"), Some(&ex("g2")))
+            .unwrap();
+        d.insert(&ex("x"), &ex("p"), &ex("o"), Some(&ex("g3")))
+            .unwrap();
+        let graphs = d.graphs_of(&ex("s")).unwrap();
+        assert_eq!(graphs.len(), 3);
+        assert!(graphs.contains(&None));
+        assert!(graphs.contains(&Some(ex("g1"))));
+        assert!(graphs.contains(&Some(ex("g2"))));
+    }
+
+    #[derive(Debug)]
+    struct UpperUrn;
+
+    impl OxiTermConverter for UpperUrn {
+        fn object(&self, t: &Term<&str>) -> Result<OTerm, ConversionError> {
+            match t {
+                Term::Iri(i) if i.value().starts_with("urn:") => {
+                    Ok(NamedNode::new_unchecked(i.value().to_uppercase()).into())
+                }
+                _ => t.try_oxigraphize(),
+            }
+        }
+    }
+
+    #[test]
+    fn custom_converter() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap()).with_converter(UpperUrn);
+        let urn = Term::<&str>::new_iri("urn:isbn:abc").unwrap();
+        d.insert(&ex("s"), &ex("p"), &urn, DG).unwrap();
+        d.insert(&ex("s"), &ex("p"), &ex("o"), DG).unwrap();
+        let expected = OQuad::new(
+            NamedNode::new_unchecked("http://example.org/s"),
+            NamedNode::new_unchecked("http://example.org/p"),
+            NamedNode::new_unchecked("URN:ISBN:ABC"),
+            None,
+        );
+        assert!(d.as_oxi().contains(&expected).unwrap());
+        assert!(d.contains(&ex("s"), &ex("p"), &urn, DG).unwrap());
+        assert!(d.contains(&ex("s"), &ex("p"), &ex("o"), DG).unwrap());
+    }
+
+    struct UpperLiterals;
+
+    impl OxiTermConverter for UpperLiterals {
+        fn object(&self, t: &Term<&str>) -> Result<OTerm, ConversionError> {
+            match t {
+                Term::Literal(l) => Ok(oxigraph::model::Literal::new_simple_literal(
+                    l.value().to_uppercase(),
+                )
+                .into()),
+                _ => t.try_oxigraphize(),
+            }
+        }
+    }
+
+    #[test]
+    fn unchecked_language_tags_with_converter() {
+        use sophia_term::literal::Literal;
+
+        let bad_tag = Term::Literal(Literal::new_lang_unchecked("chat", "fr_FR"));
+        let repo = MemoryRepository::default();
+        let mut d =
+            SophiaConnection::new(repo.connection().unwrap()).with_unchecked_language_tags();
+        d.insert(&ex("s"), &ex("p"), &bad_tag, DG).unwrap();
+        d.insert(&ex("s"), &ex("p"), &lit("foo"), DG).unwrap();
+        assert!(d.contains(&ex("s"), &ex("p"), &bad_tag, DG).unwrap());
+        assert!(d.contains(&ex("s"), &ex("p"), &lit("foo"), DG).unwrap());
+
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap())
+            .with_unchecked_language_tags()
+            .with_converter(UpperLiterals);
+        d.insert(&ex("s"), &ex("p"), &bad_tag, DG).unwrap();
+        let expected = OQuad::new(
+            NamedNode::new_unchecked("http://example.org/s"),
+            NamedNode::new_unchecked("http://example.org/p"),
+            oxigraph::model::Literal::new_simple_literal("CHAT"),
+            None,
+        );
+        assert!(d.as_oxi().contains(&expected).unwrap());
+        assert_eq!(d.quads().count(), 1);
+    }
+
+    #[test]
+    fn content_hash() {
+        let b1 = Term::<&str>::new_bnode("b1").unwrap();
+        let b2 = Term::<&str>::new_bnode("b2").unwrap();
+        let repo1 = MemoryRepository::default();
+        let mut d1 = SophiaConnection::new(repo1.connection().unwrap());
+        d1.insert(&ex("s"), &ex("p"), &b1, DG).unwrap();
+        d1.insert(&b1, &ex("p"), &lit("foo"), DG).unwrap();
+        d1.insert(&ex("s"), &ex("p"), &ex("o"), Some(&ex("g")))
+            .unwrap();
+
+        let repo2 = MemoryRepository::default();
+        let mut d2 = SophiaConnection::new(repo2.connection().unwrap());
+        d2.insert(&ex("s"), &ex("p"), &ex("o"), Some(&ex("g")))
+            .unwrap();
+        d2.insert(&b2, &ex("p"), &lit("foo"), DG).unwrap();
+        d2.insert(&ex("s"), &ex("p"), &b2, DG).unwrap();
+
+        assert_eq!(d1.content_hash().unwrap(), d2.content_hash().unwrap());
+
+        d2.insert(&ex("s"), &ex("p"), &ex("o2"), DG).unwrap();
+        assert_ne!(d1.content_hash().unwrap(), d2.content_hash().unwrap());
+    }
+
+    #[test]
+    fn drain() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.insert(&ex("s"), &ex("p"), &ex("o1"), DG).unwrap();
+        d.insert(&ex("s"), &ex("q"), &ex("o2"), DG).unwrap();
+        d.insert(&ex("s"), &ex("p"), &ex("o3"), Some(&ex("g")))
+            .unwrap();
+        let p = ex("p");
+        let drained = d.drain_matching(&ANY, &p, &ANY, &ANY).unwrap().count();
+        assert_eq!(drained, 2);
+        assert_eq!(d.quads().count(), 1);
+        let objects: Vec<_> = d.drain().unwrap().map(|q| q.unwrap().o().clone()).collect();
+        assert_eq!(objects, vec![ex("o2")]);
+        assert_eq!(d.quads().count(), 0);
+    }
+
+    #[test]
+    fn typed_objects() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        let answer = Term::<&str>::new_literal_dt("42", xsd::integer).unwrap();
+        d.insert(&ex("s"), &ex("p"), &answer, DG).unwrap();
+        d.insert(&ex("s"), &ex("p"), &lit("foo"), DG).unwrap();
+        d.insert(&ex("s"), &ex("p"), &ex("o"), DG).unwrap();
+        let mut objects = d.typed_objects(&ex("s"), &ex("p")).unwrap();
+        objects.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(objects.len(), 2);
+        assert_eq!(objects[0].0, "42");
+        assert_eq!(objects[0].1, xsd::integer);
+        assert_eq!(objects[1].0, "foo");
+        assert_eq!(objects[1].1, xsd::string);
+    }
+
+    #[test]
+    fn dump_to_file() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.insert(&ex("s"), &ex("p"), &lit("foo"), DG).unwrap();
+        d.insert(&ex("s"), &ex("p"), &ex("o"), Some(&ex("g")))
+            .unwrap();
+        let dir = std::env::temp_dir();
+        let nq = dir.join(format!("sophia_oxigraph_dump_{}.nq", std::process::id()));
+        let nt = dir.join(format!("sophia_oxigraph_dump_{}.nt", std::process::id()));
+
+        d.dump_to_file(&nq, None, false).unwrap();
+        let repo2 = MemoryRepository::default();
+        let mut d2 = SophiaConnection::new(repo2.connection().unwrap());
+        d2.as_oxi_mut()
+            .load_dataset(
+                std::io::BufReader::new(File::open(&nq).unwrap()),
+                oxigraph::DatasetSyntax::NQuads,
+                None,
+            )
+            .unwrap();
+        assert_eq!(d.content_hash().unwrap(), d2.content_hash().unwrap());
+
+        assert!(d.dump_to_file(&nt, None, false).is_err());
+        d.dump_to_file(&nt, None, true).unwrap();
+        let dumped = std::fs::read_to_string(&nt).unwrap();
+        assert_eq!(dumped.lines().count(), 1);
+
+        std::fs::remove_file(nq).unwrap();
+        std::fs::remove_file(nt).unwrap();
+    }
+
+    #[test]
+    fn find_untyped_literals() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        let answer = Term::<&str>::new_literal_dt("42", xsd::integer).unwrap();
+        let hello = Term::<&str>::new_literal_lang("hello", "en").unwrap();
+        d.insert(&ex("s"), &ex("p"), &answer, DG).unwrap();
+        d.insert(&ex("s"), &ex("p"), &hello, DG).unwrap();
+        d.insert(&ex("s"), &ex("p"), &lit("foo"), Some(&ex("g")))
+            .unwrap();
+        assert!(d.find_untyped_literals().unwrap().is_empty());
+    }
+
+    #[test]
+    fn query_select_bound() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.insert(&ex("s1"), &ex("p"), &lit("foo"), DG).unwrap();
+        d.insert(&ex("s2"), &ex("p"), &lit("bar"), DG).unwrap();
+        let query = "SELECT ?x ?o { ?x <http://example.org/p> ?o }";
+        let mut bindings = HashMap::new();
+        assert_eq!(d.query_select_bound(query, &bindings).unwrap().len(), 2);
+        bindings.insert("x".to_string(), ex("s1"));
+        let solutions = d.query_select_bound(query, &bindings).unwrap();
+        assert_eq!(solutions.len(), 1);
+        assert_eq!(solutions[0]["x"], ex("s1"));
+        assert_eq!(solutions[0]["o"], lit("foo"));
+        bindings.insert("bad name".to_string(), ex("s1"));
+        assert!(d.query_select_bound(query, &bindings).is_err());
+    }
+
+    #[test]
+    fn graph_count() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        assert_eq!(d.graph_count().unwrap(), 0);
+        d.insert(&ex("s"), &ex("p"), &ex("o"), DG).unwrap();
+        assert_eq!(d.graph_count().unwrap(), 1);
+        d.insert(&ex("s"), &ex("p"), &ex("o"), Some(&ex("g1")))
+            .unwrap();
+        d.insert(&ex("s"), &ex("p"), &ex("o"), Some(&ex("g2")))
+            .unwrap();
+        assert_eq!(d.graph_count().unwrap(), 3);
+        d.remove(&ex("s"), &ex("p"), &ex("o"), DG).unwrap();
+        assert_eq!(d.graph_count().unwrap(), 2);
+    }
+
+    #[test]
+    fn reify() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        let stmt = ex("stmt");
+        assert_eq!(d.dereify(&stmt).unwrap(), None);
+        assert_eq!(d.reify(&ex("s"), &ex("p"), &lit("foo"), &stmt).unwrap(), 4);
+        assert_eq!(d.quads().count(), 4);
+        assert!(d.contains(&stmt, &rdf::type_, &rdf::Statement, DG).unwrap());
+        assert_eq!(
+            d.dereify(&stmt).unwrap(),
+            Some([ex("s"), ex("p"), lit("foo")])
+        );
+    }
+
+    #[test]
+    fn export_by_graph() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.insert(&ex("s"), &ex("p"), &ex("o0"), DG).unwrap();
+        d.insert(&ex("s"), &ex("p"), &ex("o1"), Some(&ex("g1")))
+            .unwrap();
+        d.insert(&ex("s"), &ex("p"), &ex("o2"), Some(&ex("g2")))
+            .unwrap();
+        d.insert(&ex("s"), &ex("q"), &ex("o2"), Some(&ex("g2")))
+            .unwrap();
+        let mut calls = vec![];
+        d.export_by_graph(|g, triples| {
+            let triples: Vec<_> = triples.map(Result::unwrap).collect();
+            calls.push((g.cloned(), triples));
+        })
+        .unwrap();
+        assert_eq!(calls.len(), 3);
+        assert_eq!(calls[0].0, None);
+        assert_eq!(calls[0].1, vec![[ex("s"), ex("p"), ex("o0")]]);
+        assert_eq!(calls[1].0, Some(ex("g1")));
+        assert_eq!(calls[1].1, vec![[ex("s"), ex("p"), ex("o1")]]);
+        assert_eq!(calls[2].0, Some(ex("g2")));
+        assert_eq!(calls[2].1.len(), 2);
+    }
+
+    #[test]
+    fn decimal_roundtrip() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        let one = Term::<&str>::new_literal_dt("1.0", xsd::decimal).unwrap();
+        d.insert(&ex("s"), &ex("p"), &one, DG).unwrap();
+        let read1: Vec<_> = d.quads().map(|q| q.unwrap().o().clone()).collect();
+        let read2: Vec<_> = d.quads().map(|q| q.unwrap().o().clone()).collect();
+        assert_eq!(read1, read2);
+        assert_eq!(read1.len(), 1);
+        match &read1[0] {
+            Term::Literal(lit) => {
+                assert_eq!(lit.dt().value(), xsd::decimal.value());
+                // decimals are stored by value, so only their canonical form can be read back
+                assert_eq!(lit.value(), "1");
+            }
+            other => panic!("unexpected term {}", other),
+        }
+        assert!(d.contains(&ex("s"), &ex("p"), &read1[0], DG).unwrap());
+        assert!(d.contains(&ex("s"), &ex("p"), &one, DG).unwrap());
+    }
+
+    #[test]
+    fn check_cardinality() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        let topic = ex("primaryTopic");
+        d.insert(&ex("doc1"), &topic, &ex("t1"), Some(&ex("g1")))
+            .unwrap();
+        d.insert(&ex("doc2"), &topic, &ex("t1"), Some(&ex("g2")))
+            .unwrap();
+        d.insert(&ex("doc2"), &topic, &ex("t2"), Some(&ex("g2")))
+            .unwrap();
+        d.insert(&ex("doc3"), &ex("p"), &ex("o"), Some(&ex("g3")))
+            .unwrap();
+        let mut violations = d.check_cardinality(&topic, 1, 1).unwrap();
+        violations.sort_by_key(|(_, c)| *c);
+        assert_eq!(violations, vec![(ex("g3"), 0), (ex("g2"), 2)]);
+        assert_eq!(
+            d.check_cardinality(&topic, 0, 1).unwrap(),
+            vec![(ex("g2"), 2)]
+        );
+    }
+
+    #[test]
+    fn delta_against() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        let b = Term::<String>::new_bnode("b").unwrap();
+        d.insert(&ex("s"), &ex("p"), &ex("kept"), DG).unwrap();
+        d.insert(&ex("s"), &ex("p"), &ex("dropped"), DG).unwrap();
+        d.insert(&ex("s"), &ex("p"), &b, DG).unwrap();
+        let source: Vec<([Term<String>; 3], Option<Term<String>>)> = vec![
+            ([ex("s"), ex("p"), ex("kept")], None),
+            ([ex("s"), ex("p"), ex("added")], Some(ex("g"))),
+            ([ex("s"), ex("p"), b.clone()], None),
+        ];
+        let delta = d
+            .delta_against(source.into_iter().as_quad_source())
+            .unwrap();
+        assert_eq!(delta.new.len(), 1);
+        assert_eq!(delta.new[0].o(), &ex("added"));
+        assert_eq!(delta.new[0].g(), Some(&ex("g")));
+        assert_eq!(delta.missing.len(), 1);
+        assert_eq!(delta.missing[0].o(), &ex("dropped"));
+    }
+
+    #[test]
+    fn default_graph_is_none() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.insert(&ex("s"), &ex("p"), &ex("o"), DG).unwrap();
+        d.insert(&ex("s"), &ex("p"), &ex("o"), Some(&ex("g")))
+            .unwrap();
+        let graphs: Vec<_> = d.quads().map(|q| q.unwrap().g().cloned()).collect();
+        assert_eq!(graphs.len(), 2);
+        assert!(graphs.contains(&None));
+        assert!(graphs.contains(&Some(ex("g"))));
+        let in_default: Vec<_> = d
+            .quads_with_g(DG)
+            .map(|q| q.unwrap().g().cloned())
+            .collect();
+        assert_eq!(in_default, vec![None]);
+        let in_default: Vec<_> = d
+            .quads_with_sg(&ex("s"), DG)
+            .map(|q| q.unwrap().g().cloned())
+            .collect();
+        assert_eq!(in_default, vec![None]);
+    }
+
+    #[test]
+    fn undefined_predicates() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.insert(&ex("s"), &ex("described"), &ex("o"), DG).unwrap();
+        d.insert(&ex("s"), &ex("undescribed"), &ex("o"), DG)
+            .unwrap();
+        d.insert(
+            &ex("described"),
+            &rdfs::label,
+            &lit("described"),
+            Some(&ex("g")),
+        )
+        .unwrap();
+        let undefined = d.undefined_predicates().unwrap();
+        assert_eq!(undefined.len(), 2);
+        assert!(undefined.contains(&ex("undescribed")));
+        assert!(
+            undefined.contains(&Term::<String>::new_iri(rdfs::label.value().to_string()).unwrap())
+        );
+    }
+
+    #[test]
+    fn preferred_label() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        let en = Term::<&str>::new_literal_lang("cat", "en").unwrap();
+        let fr = Term::<&str>::new_literal_lang("chat", "fr-FR").unwrap();
+        d.insert(&ex("cat"), &rdfs::label, &en, DG).unwrap();
+        d.insert(&ex("cat"), &rdfs::label, &fr, DG).unwrap();
+        assert_eq!(
+            d.preferred_label(&ex("cat"), &["fr", "en"]).unwrap(),
+            Some("chat".to_string())
+        );
+        assert_eq!(
+            d.preferred_label(&ex("cat"), &["de", "en"]).unwrap(),
+            Some("cat".to_string())
+        );
+        assert!(d.preferred_label(&ex("cat"), &["de"]).unwrap().is_some());
+        assert_eq!(
+            d.preferred_label(&ex("dog"), &["en"]).unwrap(),
+            Some("dog".to_string())
+        );
+    }
+
+    #[test]
+    fn load_skip_bnodes() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        let ttl = r#"
+            @prefix : <http://example.org/>.
+            :s :p :o1, :o2, "foo".
+            :s :p [ :q :o3 ].
+        "#;
+        let skipped = d
+            .load_skip_bnodes(ttl.as_bytes(), GraphSyntax::Turtle, None)
+            .unwrap();
+        assert_eq!(skipped, 2);
+        assert_eq!(d.quads().count(), 3);
+        assert!(d.bnodes().unwrap().is_empty());
+    }
+
+    #[test]
+    fn literals_with_datatype() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        let date1 = Term::<String>::new_literal_dt("2020-01-01", xsd::date).unwrap();
+        let date2 = Term::<String>::new_literal_dt("2020-01-02", xsd::date).unwrap();
+        d.insert(&ex("s"), &ex("p"), &date1, DG).unwrap();
+        d.insert(&ex("s"), &ex("p"), &date2, Some(&ex("g")))
+            .unwrap();
+        d.insert(&ex("s"), &ex("p"), &lit("2020-01-03"), DG)
+            .unwrap();
+        let dates = d.literals_with_datatype(&xsd::date).unwrap();
+        assert_eq!(dates.len(), 2);
+        assert!(dates.contains(&date1));
+        assert!(dates.contains(&date2));
+    }
+
+    #[test]
+    fn diagnostics() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        let diag = d.diagnostics().unwrap();
+        assert_eq!(
+            (
+                diag.quads,
+                diag.graphs,
+                diag.subjects,
+                diag.predicates,
+                diag.objects
+            ),
+            (0, 0, 0, 0, 0)
+        );
+        assert!(!diag.backend.is_empty());
+        d.insert(&ex("s1"), &ex("p"), &ex("o"), DG).unwrap();
+        d.insert(&ex("s2"), &ex("p"), &ex("o"), DG).unwrap();
+        d.insert(&ex("s1"), &ex("q"), &lit("foo"), Some(&ex("g")))
+            .unwrap();
+        let diag = d.diagnostics().unwrap();
+        assert_eq!(
+            (
+                diag.quads,
+                diag.graphs,
+                diag.subjects,
+                diag.predicates,
+                diag.objects
+            ),
+            (3, 2, 2, 2, 2)
+        );
+        assert!(diag.to_string().contains("quads:      3"));
+    }
+
+    #[test]
+    fn skolemize() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        let b1 = Term::<&str>::new_bnode("b1").unwrap();
+        let b2 = Term::<&str>::new_bnode("b2").unwrap();
+        d.insert(&ex("s"), &ex("p"), &b1, DG).unwrap();
+        d.insert(&b1, &ex("p"), &b2, Some(&b2)).unwrap();
+        d.insert(&ex("s"), &ex("p"), &ex("o"), DG).unwrap();
+        let hash = d.content_hash().unwrap();
+
+        assert_eq!(d.skolemize("http://example.org").unwrap(), 2);
+        assert!(d.bnodes().unwrap().is_empty());
+        assert_eq!(d.quads().count(), 3);
+        assert!(d.iris().unwrap().iter().any(|i| i
+            .value()
+            .starts_with("http://example.org/.well-known/genid/")));
+
+        assert_eq!(d.deskolemize("http://example.org").unwrap(), 2);
+        assert_eq!(d.bnodes().unwrap().len(), 2);
+        assert_eq!(d.content_hash().unwrap(), hash);
+    }
+
+    #[test]
+    fn skolemize_bad_base() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        let b1 = Term::<&str>::new_bnode("b1").unwrap();
+        d.insert(&ex("s"), &ex("p"), &b1, DG).unwrap();
+        let hash = d.content_hash().unwrap();
+
+        for base in &["not an iri", "http://example.org/a b"] {
+            assert!(matches!(
+                d.skolemize(base),
+                Err(MutationError::Conversion {
+                    source: ConversionError::InvalidIri(_)
+                })
+            ));
+            assert!(matches!(
+                d.deskolemize(base),
+                Err(MutationError::Conversion {
+                    source: ConversionError::InvalidIri(_)
+                })
+            ));
+        }
+        assert_eq!(d.bnodes().unwrap().len(), 1);
+        assert_eq!(d.content_hash().unwrap(), hash);
+    }
+
+    #[test]
+    fn update_counted() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.insert(&ex("s"), &ex("p"), &ex("o"), DG).unwrap();
+        d.insert(&ex("s"), &ex("q"), &ex("o"), DG).unwrap();
+        let counts = d
+            .update_counted(
+                "DELETE { ?s <http://example.org/p> ?o } \
+                 INSERT { ?s <http://example.org/p2> ?o. ?o <http://example.org/p3> ?s } \
+                 WHERE { ?s <http://example.org/p> ?o }",
+            )
+            .unwrap();
+        assert_eq!(counts, (2, 1));
+        assert_eq!(d.quads().count(), 3);
+    }
+
+    #[test]
+    fn query_select_prefixes() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.insert(&ex("alice"), &rdf::type_, &ex("Person"), DG)
+            .unwrap();
+        d.insert(&ex("bob"), &rdf::type_, &ex("Dog"), DG).unwrap();
+
+        let prefixes = [("ex", "http://example.org/")];
+        let prefixed = d
+            .query_select("SELECT ?s { ?s a ex:Person }", Some(&prefixes[..]))
+            .unwrap();
+        let expanded = d
+            .query_select("SELECT ?s { ?s a <http://example.org/Person> }", None)
+            .unwrap();
+        assert_eq!(prefixed.len(), 1);
+        assert_eq!(prefixed, expanded);
+
+        let relative = [("ex", "foo/")];
+        assert!(matches!(
+            d.query_select("SELECT ?s { ?s a ex:Person }", Some(&relative[..])),
+            Err(QueryError::InvalidPrefix(..))
+        ));
+    }
+
+    #[test]
+    fn distinct_objects() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.insert(&ex("s1"), &ex("p"), &ex("o1"), DG).unwrap();
+        d.insert(&ex("s2"), &ex("p"), &ex("o1"), DG).unwrap();
+        d.insert(&ex("s1"), &ex("p"), &ex("o1"), Some(&ex("g")))
+            .unwrap();
+        d.insert(&ex("s1"), &ex("p"), &ex("o2"), Some(&ex("g")))
+            .unwrap();
+        d.insert(&ex("s1"), &ex("q"), &ex("o3"), DG).unwrap();
+
+        let mut objects: Vec<_> = d
+            .distinct_objects(&ex("p"))
+            .map(|o| o.unwrap().value().to_string())
+            .collect();
+        objects.sort();
+        assert_eq!(
+            objects,
+            vec!["http://example.org/o1", "http://example.org/o2"]
+        );
+    }
+
+    #[test]
+    fn cross_graph_duplicates() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.insert(&ex("s"), &ex("p"), &ex("o"), Some(&ex("g1")))
+            .unwrap();
+        d.insert(&ex("s"), &ex("p"), &ex("o"), Some(&ex("g2")))
+            .unwrap();
+        d.insert(&ex("s"), &ex("p"), &ex("o2"), Some(&ex("g1")))
+            .unwrap();
+        d.insert(&ex("s"), &ex("p"), &ex("o2"), DG).unwrap();
+        d.insert(&ex("s"), &ex("p"), &ex("o3"), Some(&ex("g2")))
+            .unwrap();
+
+        let mut dups = d.cross_graph_duplicates().unwrap();
+        dups.sort_by_key(|(t, _)| t[2].value().to_string());
+        assert_eq!(dups.len(), 2);
+        assert_eq!(dups[0].0[2], ex("o"));
+        assert_eq!(
+            dups[0].1,
+            vec![Some(ex("g1")), Some(ex("g2"))].into_iter().collect()
+        );
+        assert_eq!(dups[1].0[2], ex("o2"));
+        assert_eq!(dups[1].1, vec![Some(ex("g1")), None].into_iter().collect());
+    }
+
+    #[test]
+    fn insert_into_graphs() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        let g1 = ex("g1");
+        let g2 = ex("g2");
+        d.insert(&ex("s"), &ex("p"), &ex("o"), Some(&g1)).unwrap();
+
+        let n = d
+            .insert_into_graphs(&ex("s"), &ex("p"), &ex("o"), &[Some(&g1), Some(&g2)])
+            .unwrap();
+        assert_eq!(n, 1);
+        assert_eq!(d.quads().count(), 2);
+        assert!(d.contains(&ex("s"), &ex("p"), &ex("o"), Some(&g2)).unwrap());
+    }
+
+    #[test]
+    fn extract_vocabulary() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.insert(&ex("alice"), &rdf::type_, &ex("Person"), DG)
+            .unwrap();
+        d.insert(&ex("alice"), &ex("name"), &lit("Alice"), DG)
+            .unwrap();
+        d.insert(&ex("alice"), &ex("owns"), &ex("rex"), DG).unwrap();
+        d.insert(&ex("rex"), &rdf::type_, &ex("Dog"), Some(&ex("g")))
+            .unwrap();
+        d.insert(&ex("rex"), &ex("name"), &lit("Rex"), Some(&ex("g")))
+            .unwrap();
+
+        let voc = d.extract_vocabulary().unwrap();
+        assert_eq!(
+            voc.classes,
+            vec![ex("Person"), ex("Dog")].into_iter().collect()
+        );
+        assert_eq!(voc.properties.len(), 3);
+        assert_eq!(
+            voc.class_properties[&ex("Person")],
+            vec![ex("name"), ex("owns")].into_iter().collect()
+        );
+        assert_eq!(
+            voc.class_properties[&ex("Dog")],
+            vec![ex("name")].into_iter().collect()
+        );
+    }
+
+    #[test]
+    fn placeholders() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap())
+            .with_placeholders("http://example.org/placeholder/")
+            .unwrap();
+        let relative = Term::Iri(SIri::new_unchecked("foo", false));
+        assert!(d.insert(&ex("s"), &ex("p"), &relative, DG).unwrap());
+        assert!(d.insert(&ex("s"), &ex("p"), &ex("o"), DG).unwrap());
+        assert_eq!(d.quads().count(), 2);
+
+        let subst = d.substitutions();
+        assert_eq!(subst.len(), 1);
+        assert_eq!(subst[0].original, "<foo>");
+        assert_eq!(
+            subst[0].placeholder,
+            Term::<String>::new_iri(format!(
+                "http://example.org/placeholder/{:032x}",
+                hash_str("<foo>")
+            ))
+            .unwrap()
+        );
+        assert!(d
+            .contains(&ex("s"), &ex("p"), &subst[0].placeholder, DG)
+            .unwrap());
+    }
+
+    #[test]
+    fn placeholders_bad_base() {
+        let repo = MemoryRepository::default();
+        match SophiaConnection::new(repo.connection().unwrap()).with_placeholders("placeholder/") {
+            Err(ConversionError::InvalidIri(base)) => assert_eq!(base, "placeholder/"),
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn graph_triple_sources() {
+        use sophia::graph::{inmem::FastGraph, Graph};
+        use sophia::parser::{nt::NTriplesParser, TripleParser};
+        use sophia::serializer::{nt::NtSerializer, Stringifier, TripleSerializer};
+        use sophia::triple::Triple;
+
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.insert(&ex("s"), &ex("p"), &ex("o1"), DG).unwrap();
+        d.insert(&ex("s"), &ex("p"), &lit("a"), Some(&ex("g1")))
+            .unwrap();
+        d.insert(&ex("s"), &ex("p"), &ex("o2"), Some(&ex("g1")))
+            .unwrap();
+        d.insert(&ex("s"), &ex("p"), &ex("o3"), Some(&ex("g2")))
+            .unwrap();
+
+        let mut names = vec![];
+        for (g, triples) in d.graph_triple_sources().unwrap() {
+            let mut ser = NtSerializer::new_stringifier();
+            let nt = ser.serialize_triples(triples).unwrap().as_str().to_string();
+            let parsed: FastGraph = NTriplesParser {}.parse_str(&nt).collect_triples().unwrap();
+            assert_eq!(parsed.triples().count(), d.quads_with_g(g.as_ref()).count());
+            for t in parsed.triples() {
+                let t = t.unwrap();
+                assert!(d.contains(t.s(), t.p(), t.o(), g.as_ref()).unwrap());
+            }
+            names.push(g);
+        }
+        assert_eq!(names, vec![None, Some(ex("g1")), Some(ex("g2"))]);
+    }
+
+    #[test]
+    fn quads_with_spog_lookup() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap()).with_bnode_cache();
+        let x = Term::<&str>::new_bnode("x").unwrap();
+        for i in 0..1000 {
+            d.insert(&x, &ex("p"), &ex(&format!("o{}", i)), Some(&ex("g")))
+                .unwrap();
+        }
+        let (p, o, g) = (ex("p"), ex("o500"), ex("g"));
+        let found: Vec<_> = d
+            .quads_with_spog(&x, &p, &o, Some(&g))
+            .map(|q| {
+                let q = q.unwrap();
+                (
+                    q.s().to_string(),
+                    q.p().to_string(),
+                    q.o().to_string(),
+                    q.g().map(ToString::to_string),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![(
+                x.to_string(),
+                p.to_string(),
+                o.to_string(),
+                Some(g.to_string())
+            )]
+        );
+        assert_eq!(d.quads_with_spog(&x, &p, &ex("o1000"), Some(&g)).count(), 0);
+        assert_eq!(d.quads_with_spog(&x, &p, &o, DG).count(), 0);
+        let y = Term::<&str>::new_bnode("y").unwrap();
+        assert_eq!(d.quads_with_spog(&y, &p, &o, Some(&g)).count(), 0);
+    }
+
+    #[test]
+    fn quads_with_spog_returns_stored_quad() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        let one = Term::<&str>::new_literal_dt("1.0", xsd::decimal).unwrap();
+        d.insert(&ex("s"), &ex("p"), &one, DG).unwrap();
+
+        let stored: Vec<_> = d
+            .quads()
+            .map(|q| q.unwrap().o().value().to_string())
+            .collect();
+        let looked_up: Vec<_> = d
+            .quads_with_spog(&ex("s"), &ex("p"), &one, DG)
+            .map(|q| q.unwrap().o().value().to_string())
+            .collect();
+        assert_eq!(looked_up.len(), 1);
+        assert_eq!(looked_up, stored);
+    }
+
+    #[test]
+    fn bnode_label_roundtrip() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        let x = Term::<&str>::new_bnode("x").unwrap();
+        d.insert(&x, &ex("p"), &ex("o"), DG).unwrap();
+
+        let quads: Vec<_> = d.quads_with_s(&x).collect();
+        assert_eq!(quads.len(), 1);
+        let q = quads[0].as_ref().unwrap();
+        assert_eq!(q.s(), &x);
+        assert_eq!(q.s().value(), "x");
+    }
+
+    #[test]
+    fn bnode_cache() {
+        let repo = MemoryRepository::default();
+        let uuid = Term::<&str>::new_bnode("b0f9c2a4-5d7e-4c1b-9a3f-2e8d6c4b1a07-0042").unwrap();
+        let weird = Term::BNode(SBlankNode::new_unchecked("not a valid bnode label"));
+
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        assert!(d.insert(&weird, &ex("p"), &ex("o"), DG).is_err());
+
+        let mut d = d.with_bnode_cache();
+        d.insert(&uuid, &ex("p"), &ex("o"), DG).unwrap();
+        d.insert(&weird, &ex("p"), &uuid, DG).unwrap();
+        assert!(d.contains(&weird, &ex("p"), &uuid, DG).unwrap());
+
+        let quads: Vec<_> = d.quads_with_s(&weird).collect();
+        assert_eq!(quads.len(), 1);
+        let q = quads[0].as_ref().unwrap();
+        assert_eq!(q.s().value(), "not a valid bnode label");
+        assert_eq!(q.o().value(), uuid.value());
+
+        let quads: Vec<_> = d.quads_with_s(&uuid).collect();
+        assert_eq!(quads.len(), 1);
+        assert_eq!(quads[0].as_ref().unwrap().s(), &uuid);
+    }
+
+    #[test]
+    fn lang_tag_case() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        let hello = Term::<&str>::new_literal_lang("x", "en-US").unwrap();
+        d.insert(&ex("s"), &ex("p"), &hello, DG).unwrap();
+
+        let quads: Vec<_> = d.quads_with_s(&ex("s")).collect();
+        match quads[0].as_ref().unwrap().o() {
+            Term::Literal(lit) => {
+                assert_eq!(lit.lang().map(|t| t.to_string()), Some("en-US".to_string()))
+            }
+            other => panic!("unexpected term {:?}", other),
+        }
+    }
+
+    #[test]
+    fn xsd_string() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.insert(&ex("s"), &ex("p"), &lit("abc"), DG).unwrap();
+
+        assert!(d.contains(&ex("s"), &ex("p"), &lit("abc"), DG).unwrap());
+        let simple = OTerm::from(oxigraph::model::Literal::new_simple_literal("abc"));
+        let s = d.oxi_s(&ex("s")).unwrap();
+        let p = d.oxi_p(&ex("p")).unwrap();
+        assert!(d
+            .as_oxi()
+            .contains(&OQuad::new(s, p, simple, None))
+            .unwrap());
+    }
+
+    #[test]
+    fn insert_variable() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap())
+            .with_placeholders("http://example.org/placeholder/")
+            .unwrap();
+        let x = Term::<&str>::new_variable("x").unwrap();
+        match d.insert(&x, &ex("p"), &ex("o"), DG) {
+            Err(MutationError::Conversion {
+                source: ConversionError::Variable(v),
+            }) => assert_eq!(v, "x"),
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert!(d.insert(&ex("s"), &ex("p"), &ex("o"), Some(&x)).is_err());
+        assert_eq!(d.quads().count(), 0);
+        assert!(d.substitutions().is_empty());
+        assert!(d.variables().unwrap().is_empty());
+    }
+
+    #[test]
+    fn insert_reports_new() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        assert!(d.insert(&ex("s"), &ex("p"), &ex("o"), DG).unwrap());
+        assert!(!d.insert(&ex("s"), &ex("p"), &ex("o"), DG).unwrap());
+        assert!(d
+            .insert(&ex("s"), &ex("p"), &ex("o"), Some(&ex("g")))
+            .unwrap());
+        assert_eq!(d.quads().count(), 2);
+    }
+
+    #[test]
+    fn remove_reports_present() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.insert(&ex("s"), &ex("p"), &ex("o"), DG).unwrap();
+        assert!(!d.remove(&ex("s"), &ex("p"), &ex("o2"), DG).unwrap());
+        assert!(!d
+            .remove(&ex("s"), &ex("p"), &ex("o"), Some(&ex("g")))
+            .unwrap());
+        assert!(d.remove(&ex("s"), &ex("p"), &ex("o"), DG).unwrap());
+        assert!(!d.remove(&ex("s"), &ex("p"), &ex("o"), DG).unwrap());
+        assert_eq!(d.quads().count(), 0);
+    }
+
+    #[test]
+    fn quads_matching() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        for i in 0..10 {
+            let g = ex(&format!("g{}", i % 2));
+            d.insert(&ex(&format!("s{}", i)), &ex("p"), &ex("o"), Some(&g))
+                .unwrap();
+            d.insert(&ex(&format!("s{}", i)), &ex("q"), &lit("foo"), DG)
+                .unwrap();
+        }
+        let p = ex("p");
+        let g0 = ex("g0");
+        let even = |t: &Term<&str>| {
+            t.value()
+                .ends_with(|c: char| c.to_digit(10).unwrap_or(1) % 2 == 0)
+        };
+
+        assert_eq!(d.quads_matching(&ANY, &p, &ANY, &ANY).count(), 10);
+        assert_eq!(d.quads_matching(&ANY, &ANY, &ANY, &Some(&g0)).count(), 5);
+        assert_eq!(d.quads_matching(&even, &ANY, &ANY, &ANY).count(), 10);
+        assert_eq!(d.quads_matching(&even, &p, &ANY, &ANY).count(), 5);
+        for q in d.quads_matching(&even, &p, &ANY, &ANY) {
+            assert_eq!(q.unwrap().g(), Some(&g0));
+        }
+    }
+
+    #[test]
+    fn count_and_is_empty() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        assert_eq!(d.count().unwrap(), 0);
+        assert!(d.is_empty().unwrap());
+
+        d.insert(&ex("s"), &ex("p"), &ex("o"), DG).unwrap();
+        d.insert(&ex("s"), &ex("p"), &ex("o"), Some(&ex("g")))
+            .unwrap();
+        d.insert(&ex("s"), &ex("p"), &lit("o"), Some(&ex("g")))
+            .unwrap();
+        assert_eq!(d.count().unwrap(), 3);
+        assert!(!d.is_empty().unwrap());
+    }
+
+    #[test]
+    fn select() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.insert(&ex("s1"), &ex("p"), &ex("o1"), DG).unwrap();
+        d.insert(&ex("s2"), &ex("p"), &lit("o2"), Some(&ex("g")))
+            .unwrap();
+        d.insert(&ex("s3"), &ex("q"), &ex("o3"), DG).unwrap();
+
+        let bindings = d
+            .select("SELECT ?s ?o WHERE { ?s <http://example.org/p> ?o }")
+            .unwrap();
+        assert_eq!(bindings.variables(), &["s".to_string(), "o".to_string()]);
+        let solutions: Vec<_> = bindings.collect::<Result<_, _>>().unwrap();
+        assert_eq!(solutions.len(), 1);
+        assert_eq!(solutions[0]["s"], ex("s1"));
+        assert_eq!(solutions[0]["o"], ex("o1"));
+
+        let mut objects: Vec<_> = d
+            .select("SELECT ?o { GRAPH ?g { ?s ?p ?o } }")
+            .unwrap()
+            .map(|b| b.unwrap()["o"].clone())
+            .collect();
+        assert_eq!(objects.pop(), Some(lit("o2")));
+    }
+
+    #[test]
+    fn select_is_lazy() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        for i in 0..10_000 {
+            d.insert(&ex(&format!("s{}", i)), &ex("p"), &ex("o"), DG)
+                .unwrap();
+        }
+
+        let mut bindings = d.select("SELECT ?s { ?s ?p ?o }").unwrap();
+        let first: Vec<_> = bindings.by_ref().take(3).collect();
+        assert_eq!(first.len(), 3);
+        assert!(first.iter().all(|b| b.as_ref().unwrap().contains_key("s")));
+        // the remaining solutions are still available, and were not converted yet
+        assert!(bindings.next().is_some());
+        assert_eq!(bindings.count(), 10_000 - 4);
+    }
+
+    #[test]
+    fn construct() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.insert(&ex("alice"), &ex("parent"), &ex("bob"), DG)
+            .unwrap();
+        d.insert(&ex("alice"), &ex("parent"), &ex("carol"), Some(&ex("g")))
+            .unwrap();
+        d.insert(&ex("bob"), &ex("name"), &lit("Bob"), DG).unwrap();
+
+        let mut triples: Vec<_> = d
+            .construct(
+                "CONSTRUCT { ?o <http://example.org/child> ?s } \
+                 WHERE { { ?s <http://example.org/parent> ?o } UNION \
+                 { GRAPH ?g { ?s <http://example.org/parent> ?o } } }",
+            )
+            .unwrap()
+            .map(|q| {
+                let q = q.unwrap();
+                assert!(q.g().is_none());
+                [q.s().clone(), q.p().clone(), q.o().clone()]
+            })
+            .collect();
+        triples.sort_by_key(|t| t[0].value().to_string());
+        assert_eq!(
+            triples,
+            vec![
+                [ex("bob"), ex("child"), ex("alice")],
+                [ex("carol"), ex("child"), ex("alice")],
+            ]
+        );
+    }
+
+    #[test]
+    fn ask() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.insert(&ex("s"), &ex("p"), &ex("o"), DG).unwrap();
+
+        assert!(d.ask("ASK { ?s <http://example.org/p> ?o }").unwrap());
+        assert!(!d.ask("ASK { ?s <http://example.org/q> ?o }").unwrap());
+        assert!(d.ask("SELECT ?s { ?s ?p ?o }").is_err());
+    }
+
+    #[test]
+    fn term_set_errors() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.insert(&ex("s"), &ex("p"), &ex("o"), DG).unwrap();
+        let run = |query: &str| {
+            let q = d
+                .as_oxi()
+                .prepare_query(query, QueryOptions::default())
+                .unwrap();
+            sparql_result_as_term_set(q.exec().unwrap())
+        };
+
+        assert_eq!(run("SELECT ?s { ?s ?p ?o }").unwrap().len(), 1);
+        assert!(matches!(
+            run("SELECT ?s ?o { ?s ?p ?o }"),
+            Err(QueryError::UnexpectedArity(2))
+        ));
+        assert!(matches!(
+            run("SELECT ?x { ?s ?p ?o OPTIONAL { ?o ?p ?x } }"),
+            Err(QueryError::NullBinding(_))
+        ));
+        assert!(matches!(
+            run("ASK { ?s ?p ?o }"),
+            Err(QueryError::WrongResultKind("boolean"))
+        ));
+    }
+
+    #[test]
+    fn update() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.update(
+            "INSERT DATA { <http://example.org/s> <http://example.org/p> <http://example.org/o> }",
+        )
+        .unwrap();
+        assert!(d.contains(&ex("s"), &ex("p"), &ex("o"), DG).unwrap());
+        d.update("DELETE WHERE { <http://example.org/s> ?p ?o }")
+            .unwrap();
+        assert!(!d.contains(&ex("s"), &ex("p"), &ex("o"), DG).unwrap());
+        assert!(d.update("INSERT DATA { oops }").is_err());
+
+        d.insert(&ex("s"), &ex("p"), &ex("o1"), DG).unwrap();
+        d.set_dry_run(true);
+        d.update(
+            "INSERT DATA { <http://example.org/s> <http://example.org/p> <http://example.org/o2> }",
+        )
+        .unwrap();
+        assert!(d.contains(&ex("s"), &ex("p"), &ex("o2"), DG).unwrap());
+        assert_eq!(
+            d.update_counted("DELETE WHERE { ?s ?p ?o }").unwrap(),
+            (0, 2)
+        );
+        let report = d.dry_run_report().unwrap();
+        assert_eq!(report.added().len(), 0);
+        assert_eq!(report.removed().len(), 1);
+        assert!(!d.contains(&ex("s"), &ex("p"), &ex("o1"), DG).unwrap());
+        assert_eq!(d.quads().count(), 1);
+        assert!(d.update("INSERT DATA { oops }").is_err());
+    }
+
+    #[test]
+    fn remove_matching() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        for i in 0..10 {
+            let s = ex(&format!("s{}", i));
+            d.insert(&s, &ex("p"), &ex("o"), DG).unwrap();
+            d.insert(&s, &ex("p"), &ex("o"), Some(&ex("g"))).unwrap();
+            d.insert(&s, &ex("q"), &ex("o"), DG).unwrap();
+        }
+        let p = ex("p");
+        assert_eq!(d.remove_matching(&ANY, &p, &ANY, &ANY).unwrap(), 20);
+        assert_eq!(d.count().unwrap(), 10);
+        assert_eq!(d.remove_matching(&ANY, &p, &ANY, &ANY).unwrap(), 0);
+    }
+
+    #[test]
+    fn insert_all() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.insert(&ex("s0"), &ex("p"), &ex("o"), DG).unwrap();
+        let quads: Vec<([Term<String>; 3], Option<Term<String>>)> = (0..1000)
+            .map(|i| ([ex(&format!("s{}", i)), ex("p"), ex("o")], None))
+            .collect();
+        assert_eq!(
+            d.insert_all(quads.into_iter().as_quad_source()).unwrap(),
+            999
+        );
+        assert_eq!(d.count().unwrap(), 1000);
+
+        let relative = Term::Iri(SIri::new_unchecked("foo".to_string(), false));
+        let bad: Vec<([Term<String>; 3], Option<Term<String>>)> = vec![
+            ([ex("new"), ex("p"), ex("o")], None),
+            ([ex("s"), ex("p"), relative], None),
+        ];
+        assert!(d.insert_all(bad.into_iter().as_quad_source()).is_err());
+        assert_eq!(d.count().unwrap(), 1000);
+    }
+
+    #[test]
+    fn remove_all() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        let quad = |i: usize| -> ([Term<String>; 3], Option<Term<String>>) {
+            ([ex(&format!("s{}", i)), ex("p"), ex("o")], None)
+        };
+        d.insert_all((0..500).map(quad).as_quad_source()).unwrap();
+
+        let relative = Term::Iri(SIri::new_unchecked("foo".to_string(), false));
+        let bad = vec![quad(0), ([ex("s"), ex("p"), relative], None)];
+        assert!(d.remove_all(bad.into_iter().as_quad_source()).is_err());
+        assert_eq!(d.count().unwrap(), 500);
+
+        let n = d.remove_all((200..700).map(quad).as_quad_source()).unwrap();
+        assert_eq!(n, 300);
+        assert_eq!(d.count().unwrap(), 200);
+    }
+
+    #[test]
+    fn quads_ref() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.insert(&ex("s"), &ex("p"), &lit("o"), Some(&ex("g")))
+            .unwrap();
+        let by_value = d.quads().next().unwrap().unwrap();
+        let mut n = 0;
+        d.quads_ref()
+            .try_for_each_quad(|q| -> Result<(), std::convert::Infallible> {
+                assert!(q.s() == by_value.s());
+                assert!(q.o() == by_value.o());
+                n += 1;
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(n, 1);
+    }
+
+    #[test]
+    fn sophia_graph() {
+        use sophia::triple::Triple;
+
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.insert(&ex("s1"), &ex("p"), &ex("o1"), DG).unwrap();
+        d.insert(&ex("s1"), &ex("p"), &ex("o2"), Some(&ex("g")))
+            .unwrap();
+        d.insert(&ex("s2"), &ex("p"), &ex("o3"), Some(&ex("g")))
+            .unwrap();
+
+        let mut g = d.graph(Some(&ex("g"))).unwrap();
+        assert_eq!(g.name(), Some(ex("g")));
+        assert_eq!(g.triples().count(), 2);
+        let t = g.triples_with_s(&ex("s1")).next().unwrap().unwrap();
+        assert_eq!(t.o(), &ex("o2"));
+        assert_eq!(g.triples_with_s(&ex("s1")).count(), 1);
+
+        assert!(MutableGraph::insert(&mut g, &ex("s3"), &ex("p"), &ex("o4")).unwrap());
+        assert!(!MutableGraph::insert(&mut g, &ex("s3"), &ex("p"), &ex("o4")).unwrap());
+        assert!(MutableGraph::remove(&mut g, &ex("s1"), &ex("p"), &ex("o2")).unwrap());
+        assert!(!MutableGraph::remove(&mut g, &ex("s1"), &ex("p"), &ex("o1")).unwrap());
+        assert_eq!(g.triples().count(), 2);
+
+        let d = g.into_connection();
+        assert!(d.contains(&ex("s1"), &ex("p"), &ex("o1"), DG).unwrap());
+        assert!(d
+            .contains(&ex("s3"), &ex("p"), &ex("o4"), Some(&ex("g")))
+            .unwrap());
+        assert_eq!(d.quads().count(), 3);
+
+        let dg = d.graph(DG).unwrap();
+        assert_eq!(dg.name(), None);
+        assert_eq!(dg.triples().count(), 1);
+    }
+
+    #[test]
+    fn load_from_reader() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        let ttl = r#"
+            @prefix : <http://example.org/>.
+            :s :p :o1, :o2, "foo".
+            :s :p [ :q <o3> ].
+        "#;
+        d.load_from_reader(
+            ttl.as_bytes(),
+            GraphSyntax::Turtle,
+            Some("http://example.org/"),
+        )
+        .unwrap();
+        assert_eq!(d.quads().count(), 5);
+        assert_eq!(d.bnodes().unwrap().len(), 1);
+        assert!(d.contains(&ex("s"), &ex("p"), &lit("foo"), DG).unwrap());
+        assert_eq!(d.quads_with_o(&ex("o3")).count(), 1);
+
+        let nt = "<http://example.org/s> <http://example.org/p> <http://example.org/o4> .\n";
+        d.load_from_reader(nt.as_bytes(), GraphSyntax::NTriples, None)
+            .unwrap();
+        assert_eq!(d.quads().count(), 6);
+
+        let mut d = d.with_dry_run();
+        let nt = "<http://example.org/s> <http://example.org/p> <http://example.org/o5> .\n";
+        d.load_from_reader(nt.as_bytes(), GraphSyntax::NTriples, None)
+            .unwrap();
+        assert_eq!(d.dry_run_report().unwrap().added().len(), 1);
+        assert_eq!(d.quads().count(), 6);
+    }
+
+    #[test]
+    fn dump_to_writer() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        let ttl = r#"
+            @prefix : <http://example.org/>.
+            :s :p :o1, "foo".
+        "#;
+        d.load_from_reader(ttl.as_bytes(), GraphSyntax::Turtle, None)
+            .unwrap();
+        d.insert(&ex("s"), &ex("p"), &ex("o2"), Some(&ex("g")))
+            .unwrap();
+
+        let mut nq = vec![];
+        d.dump_to_writer(&mut nq, LineFormat::NQuads).unwrap();
+        let nq = String::from_utf8(nq).unwrap();
+        assert_eq!(nq.lines().count(), 3);
+        assert!(nq
+            .contains("<http://example.org/s> <http://example.org/p> <http://example.org/o1> .\n"));
+        assert!(nq.contains("<http://example.org/s> <http://example.org/p> \"foo\" .\n"));
+        assert!(nq.contains(
+            "<http://example.org/s> <http://example.org/p> <http://example.org/o2> <http://example.org/g> .\n"
+        ));
+
+        assert!(d.dump_to_writer(vec![], LineFormat::NTriples).is_err());
+        let mut nt = vec![];
+        d.dump_graph_to_writer(&mut nt, LineFormat::NTriples, Some(&ex("g")))
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(nt).unwrap(),
+            "<http://example.org/s> <http://example.org/p> <http://example.org/o2> .\n"
+        );
+    }
+
+    #[test]
+    fn clear_graph() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        let b = Term::<String>::new_bnode("b").unwrap();
+        d.insert(&ex("s"), &ex("p"), &ex("o1"), DG).unwrap();
+        d.insert(&ex("s"), &ex("p"), &ex("o2"), Some(&ex("g1")))
+            .unwrap();
+        d.insert(&ex("s"), &ex("p"), &ex("o3"), Some(&ex("g2")))
+            .unwrap();
+        d.insert(&ex("s"), &ex("p"), &ex("o4"), Some(&b)).unwrap();
+
+        d.clear_graph(Some(&ex("g1"))).unwrap();
+        assert_eq!(d.quads().count(), 3);
+        assert_eq!(d.quads_with_g(Some(&ex("g1"))).count(), 0);
+
+        d.clear_graph(DG).unwrap();
+        assert_eq!(d.quads().count(), 2);
+        assert_eq!(d.quads_with_g(DG).count(), 0);
+        assert_eq!(d.quads_with_g(Some(&ex("g2"))).count(), 1);
+
+        d.clear_graph(Some(&lit("g"))).unwrap_err();
+        assert_eq!(d.quads().count(), 2);
+
+        d.clear_graph(Some(&b)).unwrap();
+        assert_eq!(d.quads().count(), 1);
+
+        let mut d = d.with_dry_run();
+        d.clear_graph(Some(&ex("g2"))).unwrap();
+        assert_eq!(d.dry_run_report().unwrap().removed().len(), 1);
+        assert_eq!(d.quads().count(), 1);
+    }
+
+    #[test]
+    fn into_quads() {
+        let repo = MemoryRepository::default();
+        let quads = {
+            let mut d = SophiaConnection::new(repo.connection().unwrap());
+            d.insert(&ex("s"), &ex("p"), &ex("o1"), DG).unwrap();
+            d.insert(&ex("s"), &ex("p"), &ex("o2"), Some(&ex("g")))
+                .unwrap();
+            d.into_quads()
+        };
+        let mut objects: Vec<_> = quads.map(|q| q.unwrap().o().clone()).collect();
+        objects.sort_by_key(|o| o.to_string());
+        assert_eq!(objects, vec![ex("o1"), ex("o2")]);
+    }
+
+    #[test]
+    fn prepared_queries_are_cached() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.insert(&ex("s"), &ex("p"), &ex("o"), DG).unwrap();
+        let first = d.subjects().unwrap();
+        d.insert(&ex("s2"), &ex("p"), &ex("o"), DG).unwrap();
+        let second = d.subjects().unwrap();
+        assert_eq!(first.len(), 1);
+        assert_eq!(second.len(), 2);
+        assert_eq!(d.prepared.queries.lock().unwrap().len(), 1);
+        d.subjects().unwrap();
+        assert_eq!(d.prepared.queries.lock().unwrap().len(), 1);
+        d.objects().unwrap();
+        assert_eq!(d.prepared.queries.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn try_contains() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.insert(&ex("s"), &ex("p"), &ex("o"), DG).unwrap();
+
+        assert!(d.try_contains(&ex("s"), &ex("p"), &ex("o"), DG).unwrap());
+
+        assert!(!d.contains(&lit("s"), &ex("p"), &ex("o"), DG).unwrap());
+        assert!(matches!(
+            d.try_contains(&lit("s"), &ex("p"), &ex("o"), DG),
+            Err(QueryError::Conversion {
+                source: ConversionError::Literal(_)
+            })
+        ));
+
+        assert!(!d.contains(&ex("s"), &ex("p"), &ex("o2"), DG).unwrap());
+        assert!(!d.try_contains(&ex("s"), &ex("p"), &ex("o2"), DG).unwrap());
+    }
+
+    #[test]
+    fn insert_skolemized() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        let b0 = Term::<String>::new_bnode("b0").unwrap();
+        let src1: Vec<([Term<String>; 3], Option<Term<String>>)> = vec![
+            ([ex("s1"), ex("p"), b0.clone()], None),
+            ([b0.clone(), ex("p"), ex("o1")], None),
+        ];
+        let src2: Vec<([Term<String>; 3], Option<Term<String>>)> =
+            vec![([ex("s2"), ex("p"), b0.clone()], None)];
+        let base = "http://example.org";
+        assert_eq!(
+            d.insert_skolemized(src1.into_iter().as_quad_source(), base)
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            d.insert_skolemized(src2.into_iter().as_quad_source(), base)
+                .unwrap(),
+            1
+        );
+        assert!(d.bnodes().unwrap().is_empty());
+
+        let o1 = d
+            .quads_with_s(&ex("s1"))
+            .next()
+            .unwrap()
+            .unwrap()
+            .o()
+            .clone();
+        let o2 = d
+            .quads_with_s(&ex("s2"))
+            .next()
+            .unwrap()
+            .unwrap()
+            .o()
+            .clone();
+        assert!(o1
+            .value()
+            .starts_with("http://example.org/.well-known/genid/"));
+        assert_ne!(o1, o2);
+        // the same label is mapped consistently within one source
+        assert_eq!(d.quads_with_s(&o1).count(), 1);
+        assert_eq!(d.quads_with_s(&o2).count(), 0);
+    }
+
+    #[test]
+    fn insert_skolemized_bad_base() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        let b0 = Term::<String>::new_bnode("b0").unwrap();
+        let src: Vec<([Term<String>; 3], Option<Term<String>>)> =
+            vec![([ex("s"), ex("p"), b0], None)];
+        let res = d.insert_skolemized(src.into_iter().as_quad_source(), "not an iri");
+        assert!(matches!(
+            res,
+            Err(SinkError(MutationError::Conversion {
+                source: ConversionError::InvalidIri(_)
+            }))
+        ));
+        assert_eq!(d.quads().count(), 0);
+    }
+
+    #[test]
+    fn quads_interned() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap()).with_interner();
+        for i in 0..10 {
+            d.insert(&ex(&format!("s{}", i)), &ex("p"), &lit("o"), DG)
+                .unwrap();
+        }
+        let quads = d.quads_interned().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(quads.len(), 10);
+        assert_eq!(quads[0].p(), &ex("p"));
+        assert_eq!(quads[0].o(), &lit("o"));
+        // 10 subjects, 1 predicate, 1 literal value and its datatype
+        let n = d.interner.as_ref().unwrap().lock().unwrap().len();
+        assert_eq!(n, 13);
+        // a second iteration reuses the interner of the connection
+        d.quads_interned().for_each(drop);
+        assert_eq!(d.interner.as_ref().unwrap().lock().unwrap().len(), n);
+    }
+
+    #[test]
+    fn add_to_dataset() {
+        use sophia::parser::trig::TriGParser;
+        use sophia::parser::QuadParser;
+
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        let ttl = r#"
+            @prefix : <http://example.org/>.
+            :s :p :o1, :o2, "foo".
+            :s :p [ :q :o3 ].
+        "#;
+        let n = TriGParser { base: None }
+            .parse_str(ttl)
+            .add_to_dataset(&mut d)
+            .unwrap();
+        assert_eq!(n, 5);
+        assert_eq!(d.quads_with_g(DG).count(), 5);
+        assert!(d.contains(&ex("s"), &ex("p"), &lit("foo"), DG).unwrap());
+
+        let relative = Term::Iri(SIri::new_unchecked("foo".to_string(), false));
+        let bad: Vec<([Term<String>; 3], Option<Term<String>>)> = vec![
+            ([ex("s"), ex("p"), ex("o4")], None),
+            ([ex("s"), ex("p"), relative], None),
+        ];
+        match bad.into_iter().as_quad_source().add_to_dataset(&mut d) {
+            Err(SinkError(MutationError::QuadConversion { index, source })) => {
+                assert_eq!(index, 1);
+                assert!(matches!(source, ConversionError::RelativeIriRef(_)));
+            }
+            _ => panic!("expected a QuadConversion error"),
+        }
+        assert_eq!(d.quads().count(), 5);
+    }
+
+    #[test]
+    fn default_graph_iri() {
+        let repo = MemoryRepository::default();
+        let dg = "http://example.org/default";
+        let mut d = SophiaConnection::new(repo.connection().unwrap())
+            .with_default_graph_iri(dg)
+            .unwrap();
+        assert_eq!(d.default_graph_iri(), Some(ex("default")));
+        d.insert(&ex("s"), &ex("p"), &ex("o1"), DG).unwrap();
+        d.insert(&ex("s"), &ex("p"), &ex("o2"), Some(&ex("default")))
+            .unwrap();
+        d.insert(&ex("s"), &ex("p"), &ex("o3"), Some(&ex("g")))
+            .unwrap();
+        assert_eq!(d.graph_names().unwrap().len(), 1);
+        assert_eq!(d.quads_with_g(DG).count(), 2);
+        assert_eq!(d.quads_with_g(Some(&ex("default"))).count(), 2);
+        assert!(d
+            .quads_with_g(Some(&ex("default")))
+            .all(|q| q.unwrap().g().is_none()));
+        assert!(d
+            .contains(&ex("s"), &ex("p"), &ex("o1"), Some(&ex("default")))
+            .unwrap());
+        assert!(d.contains(&ex("s"), &ex("p"), &ex("o2"), DG).unwrap());
+
+        d.remove(&ex("s"), &ex("p"), &ex("o1"), Some(&ex("default")))
+            .unwrap();
+        assert_eq!(d.quads_with_g(DG).count(), 1);
+
+        assert!(SophiaConnection::new(repo.connection().unwrap())
+            .with_default_graph_iri("not an iri")
+            .is_err());
+    }
+
+    #[test]
+    fn oxi_quads_with() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.insert(&ex("s"), &ex("p"), &ex("o1"), DG).unwrap();
+        d.insert(&ex("s"), &ex("p"), &lit("o2"), Some(&ex("g")))
+            .unwrap();
+        d.insert(&ex("s"), &ex("q"), &ex("o1"), DG).unwrap();
+        let any: Option<&Term<&str>> = None;
+        let any_g: Option<Option<&Term<&str>>> = None;
+        assert_eq!(d.oxi_quads_with(any, any, any, any_g).count(), 3);
+        assert_eq!(
+            d.oxi_quads_with(any, any, any, any_g).count(),
+            d.quads().count()
+        );
+        assert_eq!(
+            d.oxi_quads_with(Some(&ex("s")), Some(&ex("p")), any, any_g)
+                .count(),
+            d.quads_with_sp(&ex("s"), &ex("p")).count()
+        );
+        assert_eq!(
+            d.oxi_quads_with(any, any, any, Some(DG)).count(),
+            d.quads_with_g(DG).count()
+        );
+        let var = Term::<&str>::new_variable("x").unwrap();
+        assert_eq!(d.oxi_quads_with(Some(&var), any, any, any_g).count(), 0);
+    }
+
+    #[test]
+    fn flush() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.insert(&ex("s"), &ex("p"), &ex("o"), DG).unwrap();
+        assert!(d.flush().is_ok());
+        assert_eq!(d.quads().count(), 1);
+    }
+
+    #[test]
+    fn dataset_ref() {
+        fn count_subjects<D: Dataset>(d: D) -> usize {
+            d.subjects().unwrap().len()
+        }
+
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.insert(&ex("s1"), &ex("p"), &ex("o"), DG).unwrap();
+        d.insert(&ex("s2"), &ex("p"), &ex("o"), Some(&ex("g")))
+            .unwrap();
+        assert_eq!(count_subjects(&d), 2);
+        let r = &d;
+        assert_eq!(r.quads_with_g(DG).count(), 1);
+        assert!(r.contains(&ex("s1"), &ex("p"), &ex("o"), DG).unwrap());
+        assert_eq!(count_subjects(d), 2);
+    }
+
+    #[test]
+    fn graph_sizes() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        assert!(d.graph_sizes().unwrap().is_empty());
+        d.insert(&ex("s"), &ex("p"), &ex("o1"), DG).unwrap();
+        for i in 0..3 {
+            let o = ex(&format!("o{}", i));
+            d.insert(&ex("s"), &ex("p"), &o, Some(&ex("g1"))).unwrap();
+            d.insert(&ex("s"), &ex("q"), &o, Some(&ex("g2"))).unwrap();
+        }
+        d.insert(&ex("s"), &ex("p"), &lit("o"), Some(&ex("g2")))
+            .unwrap();
+
+        let sizes = d.graph_sizes().unwrap();
+        assert_eq!(sizes.len(), 3);
+        assert_eq!(sizes[&None], 1);
+        assert_eq!(sizes[&Some(ex("g1"))], 3);
+        assert_eq!(sizes[&Some(ex("g2"))], 4);
+    }
+
+    #[test]
+    fn conversion_mode() {
+        let quads = || {
+            let generalized: Vec<([Term<String>; 3], Option<Term<String>>)> = vec![
+                ([ex("s"), ex("p"), ex("o1")], None),
+                ([lit("s"), ex("p"), ex("o2")], None),
+                ([ex("s"), ex("p"), ex("o3")], None),
+            ];
+            generalized.into_iter().as_quad_source()
+        };
+
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        match d.insert_all(quads()) {
+            Err(SinkError(MutationError::QuadConversion { index, source })) => {
+                assert_eq!(index, 1);
+                assert!(source.is_generalized());
+            }
+            _ => panic!("expected a QuadConversion error"),
+        }
+        assert_eq!(d.quads().count(), 0);
+
+        let mut d = d.with_conversion_mode(ConversionMode::Lenient);
+        assert_eq!(d.insert_all(quads()).unwrap(), 2);
+        assert_eq!(d.quads().count(), 2);
+        assert!(!d.contains(&lit("s"), &ex("p"), &ex("o2"), DG).unwrap());
+
+        // other conversion errors still abort the insertion
+        let relative = Term::Iri(SIri::new_unchecked("foo".to_string(), false));
+        let bad: Vec<([Term<String>; 3], Option<Term<String>>)> =
+            vec![([ex("s"), ex("p"), relative], None)];
+        assert!(d.insert_all(bad.into_iter().as_quad_source()).is_err());
+    }
+
+    #[test]
+    fn to_inmem_dataset() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        let b = Term::<String>::new_bnode("b").unwrap();
+        d.insert(&ex("s"), &ex("p"), &ex("o"), DG).unwrap();
+        d.insert(&ex("s"), &ex("p"), &lit("o"), Some(&ex("g")))
+            .unwrap();
+        d.insert(&b, &ex("p"), &ex("o"), Some(&b)).unwrap();
+
+        let snapshot = d.to_inmem_dataset().unwrap();
+        assert_eq!(snapshot.quads().count(), 3);
+        for q in d.quads() {
+            let q = q.unwrap();
+            assert!(snapshot.contains(q.s(), q.p(), q.o(), q.g()).unwrap());
+        }
+
+        // the snapshot is independent of the store
+        d.remove(&ex("s"), &ex("p"), &ex("o"), DG).unwrap();
+        assert_eq!(snapshot.quads().count(), 3);
+    }
+
+    #[test]
+    fn is_isomorphic_to() {
+        use sophia::parser::trig::TriGParser;
+        use sophia::parser::QuadParser;
+
+        let trig = r#"
+            @prefix : <http://example.org/>.
+            :s :p [ :q "a" ], [ :q "b" ].
+            :g { [] :p :o }
+        "#;
+        let source: FastDataset = TriGParser { base: None }
+            .parse_str(trig)
+            .collect_quads()
+            .unwrap();
+
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        TriGParser { base: None }
+            .parse_str(trig)
+            .add_to_dataset(&mut d)
+            .unwrap();
+        assert!(d.is_isomorphic_to(&source).unwrap());
+        assert!(d.is_isomorphic_to(&d.to_inmem_dataset().unwrap()).unwrap());
+
+        d.insert(&ex("s"), &ex("p"), &lit("c"), DG).unwrap();
+        assert!(!d.is_isomorphic_to(&source).unwrap());
+        d.remove(&ex("s"), &ex("p"), &lit("c"), DG).unwrap();
+        let b = d.bnodes().unwrap().into_iter().next().unwrap();
+        d.insert(&b, &ex("r"), &ex("o"), DG).unwrap();
+        assert!(!d.is_isomorphic_to(&source).unwrap());
+    }
+
+    #[test]
+    fn is_isomorphic_to_regular_structures() {
+        use sophia::parser::trig::TriGParser;
+        use sophia::parser::QuadParser;
+
+        let parse = |trig: &str| -> FastDataset {
+            TriGParser { base: None }
+                .parse_str(&format!("@prefix : <http://example.org/>.\n{}", trig))
+                .collect_quads()
+                .unwrap()
+        };
+        let two_triangles =
+            "_:a :p _:b. _:b :p _:c. _:c :p _:a. _:d :p _:e. _:e :p _:f. _:f :p _:d.";
+        let hexagon = "_:a :p _:b. _:b :p _:c. _:c :p _:d. _:d :p _:e. _:e :p _:f. _:f :p _:a.";
+        let relabelled = "_:x :p _:y. _:y :p _:z. _:z :p _:x. _:u :p _:v. _:v :p _:w. _:w :p _:u.";
+
+        let repo1 = MemoryRepository::default();
+        let mut d1 = SophiaConnection::new(repo1.connection().unwrap());
+        d1.insert_all(parse(two_triangles).quads()).unwrap();
+        let repo2 = MemoryRepository::default();
+        let mut d2 = SophiaConnection::new(repo2.connection().unwrap());
+        d2.insert_all(parse(hexagon).quads()).unwrap();
+
+        assert_eq!(d1.content_hash().unwrap(), d2.content_hash().unwrap());
+        assert!(!d1.is_isomorphic_to(&parse(hexagon)).unwrap());
+        assert!(!d2.is_isomorphic_to(&parse(two_triangles)).unwrap());
+        assert!(d1.is_isomorphic_to(&parse(relabelled)).unwrap());
+        assert!(d2.is_isomorphic_to(&parse(hexagon)).unwrap());
+    }
+
+    #[test]
+    fn parse_count() {
+        use oxigraph::model::Literal;
+
+        let int = |v: &str| -> Option<OTerm> {
+            Some(
+                Literal::new_typed_literal(
+                    v,
+                    NamedNode::new("http://www.w3.org/2001/XMLSchema#integer").unwrap(),
+                )
+                .into(),
+            )
+        };
+        assert_eq!(super::parse_count(int("42")).unwrap(), 42);
+        assert!(super::parse_count(int("-1")).is_err());
+        assert!(super::parse_count(int("many")).is_err());
+        let iri = NamedNode::new("http://example.org/c").unwrap();
+        assert!(super::parse_count(Some(iri.into())).is_err());
+        assert!(super::parse_count(None).is_err());
+    }
+
+    #[test]
+    fn explain() {
+        let repo = MemoryRepository::default();
+        let d = SophiaConnection::new(repo.connection().unwrap());
+        let plan = d.explain("SELECT ?s { ?s ?p ?o }").unwrap();
+        assert!(!plan.is_empty());
+        assert_eq!(plan, EXPLAIN_NOT_SUPPORTED);
+        assert!(d.explain("SELECT ?s {").is_err());
+    }
+
+    #[test]
+    fn dry_run_batches() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap()).with_dry_run();
+        let quads: Vec<([Term<String>; 3], Option<Term<String>>)> = vec![
+            ([ex("s"), ex("p"), ex("o")], None),
+            ([ex("s"), ex("p"), ex("o")], None),
+        ];
+        assert_eq!(
+            d.insert_all(quads.clone().into_iter().as_quad_source())
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            d.insert_all(quads.clone().into_iter().as_quad_source())
+                .unwrap(),
+            0
+        );
+        assert_eq!(d.dry_run_report().unwrap().added().len(), 1);
+        assert!(d.contains(&ex("s"), &ex("p"), &ex("o"), DG).unwrap());
+
+        assert_eq!(d.remove_matching(&ANY, &ANY, &ANY, &ANY).unwrap(), 1);
+        assert_eq!(d.remove_matching(&ANY, &ANY, &ANY, &ANY).unwrap(), 0);
+        assert!(d.dry_run_report().unwrap().added().is_empty());
+        assert!(!d.contains(&ex("s"), &ex("p"), &ex("o"), DG).unwrap());
+        assert_eq!(d.quads().count(), 0);
+    }
 }
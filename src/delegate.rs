@@ -0,0 +1,324 @@
+//! Generation of `Dataset` implementations delegating to a `SophiaConnection`
+use sophia_term::Term;
+
+/// Wildcard for the subject, predicate or object of a pattern
+pub(crate) const ANY_TERM: Option<&Term<&str>> = None;
+/// Wildcard for the graph name of a pattern
+pub(crate) const ANY_GRAPH: Option<Option<&Term<&str>>> = None;
+
+/// Implement `Dataset` for a type wrapping a `SophiaConnection`,
+/// by delegating every method to that connection.
+///
+/// The way the connection is reached is given after the impl header, and is one of
+/// * `deref(|this| expr)`, where `expr` borrows the connection from `this: &Self`;
+/// * `closure(quads, call)`, where `quads` and `call` are functions
+///   taking `&self` and a closure, and calling it with the connection;
+///   `quads` is used for the methods returning quads, `call` for all the others;
+/// * `pattern(quads, matching, call)`, where `call` is as above,
+///   `quads` takes `&self` and the four components of a pattern (see `SophiaConnection::oxi_quads_with`),
+///   and `matching` takes `&self` and four matchers.
+macro_rules! delegate_dataset {
+    (impl[$($gen:tt)*] $ty:ty where [$($bound:tt)*], $kind:ident $how:tt) => {
+        delegate_dataset!(@impl [$($gen)*] $ty, [$($bound)*], ($kind $how));
+    };
+
+    (@quads (deref (|$this:ident| $inner:expr)), $self_:ident, $m:ident($($arg:ident),*), $pattern:tt) => {
+        ({
+            let $this = $self_;
+            $inner
+        })
+        .$m($($arg),*)
+    };
+    (@quads (closure ($quads:path, $call:path)), $self_:ident, $m:ident($($arg:ident),*), $pattern:tt) => {
+        $quads($self_, |c| c.$m($($arg),*))
+    };
+    (@quads (pattern ($quads:path, $matching:path, $call:path)), $self_:ident, $m:ident($($arg:ident),*), ($s:tt, $p:tt, $o:tt, $g:tt)) => {
+        $quads(
+            $self_,
+            delegate_dataset!(@term $s),
+            delegate_dataset!(@term $p),
+            delegate_dataset!(@term $o),
+            delegate_dataset!(@graph $g),
+        )
+    };
+    (@term _) => {
+        $crate::delegate::ANY_TERM
+    };
+    (@term $t:ident) => {
+        Some($t)
+    };
+    (@graph _) => {
+        $crate::delegate::ANY_GRAPH
+    };
+    (@graph $g:ident) => {
+        Some($g)
+    };
+
+    (@matching (pattern ($quads:path, $matching:path, $call:path)), $self_:ident, $m:ident($($arg:ident),*)) => {
+        $matching($self_, $($arg),*)
+    };
+    (@matching $how:tt, $self_:ident, $m:ident($($arg:ident),*)) => {
+        delegate_dataset!(@quads $how, $self_, $m($($arg),*), ())
+    };
+
+    (@call (deref (|$this:ident| $inner:expr)), $self_:ident, $m:ident($($arg:ident),*)) => {
+        delegate_dataset!(@quads (deref (|$this| $inner)), $self_, $m($($arg),*), ())
+    };
+    (@call (closure ($quads:path, $call:path)), $self_:ident, $m:ident($($arg:ident),*)) => {
+        $call($self_, |c| c.$m($($arg),*))
+    };
+    (@call (pattern ($quads:path, $matching:path, $call:path)), $self_:ident, $m:ident($($arg:ident),*)) => {
+        $call($self_, |c| c.$m($($arg),*))
+    };
+
+    (@impl [$($gen:tt)*] $ty:ty, [$($bound:tt)*], $how:tt) => {
+        impl<$($gen)*> sophia::dataset::Dataset for $ty
+        where
+            $($bound)*
+        {
+            type Quad = sophia::quad::streaming_mode::ByValue<$crate::quad::QuadBridge>;
+            type Error = oxigraph::Error;
+
+            #[inline]
+            fn quads(&self) -> sophia::dataset::DQuadSource<Self> {
+                delegate_dataset!(@quads $how, self, quads(), (_, _, _, _))
+            }
+            #[inline]
+            fn quads_with_s<'s, T>(&'s self, s: &'s sophia_term::Term<T>) -> sophia::dataset::DQuadSource<'s, Self>
+            where
+                T: sophia_term::TermData,
+            {
+                delegate_dataset!(@quads $how, self, quads_with_s(s), (s, _, _, _))
+            }
+            #[inline]
+            fn quads_with_p<'s, T>(&'s self, p: &'s sophia_term::Term<T>) -> sophia::dataset::DQuadSource<'s, Self>
+            where
+                T: sophia_term::TermData,
+            {
+                delegate_dataset!(@quads $how, self, quads_with_p(p), (_, p, _, _))
+            }
+            #[inline]
+            fn quads_with_o<'s, T>(&'s self, o: &'s sophia_term::Term<T>) -> sophia::dataset::DQuadSource<'s, Self>
+            where
+                T: sophia_term::TermData,
+            {
+                delegate_dataset!(@quads $how, self, quads_with_o(o), (_, _, o, _))
+            }
+            #[inline]
+            fn quads_with_g<'s, T>(&'s self, g: Option<&'s sophia_term::Term<T>>) -> sophia::dataset::DQuadSource<'s, Self>
+            where
+                T: sophia_term::TermData,
+            {
+                delegate_dataset!(@quads $how, self, quads_with_g(g), (_, _, _, g))
+            }
+            #[inline]
+            fn quads_with_sp<'s, T, U>(
+                &'s self,
+                s: &'s sophia_term::Term<T>,
+                p: &'s sophia_term::Term<U>,
+            ) -> sophia::dataset::DQuadSource<'s, Self>
+            where
+                T: sophia_term::TermData,
+                U: sophia_term::TermData,
+            {
+                delegate_dataset!(@quads $how, self, quads_with_sp(s, p), (s, p, _, _))
+            }
+            #[inline]
+            fn quads_with_so<'s, T, U>(
+                &'s self,
+                s: &'s sophia_term::Term<T>,
+                o: &'s sophia_term::Term<U>,
+            ) -> sophia::dataset::DQuadSource<'s, Self>
+            where
+                T: sophia_term::TermData,
+                U: sophia_term::TermData,
+            {
+                delegate_dataset!(@quads $how, self, quads_with_so(s, o), (s, _, o, _))
+            }
+            #[inline]
+            fn quads_with_sg<'s, T, U>(
+                &'s self,
+                s: &'s sophia_term::Term<T>,
+                g: Option<&'s sophia_term::Term<U>>,
+            ) -> sophia::dataset::DQuadSource<'s, Self>
+            where
+                T: sophia_term::TermData,
+                U: sophia_term::TermData,
+            {
+                delegate_dataset!(@quads $how, self, quads_with_sg(s, g), (s, _, _, g))
+            }
+            #[inline]
+            fn quads_with_po<'s, T, U>(
+                &'s self,
+                p: &'s sophia_term::Term<T>,
+                o: &'s sophia_term::Term<U>,
+            ) -> sophia::dataset::DQuadSource<'s, Self>
+            where
+                T: sophia_term::TermData,
+                U: sophia_term::TermData,
+            {
+                delegate_dataset!(@quads $how, self, quads_with_po(p, o), (_, p, o, _))
+            }
+            #[inline]
+            fn quads_with_pg<'s, T, U>(
+                &'s self,
+                p: &'s sophia_term::Term<T>,
+                g: Option<&'s sophia_term::Term<U>>,
+            ) -> sophia::dataset::DQuadSource<'s, Self>
+            where
+                T: sophia_term::TermData,
+                U: sophia_term::TermData,
+            {
+                delegate_dataset!(@quads $how, self, quads_with_pg(p, g), (_, p, _, g))
+            }
+            #[inline]
+            fn quads_with_og<'s, T, U>(
+                &'s self,
+                o: &'s sophia_term::Term<T>,
+                g: Option<&'s sophia_term::Term<U>>,
+            ) -> sophia::dataset::DQuadSource<'s, Self>
+            where
+                T: sophia_term::TermData,
+                U: sophia_term::TermData,
+            {
+                delegate_dataset!(@quads $how, self, quads_with_og(o, g), (_, _, o, g))
+            }
+            #[inline]
+            fn quads_with_spo<'s, T, U, V>(
+                &'s self,
+                s: &'s sophia_term::Term<T>,
+                p: &'s sophia_term::Term<U>,
+                o: &'s sophia_term::Term<V>,
+            ) -> sophia::dataset::DQuadSource<'s, Self>
+            where
+                T: sophia_term::TermData,
+                U: sophia_term::TermData,
+                V: sophia_term::TermData,
+            {
+                delegate_dataset!(@quads $how, self, quads_with_spo(s, p, o), (s, p, o, _))
+            }
+            #[inline]
+            fn quads_with_spg<'s, T, U, V>(
+                &'s self,
+                s: &'s sophia_term::Term<T>,
+                p: &'s sophia_term::Term<U>,
+                g: Option<&'s sophia_term::Term<V>>,
+            ) -> sophia::dataset::DQuadSource<'s, Self>
+            where
+                T: sophia_term::TermData,
+                U: sophia_term::TermData,
+                V: sophia_term::TermData,
+            {
+                delegate_dataset!(@quads $how, self, quads_with_spg(s, p, g), (s, p, _, g))
+            }
+            #[inline]
+            fn quads_with_sog<'s, T, U, V>(
+                &'s self,
+                s: &'s sophia_term::Term<T>,
+                o: &'s sophia_term::Term<U>,
+                g: Option<&'s sophia_term::Term<V>>,
+            ) -> sophia::dataset::DQuadSource<'s, Self>
+            where
+                T: sophia_term::TermData,
+                U: sophia_term::TermData,
+                V: sophia_term::TermData,
+            {
+                delegate_dataset!(@quads $how, self, quads_with_sog(s, o, g), (s, _, o, g))
+            }
+            #[inline]
+            fn quads_with_pog<'s, T, U, V>(
+                &'s self,
+                p: &'s sophia_term::Term<T>,
+                o: &'s sophia_term::Term<U>,
+                g: Option<&'s sophia_term::Term<V>>,
+            ) -> sophia::dataset::DQuadSource<'s, Self>
+            where
+                T: sophia_term::TermData,
+                U: sophia_term::TermData,
+                V: sophia_term::TermData,
+            {
+                delegate_dataset!(@quads $how, self, quads_with_pog(p, o, g), (_, p, o, g))
+            }
+            #[inline]
+            fn quads_with_spog<'s, T, U, V, W>(
+                &'s self,
+                s: &'s sophia_term::Term<T>,
+                p: &'s sophia_term::Term<U>,
+                o: &'s sophia_term::Term<V>,
+                g: Option<&'s sophia_term::Term<W>>,
+            ) -> sophia::dataset::DQuadSource<'s, Self>
+            where
+                T: sophia_term::TermData,
+                U: sophia_term::TermData,
+                V: sophia_term::TermData,
+                W: sophia_term::TermData,
+            {
+                delegate_dataset!(@quads $how, self, quads_with_spog(s, p, o, g), (s, p, o, g))
+            }
+            #[inline]
+            fn contains<T, U, V, W>(
+                &self,
+                s: &sophia_term::Term<T>,
+                p: &sophia_term::Term<U>,
+                o: &sophia_term::Term<V>,
+                g: Option<&sophia_term::Term<W>>,
+            ) -> sophia::dataset::DResult<Self, bool>
+            where
+                T: sophia_term::TermData,
+                U: sophia_term::TermData,
+                V: sophia_term::TermData,
+                W: sophia_term::TermData,
+            {
+                delegate_dataset!(@call $how, self, contains(s, p, o, g))
+            }
+            #[inline]
+            fn quads_matching<'s, S, P, O, G>(
+                &'s self,
+                ms: &'s S,
+                mp: &'s P,
+                mo: &'s O,
+                mg: &'s G,
+            ) -> sophia::dataset::DQuadSource<'s, Self>
+            where
+                S: sophia_term::matcher::TermMatcher + ?Sized,
+                P: sophia_term::matcher::TermMatcher + ?Sized,
+                O: sophia_term::matcher::TermMatcher + ?Sized,
+                G: sophia_term::matcher::GraphNameMatcher + ?Sized,
+            {
+                delegate_dataset!(@matching $how, self, quads_matching(ms, mp, mo, mg))
+            }
+            #[inline]
+            fn subjects(&self) -> sophia::dataset::DResultTermSet<Self> {
+                delegate_dataset!(@call $how, self, subjects())
+            }
+            #[inline]
+            fn predicates(&self) -> sophia::dataset::DResultTermSet<Self> {
+                delegate_dataset!(@call $how, self, predicates())
+            }
+            #[inline]
+            fn objects(&self) -> sophia::dataset::DResultTermSet<Self> {
+                delegate_dataset!(@call $how, self, objects())
+            }
+            #[inline]
+            fn graph_names(&self) -> sophia::dataset::DResultTermSet<Self> {
+                delegate_dataset!(@call $how, self, graph_names())
+            }
+            #[inline]
+            fn iris(&self) -> sophia::dataset::DResultTermSet<Self> {
+                delegate_dataset!(@call $how, self, iris())
+            }
+            #[inline]
+            fn bnodes(&self) -> sophia::dataset::DResultTermSet<Self> {
+                delegate_dataset!(@call $how, self, bnodes())
+            }
+            #[inline]
+            fn literals(&self) -> sophia::dataset::DResultTermSet<Self> {
+                delegate_dataset!(@call $how, self, literals())
+            }
+            #[inline]
+            fn variables(&self) -> sophia::dataset::DResultTermSet<Self> {
+                delegate_dataset!(@call $how, self, variables())
+            }
+        }
+    };
+}
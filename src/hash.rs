@@ -0,0 +1,228 @@
+//! Blank-node-insensitive hashing of a set of quads.
+//!
+//! Blank nodes are not hashed by their label,
+//! but by iteratively refining a hash of their neighbourhood
+//! (in the spirit of the Weisfeiler-Lehman test).
+//! Two isomorphic sets of quads therefore always have the same hash.
+//! The converse does not hold:
+//! this refinement can not tell apart blank nodes in regular structures
+//! (e.g. two 3-cycles and one 6-cycle of blank nodes get the same hash),
+//! so equal hashes must never be taken as a proof of isomorphism;
+//! use [`isomorphic`] for that.
+//!
+//! The hash function used is 128-bit FNV-1a,
+//! which is stable across platforms and compiler versions.
+use oxigraph::model::{BlankNode, Quad, Term};
+use std::collections::{HashMap, HashSet};
+
+const FNV_OFFSET: u128 = 0x6c62_272e_07bb_0142_62b8_2175_6295_c58d;
+const FNV_PRIME: u128 = 0x0000_0000_0100_0000_0000_0000_0000_013b;
+
+/// Marks the absence of a graph name (default graph)
+const DEFAULT_GRAPH: u128 = 1;
+/// Marks the blank node whose neighbourhood is being hashed
+const SELF: u128 = 2;
+/// Distinguishes a blank node singled out by [`isomorphic`] from the rest of its class
+const INDIVIDUALIZED: u128 = 3;
+
+/// Incremental 128-bit FNV-1a hasher
+struct Fnv(u128);
+
+impl Fnv {
+    fn new() -> Self {
+        Fnv(FNV_OFFSET)
+    }
+    fn bytes(mut self, bytes: &[u8]) -> Self {
+        for b in bytes {
+            self.0 ^= u128::from(*b);
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+        self
+    }
+    fn u128(self, n: u128) -> Self {
+        self.bytes(&n.to_le_bytes())
+    }
+    fn finish(self) -> u128 {
+        self.0
+    }
+}
+
+type Components = [Option<Term>; 4];
+
+/// Compute a blank-node-insensitive hash of `quads`.
+///
+/// `quads` is assumed to contain no duplicate.
+pub(crate) fn hash_quads<I: IntoIterator<Item = Quad>>(quads: I) -> u128 {
+    let quads = components(quads);
+    let bnodes = refine(&quads, initial_colors(&quads));
+    let mut quad_hashes: Vec<u128> = quads
+        .iter()
+        .map(|q| {
+            q.iter()
+                .fold(Fnv::new(), |h, t| h.u128(term_hash(t, &bnodes)))
+                .finish()
+        })
+        .collect();
+    quad_hashes.sort_unstable();
+    quad_hashes.into_iter().fold(Fnv::new(), Fnv::u128).finish()
+}
+
+/// Whether `a` and `b` are equal, up to the labels of their blank nodes.
+///
+/// Both are assumed to contain no duplicate.
+/// Blank nodes are first partitioned by [`refine`];
+/// whenever the partition leaves several candidates,
+/// one blank node of `a` is paired in turn with each candidate of `b`
+/// and the refinement is resumed, until a bijection is found and checked.
+pub(crate) fn isomorphic<I, J>(a: I, b: J) -> bool
+where
+    I: IntoIterator<Item = Quad>,
+    J: IntoIterator<Item = Quad>,
+{
+    let a = components(a);
+    let b = components(b);
+    if a.len() != b.len() {
+        return false;
+    }
+    let colors_a = refine(&a, initial_colors(&a));
+    let colors_b = refine(&b, initial_colors(&b));
+    let b_set: HashSet<&Components> = b.iter().collect();
+    search(&a, colors_a, &b, colors_b, &b_set)
+}
+
+fn search(
+    a: &[Components],
+    colors_a: HashMap<BlankNode, u128>,
+    b: &[Components],
+    colors_b: HashMap<BlankNode, u128>,
+    b_set: &HashSet<&Components>,
+) -> bool {
+    let histogram = color_histogram(&colors_a);
+    if histogram != color_histogram(&colors_b) {
+        return false;
+    }
+    let ambiguous = histogram
+        .into_iter()
+        .filter(|(_, n)| *n > 1)
+        .min_by_key(|(c, n)| (*n, *c));
+    match ambiguous {
+        None => {
+            let inverse: HashMap<u128, &BlankNode> =
+                colors_b.iter().map(|(bn, c)| (*c, bn)).collect();
+            a.iter().all(|q| {
+                let mut mapped = q.clone();
+                for t in mapped.iter_mut() {
+                    if let Some(Term::BlankNode(bn)) = t {
+                        let c = colors_a[&*bn];
+                        *bn = inverse[&c].clone();
+                    }
+                }
+                b_set.contains(&mapped)
+            })
+        }
+        Some((color, _)) => {
+            let individualized = Fnv::new().u128(color).u128(INDIVIDUALIZED).finish();
+            let x = colors_a
+                .iter()
+                .find(|(_, c)| **c == color)
+                .map(|(bn, _)| bn.clone())
+                .unwrap();
+            let mut refined_a = colors_a;
+            refined_a.insert(x, individualized);
+            let refined_a = refine(a, refined_a);
+            colors_b.iter().filter(|(_, c)| **c == color).any(|(y, _)| {
+                let mut refined_b = colors_b.clone();
+                refined_b.insert(y.clone(), individualized);
+                let refined_b = refine(b, refined_b);
+                search(a, refined_a.clone(), b, refined_b, b_set)
+            })
+        }
+    }
+}
+
+fn components<I: IntoIterator<Item = Quad>>(quads: I) -> Vec<Components> {
+    quads
+        .into_iter()
+        .map(|q| {
+            let (s, p, o, g) = q.destruct();
+            [Some(s.into()), Some(p.into()), Some(o), g.map(Term::from)]
+        })
+        .collect()
+}
+
+fn initial_colors(quads: &[Components]) -> HashMap<BlankNode, u128> {
+    let mut bnodes = HashMap::new();
+    for q in quads {
+        for t in q.iter() {
+            if let Some(Term::BlankNode(b)) = t {
+                bnodes.insert(b.clone(), 0);
+            }
+        }
+    }
+    bnodes
+}
+
+fn color_histogram(bnodes: &HashMap<BlankNode, u128>) -> HashMap<u128, usize> {
+    let mut histogram = HashMap::new();
+    for c in bnodes.values() {
+        *histogram.entry(*c).or_default() += 1;
+    }
+    histogram
+}
+
+/// Refine blank node hashes until the partition they induce is stable.
+fn refine(quads: &[Components], mut bnodes: HashMap<BlankNode, u128>) -> HashMap<BlankNode, u128> {
+    let mut classes = color_histogram(&bnodes).len();
+    for _ in 0..bnodes.len() {
+        let mut signatures: HashMap<&BlankNode, Vec<u128>> = HashMap::new();
+        for q in quads {
+            for (i, t) in q.iter().enumerate() {
+                if let Some(Term::BlankNode(b)) = t {
+                    let h = q
+                        .iter()
+                        .enumerate()
+                        .fold(Fnv::new().u128(i as u128), |h, (j, t)| {
+                            if i == j {
+                                h.u128(SELF)
+                            } else {
+                                h.u128(term_hash(t, &bnodes))
+                            }
+                        })
+                        .finish();
+                    signatures.entry(b).or_default().push(h);
+                }
+            }
+        }
+        let refined: HashMap<BlankNode, u128> = signatures
+            .into_iter()
+            .map(|(b, mut sig)| {
+                sig.sort_unstable();
+                let h = sig
+                    .into_iter()
+                    .fold(Fnv::new().u128(bnodes[b]), Fnv::u128)
+                    .finish();
+                (b.clone(), h)
+            })
+            .collect();
+        bnodes = refined;
+        let refined_classes = color_histogram(&bnodes).len();
+        if refined_classes == classes {
+            break;
+        }
+        classes = refined_classes;
+    }
+    bnodes
+}
+
+/// Compute the 128-bit FNV-1a hash of `txt`.
+pub(crate) fn hash_str(txt: &str) -> u128 {
+    Fnv::new().bytes(txt.as_bytes()).finish()
+}
+
+fn term_hash(t: &Option<Term>, bnodes: &HashMap<BlankNode, u128>) -> u128 {
+    match t {
+        None => DEFAULT_GRAPH,
+        Some(Term::BlankNode(b)) => bnodes[b],
+        Some(t) => Fnv::new().bytes(t.to_string().as_bytes()).finish(),
+    }
+}
@@ -0,0 +1,172 @@
+//! Batched insertion of quads into a SophiaConnection
+use crate::connection::{MutationError, SophiaConnection};
+use oxigraph::model::Quad as OQuad;
+use oxigraph::RepositoryConnection;
+use sophia::quad::Quad;
+use std::time::{Duration, Instant};
+
+/// Accepts quads one by one, and commits them to a [`SophiaConnection`] by batches.
+///
+/// A batch is committed (in a single transaction)
+/// when it reaches the batch size, or when the maximum delay (if any) has expired.
+/// Note that the delay is only checked when a quad is pushed:
+/// no commit happens in the background.
+///
+/// The remaining quads are committed by [`flush`](#method.flush),
+/// or when the inserter is dropped (in which case errors are logged as warnings).
+///
+/// [`SophiaConnection`]: ../connection/struct.SophiaConnection.html
+pub struct Inserter<'a, C: RepositoryConnection> {
+    conn: &'a mut SophiaConnection<C>,
+    batch: Vec<OQuad>,
+    batch_size: usize,
+    max_delay: Option<Duration>,
+    last_commit: Instant,
+    commits: usize,
+    inserted: usize,
+}
+
+impl<'a, C> Inserter<'a, C>
+where
+    C: RepositoryConnection,
+{
+    /// Create an inserter committing to `conn` by batches of `batch_size`.
+    ///
+    /// See also [`SophiaConnection::inserter`](../connection/struct.SophiaConnection.html#method.inserter).
+    pub fn new(conn: &'a mut SophiaConnection<C>, batch_size: usize) -> Self {
+        let batch_size = batch_size.max(1);
+        Inserter {
+            conn,
+            batch: Vec::with_capacity(batch_size),
+            batch_size,
+            max_delay: None,
+            last_commit: Instant::now(),
+            commits: 0,
+            inserted: 0,
+        }
+    }
+
+    /// Also commit pending quads when `max_delay` has expired since the last commit.
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = Some(max_delay);
+        self
+    }
+
+    /// Push a quad, committing the current batch if necessary.
+    ///
+    /// Conversion errors are reported immediately,
+    /// and the offending quad is not added to the batch.
+    pub fn push<Q: Quad>(&mut self, q: &Q) -> Result<(), MutationError> {
+        let quad = self.conn.oxi_quad(q.s(), q.p(), q.o(), q.g())?;
+        self.batch.push(quad);
+        let expired = match self.max_delay {
+            Some(delay) => self.last_commit.elapsed() >= delay,
+            None => false,
+        };
+        if self.batch.len() >= self.batch_size || expired {
+            self.commit()?;
+        }
+        Ok(())
+    }
+
+    /// Commit all pending quads.
+    pub fn flush(&mut self) -> Result<(), MutationError> {
+        self.commit()
+    }
+
+    /// The number of commits performed so far.
+    pub fn commits(&self) -> usize {
+        self.commits
+    }
+
+    /// The number of quads committed so far that were not already in the store.
+    pub fn inserted(&self) -> usize {
+        self.inserted
+    }
+
+    /// The number of quads pushed but not committed yet.
+    pub fn pending(&self) -> usize {
+        self.batch.len()
+    }
+
+    fn commit(&mut self) -> Result<(), MutationError> {
+        self.last_commit = Instant::now();
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+        let batch = std::mem::replace(&mut self.batch, Vec::with_capacity(self.batch_size));
+        self.inserted += self.conn.insert_oxi_batch(batch)?;
+        self.commits += 1;
+        Ok(())
+    }
+}
+
+impl<'a, C> Drop for Inserter<'a, C>
+where
+    C: RepositoryConnection,
+{
+    fn drop(&mut self) {
+        let pending = self.batch.len();
+        if let Err(err) = self.commit() {
+            log::warn!("Failed to commit {} pending quads: {}", pending, err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use oxigraph::{MemoryRepository, Repository};
+    use sophia::dataset::Dataset;
+    use sophia_term::Term;
+
+    #[test]
+    fn batches() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        let p = Term::<String>::new_iri("http://example.org/p").unwrap();
+        {
+            let mut ins = d.inserter(100);
+            for i in 0..250 {
+                let s = Term::<String>::new_iri(format!("http://example.org/s{}", i)).unwrap();
+                ins.push(&([s, p.clone(), p.clone()], None)).unwrap();
+            }
+            assert_eq!(ins.commits(), 2);
+            assert_eq!(ins.pending(), 50);
+            ins.flush().unwrap();
+            assert_eq!(ins.commits(), 3);
+            assert_eq!(ins.inserted(), 250);
+        }
+        assert_eq!(d.quads().count(), 250);
+    }
+
+    #[test]
+    fn max_delay() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        let p = Term::<String>::new_iri("http://example.org/p").unwrap();
+        let quad = |i: usize| {
+            let s = Term::<String>::new_iri(format!("http://example.org/s{}", i)).unwrap();
+            ([s, p.clone(), p.clone()], None::<Term<String>>)
+        };
+        {
+            let mut ins = d.inserter(100).with_max_delay(Duration::from_secs(3600));
+            for i in 0..3 {
+                ins.push(&quad(i)).unwrap();
+            }
+            assert_eq!(ins.commits(), 0);
+            assert_eq!(ins.pending(), 3);
+        }
+        assert_eq!(d.quads().count(), 3);
+        {
+            let mut ins = d.inserter(100).with_max_delay(Duration::from_secs(0));
+            for i in 3..6 {
+                ins.push(&quad(i)).unwrap();
+            }
+            assert_eq!(ins.commits(), 3);
+            assert_eq!(ins.pending(), 0);
+            assert_eq!(ins.inserted(), 3);
+        }
+        assert_eq!(d.quads().count(), 6);
+    }
+}
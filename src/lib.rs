@@ -4,8 +4,21 @@
 //! [Sophia]: https://docs.rs/sophia/latest/sophia/
 #![deny(missing_docs)]
 
+#[macro_use]
+mod delegate;
+
 pub mod connection;
+mod hash;
+pub mod inserter;
 pub mod once_toggle;
+#[cfg(feature = "parallel")]
+pub mod parallel;
 pub mod quad;
+pub mod read_only;
 pub mod repository;
+pub mod shared;
 pub mod term;
+#[cfg(test)]
+mod test_util;
+pub mod transaction;
+pub mod triple;
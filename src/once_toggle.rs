@@ -11,14 +11,20 @@
 //! which can be borrowed (immutably or mutably) without restricyions
 //! (unlike RefCells).
 //!
+//! [`OnceToggle`] is not thread-safe;
+//! [`SyncOnceToggle`] is a thread-safe variant with the same API.
+//!
+//! [`OnceToggle`]: struct.OnceToggle.html
+//! [`SyncOnceToggle`]: struct.SyncOnceToggle.html
+//!
 //! # Todo
 //!
 //! * improve memory layout: at any time, we will only store T or U.
-//!
-//! * implement `sync` version.
 
+use once_cell::sync::OnceCell as SyncOnceCell;
 use once_cell::unsync::OnceCell;
 use std::cell::RefCell;
+use std::sync::Mutex;
 
 /// See [module documentation](./index.html)
 pub struct OnceToggle<T, U> {
@@ -138,3 +144,149 @@ impl<T, U> OnceToggle<T, U> {
         }
     }
 }
+
+/// Thread-safe variant of [`OnceToggle`](struct.OnceToggle.html).
+///
+/// When several threads race to toggle it, the toggling function runs only once,
+/// and the other threads block until it completes.
+pub struct SyncOnceToggle<T, U> {
+    state1: Mutex<Option<T>>,
+    state2: SyncOnceCell<U>,
+}
+
+impl<T, U> SyncOnceToggle<T, U> {
+    /// Create a new SyncOnceToggle with the given value for state 1.
+    pub fn new(value: T) -> Self {
+        SyncOnceToggle {
+            state1: Mutex::new(Some(value)),
+            state2: SyncOnceCell::new(),
+        }
+    }
+    /// Return the state (1 or 2) of this SyncOnceToggle.
+    pub fn state(&self) -> u8 {
+        match self.state2.get() {
+            None => 1,
+            Some(_) => 2,
+        }
+    }
+
+    /// Try toggling to state 2, using the given function.
+    ///
+    /// # Pre-conditions
+    ///
+    /// This SyncOnceToggle must still be in state 1.
+    ///
+    /// # Post-condition
+    ///
+    /// If function `f` returns an error,
+    /// this SyncOnceToggle is in an inconsistent state,
+    /// and should not be used again.
+    pub fn try_toggle<F, E>(&self, f: F) -> Result<(), E>
+    where
+        F: FnOnce(T) -> Result<U, E>,
+    {
+        self.get_or_try_toggle(f).map(|_| ())
+    }
+
+    /// Toggling to state 2, using the given function.
+    ///
+    /// # Pre-conditions
+    ///
+    /// This SyncOnceToggle must still be in state 1.
+    pub fn toggle<F>(&self, f: F)
+    where
+        F: FnOnce(T) -> U,
+    {
+        self.get_or_toggle(f);
+    }
+
+    /// Borrow immutably the state 2 value of this SyncOnceToggle.
+    ///
+    /// # Pre-conditions
+    ///
+    /// This SyncOnceToggle must be in state 2.
+    pub fn get(&self) -> &U {
+        self.state2.get().unwrap()
+    }
+
+    /// Borrow mutably the state 2 value of this SyncOnceToggle.
+    ///
+    /// # Pre-conditions
+    ///
+    /// This SyncOnceToggle must be in state 2.
+    pub fn get_mut(&mut self) -> &mut U {
+        self.state2.get_mut().unwrap()
+    }
+
+    /// Unwraps the state 2 value of this SyncOnceToggle.
+    ///
+    /// # Pre-conditions
+    ///
+    /// This SyncOnceToggle must be in state 2.
+    pub fn unwrap(self) -> U {
+        self.state2.into_inner().unwrap()
+    }
+
+    /// Borrow immutably the state 2 value of this SyncOnceToggle,
+    /// toggling if necessary.
+    ///
+    /// # Post-condition
+    ///
+    /// If function `f` returns an error,
+    /// this SyncOnceToggle is in an inconsistent state,
+    /// and should not be used again.
+    pub fn get_or_try_toggle<F, E>(&self, f: F) -> Result<&U, E>
+    where
+        F: FnOnce(T) -> Result<U, E>,
+    {
+        self.state2.get_or_try_init(move || {
+            let state1 = self.state1.lock().unwrap().take().unwrap();
+            f(state1)
+        })
+    }
+
+    /// Borrow immutably the state 2 value of this SyncOnceToggle,
+    /// toggling if necessary.
+    pub fn get_or_toggle<F>(&self, f: F) -> &U
+    where
+        F: FnOnce(T) -> U,
+    {
+        self.state2.get_or_init(move || {
+            let state1 = self.state1.lock().unwrap().take().unwrap();
+            f(state1)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn sync_toggle_once() {
+        let toggle = Arc::new(SyncOnceToggle::new(21));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let toggle = toggle.clone();
+                let calls = calls.clone();
+                thread::spawn(move || {
+                    *toggle.get_or_toggle(|x| {
+                        calls.fetch_add(1, Ordering::SeqCst);
+                        thread::yield_now();
+                        x * 2
+                    })
+                })
+            })
+            .collect();
+        for t in threads {
+            assert_eq!(t.join().unwrap(), 42);
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(toggle.state(), 2);
+        assert_eq!(*toggle.get(), 42);
+    }
+}
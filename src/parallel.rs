@@ -0,0 +1,211 @@
+//! Parallel conversion of Oxigraph quads into Sophia quads
+//!
+//! This module is only available with the `parallel` feature.
+use crate::quad::QuadBridge;
+use crate::term::BnodeCache;
+use oxigraph::model::Quad as OQuad;
+use oxigraph::Error as OxigraphError;
+use sophia::quad::Quad;
+use std::collections::BTreeMap;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{spawn, JoinHandle};
+
+/// The number of quads buffered per worker
+const BUFFER_PER_WORKER: usize = 64;
+
+/// Iterator returned by [`SophiaConnection::par_quads_buffered`].
+///
+/// Quads are read from the store by the current thread,
+/// and converted into Sophia quads by a pool of worker threads.
+/// At most a fixed number of quads per worker are read in advance.
+///
+/// If reading from the store fails, the quads read before the error are yielded first,
+/// then the error, and the iteration stops.
+///
+/// [`SophiaConnection::par_quads_buffered`]: ../connection/struct.SophiaConnection.html#method.par_quads_buffered
+pub struct ParQuads<'a> {
+    source: Box<dyn Iterator<Item = Result<OQuad, OxigraphError>> + 'a>,
+    jobs: Option<Sender<(usize, OQuad)>>,
+    results: Receiver<(usize, QuadBridge)>,
+    workers: Vec<JoinHandle<()>>,
+    ordered: bool,
+    capacity: usize,
+    sent: usize,
+    yielded: usize,
+    buffer: BTreeMap<usize, QuadBridge>,
+    exhausted: bool,
+    error: Option<OxigraphError>,
+}
+
+impl<'a> ParQuads<'a> {
+    /// Blank node labels are restored from `bnode_cache`, if any,
+    /// as done by `SophiaConnection::quad_bridge`.
+    pub(crate) fn new<I>(
+        source: I,
+        workers: usize,
+        ordered: bool,
+        bnode_cache: Option<Arc<Mutex<BnodeCache>>>,
+    ) -> Self
+    where
+        I: Iterator<Item = Result<OQuad, OxigraphError>> + 'a,
+    {
+        let workers = workers.max(1);
+        let (jobs, job_receiver) = channel::<(usize, OQuad)>();
+        let (result_sender, results) = channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let workers = (0..workers)
+            .map(|_| {
+                let job_receiver = job_receiver.clone();
+                let result_sender = result_sender.clone();
+                let bnode_cache = bnode_cache.clone();
+                spawn(move || loop {
+                    let job = job_receiver.lock().unwrap().recv();
+                    let (i, q) = match job {
+                        Ok(job) => job,
+                        Err(_) => break,
+                    };
+                    let q = match &bnode_cache {
+                        None => QuadBridge::new(q),
+                        Some(cache) => QuadBridge::with_bnode_labels(q, &cache.lock().unwrap()),
+                    };
+                    // force the conversion of all terms
+                    q.s();
+                    q.p();
+                    q.o();
+                    q.g();
+                    if result_sender.send((i, q)).is_err() {
+                        break;
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        ParQuads {
+            source: Box::new(source),
+            jobs: Some(jobs),
+            results,
+            capacity: workers.len() * BUFFER_PER_WORKER,
+            workers,
+            ordered,
+            sent: 0,
+            yielded: 0,
+            buffer: BTreeMap::new(),
+            exhausted: false,
+            error: None,
+        }
+    }
+
+    fn fill(&mut self) {
+        while !self.exhausted && self.sent - self.yielded < self.capacity {
+            match self.source.next() {
+                Some(Ok(q)) => {
+                    let jobs = self.jobs.as_ref().unwrap();
+                    jobs.send((self.sent, q))
+                        .expect("conversion worker panicked");
+                    self.sent += 1;
+                }
+                Some(Err(err)) => {
+                    self.error = Some(err);
+                    self.exhausted = true;
+                }
+                None => self.exhausted = true,
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for ParQuads<'a> {
+    type Item = Result<QuadBridge, OxigraphError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.fill();
+            if self.yielded == self.sent {
+                return self.error.take().map(Err);
+            }
+            if self.ordered {
+                if let Some(q) = self.buffer.remove(&self.yielded) {
+                    self.yielded += 1;
+                    return Some(Ok(q));
+                }
+            }
+            let (i, q) = self.results.recv().expect("conversion worker panicked");
+            if self.ordered {
+                self.buffer.insert(i, q);
+            } else {
+                self.yielded += 1;
+                return Some(Ok(q));
+            }
+        }
+    }
+}
+
+impl<'a> Drop for ParQuads<'a> {
+    fn drop(&mut self) {
+        // closing the job channel stops the workers
+        self.jobs = None;
+        for w in self.workers.drain(..) {
+            let _ = w.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::connection::SophiaConnection;
+    use oxigraph::{MemoryRepository, Repository};
+    use sophia::dataset::{Dataset, MutableDataset};
+    use sophia::ns::xsd;
+    use sophia::quad::Quad;
+    use sophia_term::blank_node::BlankNode;
+    use sophia_term::Term;
+
+    #[test]
+    fn ordered() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        let p = Term::<String>::new_iri("http://example.org/p").unwrap();
+        for i in 0..1000 {
+            let s = Term::<String>::new_iri(format!("http://example.org/s{}", i)).unwrap();
+            let o = Term::<String>::new_literal_dt("x".repeat(i), xsd::string).unwrap();
+            d.insert(&s, &p, &o, if i % 2 == 0 { None } else { Some(&p) })
+                .unwrap();
+        }
+
+        let serial: Vec<_> = d
+            .quads()
+            .map(|q| {
+                let q = q.unwrap();
+                (q.s().clone(), q.p().clone(), q.o().clone(), q.g().cloned())
+            })
+            .collect();
+        let parallel: Vec<_> = d
+            .par_quads_buffered(4, true)
+            .map(|q| {
+                let q = q.unwrap();
+                (q.s().clone(), q.p().clone(), q.o().clone(), q.g().cloned())
+            })
+            .collect();
+        assert_eq!(serial.len(), 1000);
+        assert_eq!(parallel, serial);
+
+        assert_eq!(d.par_quads_buffered(4, false).count(), 1000);
+
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap()).with_bnode_cache();
+        for i in 0..100 {
+            let b = Term::BNode(BlankNode::new_unchecked(format!("not a valid label {}", i)));
+            d.insert(&b, &p, &p, None).unwrap();
+        }
+        let serial: Vec<_> = d
+            .quads()
+            .map(|q| q.unwrap().s().value().to_string())
+            .collect();
+        let parallel: Vec<_> = d
+            .par_quads_buffered(4, true)
+            .map(|q| q.unwrap().s().value().to_string())
+            .collect();
+        assert_eq!(parallel, serial);
+        assert!(parallel.iter().all(|l| l.starts_with("not a valid label")));
+    }
+}
@@ -3,47 +3,240 @@
 //! TODO: this is a straighforward implementation,
 //! which might be improved in term of CPU- and memory-efficiency.
 
-use crate::once_toggle::OnceToggle;
 use crate::term::*;
-use oxigraph::model::{NamedNode, NamedOrBlankNode, Quad as OQuad, Term as OTerm};
+use once_cell::unsync::OnceCell;
+use oxigraph::model::{
+    BlankNode as OBlankNode, NamedNode, NamedOrBlankNode, Quad as OQuad, Term as OTerm,
+};
 use sophia::quad::Quad as SQuad;
-use sophia_term::Term as STerm;
+use sophia_term::blank_node::BlankNode as SBlankNode;
+use sophia_term::{Term as STerm, TermData};
+use std::sync::Arc;
 
 /// Wraps an Oxigraph Quad into a Sophia Quad
-pub struct QuadBridge {
-    s: OnceToggle<NamedOrBlankNode, STerm<String>>,
-    p: OnceToggle<NamedNode, STerm<String>>,
-    o: OnceToggle<OTerm, STerm<String>>,
-    g: Option<OnceToggle<NamedOrBlankNode, STerm<String>>>,
+///
+/// A quad in Oxigraph's default graph has no graph name,
+/// so `g()` returns `None` for it, as expected by Sophia.
+///
+/// Each term is converted on first access, and cached;
+/// the original Oxigraph Quad remains available through [`as_oxi`](#method.as_oxi).
+///
+/// By default, converted terms own their text (`TD = String`),
+/// but any other `TermData` that can be built from a `&str` can be used
+/// (e.g. `Box<str>` or `Rc<str>`), by converting an Oxigraph Quad with `into`.
+/// A `QuadBridge<Arc<str>>` can also share the text of its terms through an [`Interner`].
+///
+/// [`Interner`]: ../term/struct.Interner.html
+pub struct QuadBridge<TD: TermData = String> {
+    quad: OQuad,
+    s: OnceCell<STerm<TD>>,
+    p: OnceCell<STerm<TD>>,
+    o: OnceCell<STerm<TD>>,
+    g: OnceCell<STerm<TD>>,
+}
+
+impl<TD: TermData> QuadBridge<TD> {
+    fn wrap(q: OQuad) -> QuadBridge<TD> {
+        QuadBridge {
+            quad: q,
+            s: OnceCell::new(),
+            p: OnceCell::new(),
+            o: OnceCell::new(),
+            g: OnceCell::new(),
+        }
+    }
+
+    /// Borrow the underlying Oxigraph Quad
+    pub fn as_oxi(&self) -> &OQuad {
+        &self.quad
+    }
+
+    /// Unwrap the underlying Oxigraph Quad
+    pub fn into_oxi(self) -> OQuad {
+        self.quad
+    }
+}
+
+impl<TD: TermData> From<OQuad> for QuadBridge<TD> {
+    fn from(q: OQuad) -> QuadBridge<TD> {
+        QuadBridge::wrap(q)
+    }
 }
 
 impl QuadBridge {
     /// Construct QuadBridge around Oxigraph Quad
     pub fn new(q: OQuad) -> QuadBridge {
-        let (subj, pred, obj, graph) = q.destruct();
-        QuadBridge {
-            s: OnceToggle::new(subj),
-            p: OnceToggle::new(pred),
-            o: OnceToggle::new(obj),
-            g: graph.map(OnceToggle::new),
+        QuadBridge::wrap(q)
+    }
+
+    /// Construct QuadBridge around Oxigraph Quad,
+    /// restoring the original label of the synthetic blank nodes in `cache`.
+    pub(crate) fn with_bnode_labels(q: OQuad, cache: &BnodeCache) -> QuadBridge {
+        let restore = |b: &OBlankNode| {
+            cache
+                .label(b)
+                .map(|label| STerm::BNode(SBlankNode::new_unchecked(label.to_string())))
+        };
+        let restore_nb = |t: &NamedOrBlankNode| match t {
+            NamedOrBlankNode::BlankNode(b) => restore(b),
+            _ => None,
+        };
+        let s = restore_nb(q.subject());
+        let o = match q.object() {
+            OTerm::BlankNode(b) => restore(b),
+            _ => None,
+        };
+        let g = q.graph_name().as_ref().and_then(restore_nb);
+        let ret = QuadBridge::new(q);
+        if let Some(s) = s {
+            let _ = ret.s.set(s);
+        }
+        if let Some(o) = o {
+            let _ = ret.o.set(o);
+        }
+        if let Some(g) = g {
+            let _ = ret.g.set(g);
+        }
+        ret
+    }
+}
+
+impl QuadBridge<Arc<str>> {
+    /// Construct QuadBridge around Oxigraph Quad,
+    /// converting its terms immediately with `interner`.
+    pub fn interned(q: OQuad, interner: &mut Interner) -> QuadBridge<Arc<str>> {
+        // Oxigraph terms are never variables, so interning them never fails
+        let ret = QuadBridge::wrap(q);
+        if let Ok(s) = interner.term(ret.quad.subject().as_sophia_ref()) {
+            let _ = ret.s.set(s);
+        }
+        if let Ok(p) = interner.term(ret.quad.predicate().as_sophia_ref()) {
+            let _ = ret.p.set(p);
+        }
+        if let Ok(o) = interner.term(ret.quad.object().as_sophia_ref()) {
+            let _ = ret.o.set(o);
+        }
+        if let Some(g) = ret.quad.graph_name() {
+            if let Ok(g) = interner.term(g.as_sophia_ref()) {
+                let _ = ret.g.set(g);
+            }
         }
+        ret
     }
 }
 
-impl SQuad for QuadBridge {
-    type TermData = String;
-    fn s(&self) -> &STerm<String> {
-        self.s.get_or_toggle(AsSophiaTerm::into_sophia)
+impl<TD> SQuad for QuadBridge<TD>
+where
+    TD: TermData + for<'x> From<&'x str>,
+{
+    type TermData = TD;
+    fn s(&self) -> &STerm<TD> {
+        self.s.get_or_init(|| self.quad.subject().as_sophia())
     }
-    fn p(&self) -> &STerm<String> {
-        self.p.get_or_toggle(AsSophiaTerm::into_sophia)
+    fn p(&self) -> &STerm<TD> {
+        self.p.get_or_init(|| self.quad.predicate().as_sophia())
     }
-    fn o(&self) -> &STerm<String> {
-        self.o.get_or_toggle(AsSophiaTerm::into_sophia)
+    fn o(&self) -> &STerm<TD> {
+        self.o.get_or_init(|| self.quad.object().as_sophia())
     }
-    fn g(&self) -> Option<&STerm<String>> {
-        self.g
-            .as_ref()
-            .map(|g| g.get_or_toggle(AsSophiaTerm::into_sophia))
+    fn g(&self) -> Option<&STerm<TD>> {
+        let g = self.quad.graph_name().as_ref()?;
+        Some(self.g.get_or_init(|| g.as_sophia()))
+    }
+}
+
+/// Wraps a borrowed Oxigraph Quad into a Sophia Quad,
+/// without copying the underlying text of its terms.
+///
+/// This is cheaper than [`QuadBridge`](struct.QuadBridge.html)
+/// when terms only need to be read,
+/// but the bridge can not outlive the Oxigraph Quad.
+pub struct ByRefQuadBridge<'a> {
+    s: STerm<&'a str>,
+    p: STerm<&'a str>,
+    o: STerm<&'a str>,
+    g: Option<STerm<&'a str>>,
+}
+
+impl<'a> ByRefQuadBridge<'a> {
+    /// Construct ByRefQuadBridge borrowing from Oxigraph Quad
+    pub fn new(q: &'a OQuad) -> ByRefQuadBridge<'a> {
+        ByRefQuadBridge {
+            s: q.subject().as_sophia_ref(),
+            p: q.predicate().as_sophia_ref(),
+            o: q.object().as_sophia_ref(),
+            g: q.graph_name().as_ref().map(AsSophiaTerm::as_sophia_ref),
+        }
+    }
+}
+
+impl<'a> SQuad for ByRefQuadBridge<'a> {
+    type TermData = &'a str;
+    fn s(&self) -> &STerm<&'a str> {
+        &self.s
+    }
+    fn p(&self) -> &STerm<&'a str> {
+        &self.p
+    }
+    fn o(&self) -> &STerm<&'a str> {
+        &self.o
+    }
+    fn g(&self) -> Option<&STerm<&'a str>> {
+        self.g.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn by_ref() {
+        let s = NamedNode::new("http://example.org/s").unwrap();
+        let p = NamedNode::new("http://example.org/p").unwrap();
+        let g = NamedNode::new("http://example.org/g").unwrap();
+        let o = OTerm::from(OBlankNode::default());
+        let q = OQuad::new(s, p, o, Some(g.into()));
+        let by_ref = ByRefQuadBridge::new(&q);
+        let by_value = QuadBridge::new(q.clone());
+        assert_eq!(by_ref.s(), by_value.s());
+        assert_eq!(by_ref.p(), by_value.p());
+        assert_eq!(by_ref.o(), by_value.o());
+        assert_eq!(by_ref.g(), by_value.g());
+    }
+
+    #[test]
+    fn term_data() {
+        use oxigraph::model::Literal as OLiteral;
+        use std::rc::Rc;
+
+        let s = NamedNode::new("http://example.org/s").unwrap();
+        let p = NamedNode::new("http://example.org/p").unwrap();
+        let o = OTerm::from(OLiteral::new_simple_literal("o"));
+        let q = OQuad::new(s, p, o, None);
+
+        let arc: QuadBridge<Arc<str>> = q.clone().into();
+        assert_eq!(&*arc.s().value(), "http://example.org/s");
+        assert_eq!(&*arc.p().value(), "http://example.org/p");
+        assert_eq!(&*arc.o().value(), "o");
+        assert!(arc.g().is_none());
+
+        let rc: QuadBridge<Rc<str>> = q.clone().into();
+        let string = QuadBridge::new(q);
+        assert_eq!(rc.s(), string.s());
+        assert_eq!(rc.o(), string.o());
+    }
+
+    #[test]
+    fn as_oxi() {
+        let s = NamedNode::new("http://example.org/s").unwrap();
+        let p = NamedNode::new("http://example.org/p").unwrap();
+        let o = OTerm::from(NamedNode::new("http://example.org/o").unwrap());
+        let q = QuadBridge::new(OQuad::new(s.clone(), p, o, None));
+        let expected = STerm::<String>::new_iri("http://example.org/s").unwrap();
+        assert_eq!(q.s(), &expected);
+        assert_eq!(q.s(), &expected);
+        assert_eq!(q.as_oxi().subject(), &NamedOrBlankNode::from(s));
+        assert!(q.g().is_none());
     }
 }
@@ -0,0 +1,65 @@
+//! Read-only view of a SophiaConnection
+use crate::connection::SophiaConnection;
+use oxigraph::RepositoryConnection;
+
+/// A [`SophiaConnection`] that can only be read.
+///
+/// It implements [`Dataset`] but not [`MutableDataset`],
+/// so handing it out guarantees, at compile time, that the store will not be modified through it.
+/// It is obtained with [`SophiaConnection::read_only`].
+///
+/// ```compile_fail
+/// # use oxigraph::{MemoryRepository, Repository};
+/// # use sophia::dataset::MutableDataset;
+/// # use sophia_oxigraph::connection::SophiaConnection;
+/// # use sophia_term::Term;
+/// let repo = MemoryRepository::default();
+/// let mut d = SophiaConnection::new(repo.connection().unwrap()).read_only();
+/// let t = Term::<&str>::new_iri("http://example.org/").unwrap();
+/// d.insert(&t, &t, &t, None);
+/// ```
+///
+/// [`SophiaConnection`]: ../connection/struct.SophiaConnection.html
+/// [`SophiaConnection::read_only`]: ../connection/struct.SophiaConnection.html#method.read_only
+/// [`Dataset`]: https://docs.rs/sophia/0.5/sophia/dataset/trait.Dataset.html
+/// [`MutableDataset`]: https://docs.rs/sophia/0.5/sophia/dataset/trait.MutableDataset.html
+#[derive(Clone, Debug)]
+pub struct ReadOnlyConnection<C: RepositoryConnection>(SophiaConnection<C>);
+
+impl<C> ReadOnlyConnection<C>
+where
+    C: RepositoryConnection,
+{
+    pub(crate) fn new(conn: SophiaConnection<C>) -> Self {
+        ReadOnlyConnection(conn)
+    }
+}
+
+delegate_dataset!(
+    impl[C] ReadOnlyConnection<C> where [C: RepositoryConnection],
+    deref(|this| &this.0)
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use oxigraph::{MemoryRepository, Repository};
+    use sophia::dataset::{Dataset, MutableDataset};
+    use sophia_term::Term;
+
+    #[test]
+    fn read_only() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        let s = Term::<&str>::new_iri("http://example.org/s").unwrap();
+        let p = Term::<&str>::new_iri("http://example.org/p").unwrap();
+        d.insert(&s, &p, &s, None as Option<&Term<&str>>).unwrap();
+
+        let ro = d.read_only();
+        assert_eq!(ro.quads().count(), 1);
+        assert!(ro
+            .contains(&s, &p, &s, None as Option<&Term<&str>>)
+            .unwrap());
+        assert_eq!(ro.subjects().unwrap().len(), 1);
+    }
+}
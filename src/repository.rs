@@ -1,300 +1,249 @@
 //! Sophia Dataset implementation for Oxigraph Repository
 use crate::connection::{MutationError, SophiaConnection};
-use crate::quad::QuadBridge;
+use ouroboros::self_referencing;
 use oxigraph::{Error as OxigraphError, Repository};
-use sophia::dataset::{DQuadSource, DResult, DResultTermSet, Dataset, MDResult, MutableDataset};
-use sophia::quad::streaming_mode::*;
-use sophia_term::matcher::{GraphNameMatcher, TermMatcher};
+use sophia::dataset::{MDResult, MutableDataset};
 use sophia_term::{Term, TermData};
-use std::mem::transmute;
-use std::pin::Pin;
+use std::error::Error;
+use std::io;
+#[cfg(any(feature = "rocksdb", feature = "sled"))]
+use std::path::Path;
+use thiserror::Error;
 
 type SoCx<'a, R> = SophiaConnection<<&'a R as Repository>::Connection>;
 
-/// Expose an Oxigraph Connection as a Sophia Dataset
+/// Expose an Oxigraph Repository as a Sophia Dataset
+///
+/// The repository is owned by this object,
+/// which also caches a connection to it.
 pub struct SophiaRepository<R>
 where
-    R: 'static,
+    for<'x> &'x R: Repository,
+{
+    cell: RepositoryCell<R>,
+    max_retries: usize,
+}
+
+/// A repository, together with a connection borrowing it
+#[self_referencing]
+struct RepositoryCell<R>
+where
     for<'x> &'x R: Repository,
 {
     repo: R,
-    conn: Option<SoCx<'static, R>>,
+    #[borrows(repo)]
+    #[not_covariant]
+    conn: SoCx<'this, R>,
 }
 
 impl<R> SophiaRepository<R>
 where
-    R: 'static,
     for<'x> &'x R: Repository,
 {
     /// Wrap `repo` as a Sophia Dataset
-    #[inline]
-    pub fn new(repo: R) -> Result<Pin<Box<Self>>, OxigraphError> {
-        let mut pinned = Box::pin(SophiaRepository { repo, conn: None });
-        unsafe {
-            let sr = Pin::get_unchecked_mut(Pin::as_mut(&mut pinned));
-            let repo: &'static R = transmute(&sr.repo);
-            sr.conn = Some(SoCx::new(repo.connection()?));
-        }
-        Ok(pinned)
+    ///
+    /// Fails with [`RepositoryInitError::ConnectionOpen`]
+    /// if no connection to `repo` can be opened.
+    ///
+    /// [`RepositoryInitError::ConnectionOpen`]: enum.RepositoryInitError.html#variant.ConnectionOpen
+    #[inline]
+    pub fn new(repo: R) -> Result<Self, RepositoryInitError> {
+        let cell = RepositoryCell::try_new(repo, |repo| repo.connection().map(SoCx::new))?;
+        Ok(SophiaRepository {
+            cell,
+            max_retries: 0,
+        })
     }
 
     /// Borrow underlying Oxigraph repository
     #[inline]
     pub fn as_oxi(&self) -> &R {
-        &self.repo
+        self.cell.borrow_repo()
     }
 
-    /// Get a SophiaConnection from the underlying repository
-    #[inline]
-    pub fn connection(&self) -> &SoCx<R> {
-        unsafe { transmute(self.conn.as_ref().unwrap()) }
+    /// Call `f` with the SophiaConnection cached by this object, and return its result.
+    ///
+    /// As that connection borrows the repository owned by this object,
+    /// it can not be returned by reference;
+    /// but anything that only borrows the connection for `'s`
+    /// (such as the iterators returned by `Dataset` methods) can be returned by `f`.
+    #[inline]
+    pub fn with_connection<'s, T, F>(&'s self, f: F) -> T
+    where
+        F: for<'this> FnOnce(&'s SoCx<'this, R>) -> T,
+    {
+        self.cell.with_conn(f)
     }
 
     /// Get a SophiaConnection from the underlying repository
     #[inline]
     pub fn fresh_connection(&self) -> Result<SoCx<R>, OxigraphError> {
-        Ok(SoCx::new(self.repo.connection()?))
+        Ok(SoCx::new(self.cell.borrow_repo().connection()?))
     }
-}
-
-impl<R> Dataset for Pin<Box<SophiaRepository<R>>>
-where
-    for<'x> &'x R: Repository,
-{
-    type Quad = ByValue<QuadBridge>;
-    type Error = OxigraphError;
 
+    /// Make sure that all the mutations applied through this object are durable
+    /// (see [`SophiaConnection::flush`]).
+    ///
+    /// [`SophiaConnection::flush`]: ../connection/struct.SophiaConnection.html#method.flush
     #[inline]
-    fn quads(&self) -> DQuadSource<Self> {
-        self.connection().quads()
-    }
-    #[inline]
-    fn quads_with_s<'s, T>(&'s self, s: &'s Term<T>) -> DQuadSource<'s, Self>
-    where
-        T: TermData,
-    {
-        self.connection().quads_with_s(s)
-    }
-    #[inline]
-    fn quads_with_p<'s, T>(&'s self, p: &'s Term<T>) -> DQuadSource<'s, Self>
-    where
-        T: TermData,
-    {
-        self.connection().quads_with_p(p)
-    }
-    #[inline]
-    fn quads_with_o<'s, T>(&'s self, o: &'s Term<T>) -> DQuadSource<'s, Self>
-    where
-        T: TermData,
-    {
-        self.connection().quads_with_o(o)
-    }
-    #[inline]
-    fn quads_with_g<'s, T>(&'s self, g: Option<&'s Term<T>>) -> DQuadSource<'s, Self>
-    where
-        T: TermData,
-    {
-        self.connection().quads_with_g(g)
-    }
-    #[inline]
-    fn quads_with_sp<'s, T, U>(&'s self, s: &'s Term<T>, p: &'s Term<U>) -> DQuadSource<'s, Self>
-    where
-        T: TermData,
-        U: TermData,
-    {
-        self.connection().quads_with_sp(s, p)
-    }
-    #[inline]
-    fn quads_with_so<'s, T, U>(&'s self, s: &'s Term<T>, o: &'s Term<U>) -> DQuadSource<'s, Self>
-    where
-        T: TermData,
-        U: TermData,
-    {
-        self.connection().quads_with_so(s, o)
-    }
-    #[inline]
-    fn quads_with_sg<'s, T, U>(
-        &'s self,
-        s: &'s Term<T>,
-        g: Option<&'s Term<U>>,
-    ) -> DQuadSource<'s, Self>
-    where
-        T: TermData,
-        U: TermData,
-    {
-        self.connection().quads_with_sg(s, g)
-    }
-    #[inline]
-    fn quads_with_po<'s, T, U>(&'s self, p: &'s Term<T>, o: &'s Term<U>) -> DQuadSource<'s, Self>
-    where
-        T: TermData,
-        U: TermData,
-    {
-        self.connection().quads_with_po(p, o)
-    }
-    #[inline]
-    fn quads_with_pg<'s, T, U>(
-        &'s self,
-        p: &'s Term<T>,
-        g: Option<&'s Term<U>>,
-    ) -> DQuadSource<'s, Self>
-    where
-        T: TermData,
-        U: TermData,
-    {
-        self.connection().quads_with_pg(p, g)
-    }
-    #[inline]
-    fn quads_with_og<'s, T, U>(
-        &'s self,
-        o: &'s Term<T>,
-        g: Option<&'s Term<U>>,
-    ) -> DQuadSource<'s, Self>
-    where
-        T: TermData,
-        U: TermData,
-    {
-        self.connection().quads_with_og(o, g)
+    pub fn flush(&mut self) -> Result<(), OxigraphError> {
+        self.cell.with_conn_mut(|conn| conn.flush())
     }
-    #[inline]
-    fn quads_with_spo<'s, T, U, V>(
-        &'s self,
-        s: &'s Term<T>,
-        p: &'s Term<U>,
-        o: &'s Term<V>,
-    ) -> DQuadSource<'s, Self>
-    where
-        T: TermData,
-        U: TermData,
-        V: TermData,
-    {
-        self.connection().quads_with_spo(s, p, o)
-    }
-    #[inline]
-    fn quads_with_spg<'s, T, U, V>(
-        &'s self,
-        s: &'s Term<T>,
-        p: &'s Term<U>,
-        g: Option<&'s Term<V>>,
-    ) -> DQuadSource<'s, Self>
-    where
-        T: TermData,
-        U: TermData,
-        V: TermData,
-    {
-        self.connection().quads_with_spg(s, p, g)
-    }
-    #[inline]
-    fn quads_with_sog<'s, T, U, V>(
-        &'s self,
-        s: &'s Term<T>,
-        o: &'s Term<U>,
-        g: Option<&'s Term<V>>,
-    ) -> DQuadSource<'s, Self>
-    where
-        T: TermData,
-        U: TermData,
-        V: TermData,
-    {
-        self.connection().quads_with_sog(s, o, g)
-    }
-    #[inline]
-    fn quads_with_pog<'s, T, U, V>(
-        &'s self,
-        p: &'s Term<T>,
-        o: &'s Term<U>,
-        g: Option<&'s Term<V>>,
-    ) -> DQuadSource<'s, Self>
-    where
-        T: TermData,
-        U: TermData,
-        V: TermData,
-    {
-        self.connection().quads_with_pog(p, o, g)
+
+    /// Set how many times a query or mutation is retried
+    /// (each time on a fresh connection)
+    /// after failing with a transient error.
+    ///
+    /// When a mutation succeeds on a fresh connection,
+    /// that connection replaces the cached one.
+    /// Queries can not replace the cached connection (as they only borrow this object),
+    /// so it is only repaired by the next successful retried mutation.
+    ///
+    /// By default, operations are not retried.
+    #[inline]
+    pub fn set_max_retries(&mut self, max_retries: usize) {
+        self.max_retries = max_retries;
     }
+
+    /// How many times a query or mutation is retried after a transient error.
     #[inline]
-    fn quads_with_spog<'s, T, U, V, W>(
-        &'s self,
-        s: &'s Term<T>,
-        p: &'s Term<U>,
-        o: &'s Term<V>,
-        g: Option<&'s Term<W>>,
-    ) -> DQuadSource<'s, Self>
-    where
-        T: TermData,
-        U: TermData,
-        V: TermData,
-        W: TermData,
-    {
-        self.connection().quads_with_spog(s, p, o, g)
+    pub fn max_retries(&self) -> usize {
+        self.max_retries
     }
-    #[inline]
-    fn contains<T, U, V, W>(
-        &self,
-        s: &Term<T>,
-        p: &Term<U>,
-        o: &Term<V>,
-        g: Option<&Term<W>>,
-    ) -> DResult<Self, bool>
+
+    /// Run `op` on the cached connection,
+    /// then on fresh connections for each retry after a transient error.
+    ///
+    /// The cached connection is left unchanged.
+    fn retry<T, F>(&self, op: F) -> Result<T, OxigraphError>
     where
-        T: TermData,
-        U: TermData,
-        V: TermData,
-        W: TermData,
+        F: Fn(&SoCx<R>) -> Result<T, OxigraphError>,
     {
-        self.connection().contains(s, p, o, g)
+        retry(self.max_retries, is_transient, |attempt| {
+            if attempt == 0 {
+                self.cell.with_conn(|conn| op(conn))
+            } else {
+                op(&self.fresh_connection()?)
+            }
+        })
     }
-    #[inline]
-    fn quads_matching<'s, S, P, O, G>(
-        &'s self,
-        ms: &'s S,
-        mp: &'s P,
-        mo: &'s O,
-        mg: &'s G,
-    ) -> DQuadSource<'s, Self>
+
+    /// Run mutation `op` on the cached connection,
+    /// then on fresh connections for each retry after a transient error.
+    ///
+    /// If a retry succeeds, its fresh connection replaces the cached one.
+    fn retry_mut<T, F>(&mut self, op: F) -> Result<T, MutationError>
     where
-        S: TermMatcher + ?Sized,
-        P: TermMatcher + ?Sized,
-        O: TermMatcher + ?Sized,
-        G: GraphNameMatcher + ?Sized,
+        F: Fn(&mut SoCx<R>) -> Result<T, MutationError>,
     {
-        self.connection().quads_matching(ms, mp, mo, mg)
+        retry(
+            self.max_retries,
+            |err| match err {
+                MutationError::Oxigraph { source } => is_transient(source),
+                _ => false,
+            },
+            |attempt| {
+                if attempt == 0 {
+                    self.cell.with_conn_mut(|conn| op(conn))
+                } else {
+                    self.cell.with_mut(|fields| {
+                        let mut conn = SoCx::new(fields.repo.connection()?);
+                        let res = op(&mut conn);
+                        if res.is_ok() {
+                            *fields.conn = conn;
+                        }
+                        res
+                    })
+                }
+            },
+        )
     }
-    #[inline]
-    fn subjects(&self) -> DResultTermSet<Self> {
-        self.connection().subjects()
-    }
-    #[inline]
-    fn predicates(&self) -> DResultTermSet<Self> {
-        self.connection().predicates()
-    }
-    #[inline]
-    fn objects(&self) -> DResultTermSet<Self> {
-        self.connection().objects()
-    }
-    #[inline]
-    fn graph_names(&self) -> DResultTermSet<Self> {
-        self.connection().graph_names()
+}
+
+#[cfg(feature = "rocksdb")]
+impl SophiaRepository<oxigraph::RocksDbRepository> {
+    /// Open (or create) a RocksDB repository at `path`, and wrap it as a Sophia Dataset
+    ///
+    /// Only available with the `rocksdb` feature.
+    pub fn open_rocksdb<P: AsRef<Path>>(path: P) -> Result<Self, RepositoryInitError> {
+        Self::new(oxigraph::RocksDbRepository::open(path).map_err(RepositoryInitError::StoreOpen)?)
     }
-    #[inline]
-    fn iris(&self) -> DResultTermSet<Self> {
-        self.connection().iris()
+}
+
+#[cfg(feature = "sled")]
+impl SophiaRepository<oxigraph::SledRepository> {
+    /// Open (or create) a Sled repository at `path`, and wrap it as a Sophia Dataset
+    ///
+    /// Only available with the `sled` feature.
+    pub fn open_sled<P: AsRef<Path>>(path: P) -> Result<Self, RepositoryInitError> {
+        Self::new(oxigraph::SledRepository::open(path).map_err(RepositoryInitError::StoreOpen)?)
     }
-    #[inline]
-    fn bnodes(&self) -> DResultTermSet<Self> {
-        self.connection().bnodes()
+}
+
+/// Error raised when a [`SophiaRepository`] can not be created
+///
+/// [`SophiaRepository`]: struct.SophiaRepository.html
+#[derive(Debug, Error)]
+pub enum RepositoryInitError {
+    /// The underlying store could not be opened
+    #[error("Could not open the store: {0}")]
+    StoreOpen(#[source] OxigraphError),
+    /// No connection to the repository could be opened
+    #[error("Could not open a connection to the repository: {0}")]
+    ConnectionOpen(#[from] OxigraphError),
+}
+
+impl From<RepositoryInitError> for OxigraphError {
+    fn from(err: RepositoryInitError) -> Self {
+        match err {
+            RepositoryInitError::StoreOpen(err) | RepositoryInitError::ConnectionOpen(err) => err,
+        }
     }
-    #[inline]
-    fn literals(&self) -> DResultTermSet<Self> {
-        self.connection().literals()
+}
+
+/// Call `op` with the attempt number (starting at 0),
+/// until it succeeds, fails with an error that is not `transient`,
+/// or has been retried `max_retries` times.
+fn retry<T, E, F, K>(max_retries: usize, transient: K, mut op: F) -> Result<T, E>
+where
+    F: FnMut(usize) -> Result<T, E>,
+    K: Fn(&E) -> bool,
+{
+    let mut attempt = 0;
+    loop {
+        match op(attempt) {
+            Err(err) if attempt < max_retries && transient(&err) => attempt += 1,
+            res => return res,
+        }
     }
-    #[inline]
-    fn variables(&self) -> DResultTermSet<Self> {
-        self.connection().variables()
+}
+
+/// Whether `err` is caused by a transient I/O error,
+/// so that the failed operation is worth retrying.
+fn is_transient(err: &OxigraphError) -> bool {
+    let mut source: Option<&(dyn Error + 'static)> = Some(err);
+    while let Some(err) = source {
+        if let Some(err) = err.downcast_ref::<io::Error>() {
+            return match err.kind() {
+                io::ErrorKind::Interrupted
+                | io::ErrorKind::WouldBlock
+                | io::ErrorKind::TimedOut
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted => true,
+                _ => false,
+            };
+        }
+        source = err.source();
     }
+    false
 }
 
-impl<R> MutableDataset for Pin<Box<SophiaRepository<R>>>
+delegate_dataset!(
+    impl[R] SophiaRepository<R> where [for<'x> &'x R: Repository],
+    closure(Self::with_connection, Self::retry)
+);
+
+impl<R> MutableDataset for SophiaRepository<R>
 where
     for<'x> &'x R: Repository,
 {
@@ -313,7 +262,7 @@ where
         V: TermData,
         W: TermData,
     {
-        self.fresh_connection()?.insert(s, p, o, g)
+        self.retry_mut(|c| c.insert(s, p, o, g))
     }
     #[inline]
     fn remove<T, U, V, W>(
@@ -329,7 +278,7 @@ where
         V: TermData,
         W: TermData,
     {
-        self.fresh_connection()?.remove(s, p, o, g)
+        self.retry_mut(|c| c.remove(s, p, o, g))
     }
 
     // TODO implement other methods (relaying to SophiaConnection)
@@ -339,18 +288,335 @@ where
 mod test {
     use super::*;
     use oxigraph::MemoryRepository;
+    use sophia::dataset::Dataset;
     use sophia::quad::stream::QuadSource;
     use sophia::test_dataset_impl;
     use sophia::triple::stream::{SinkError, StreamResult};
 
-    type SopMemRepo = Pin<Box<SophiaRepository<MemoryRepository>>>;
+    type SopMemRepo = SophiaRepository<MemoryRepository>;
 
     fn make_repo<QS: QuadSource>(qs: QS) -> StreamResult<SopMemRepo, QS::Error, MutationError> {
         let mut d = SophiaRepository::new(MemoryRepository::default())
-            .map_err(|err| SinkError(MutationError::from(err)))?;
+            .map_err(|err| SinkError(MutationError::from(OxigraphError::from(err))))?;
         d.insert_all(qs)?;
         Ok(d)
     }
 
     sophia::test_dataset_impl!(auto, SopMemRepo, false, false, make_repo);
+
+    #[test]
+    fn movable() {
+        fn make() -> SopMemRepo {
+            let mut d = SophiaRepository::new(MemoryRepository::default()).unwrap();
+            let t = Term::<&str>::new_iri("http://example.org/").unwrap();
+            d.insert(&t, &t, &t, None::<&Term<&str>>).unwrap();
+            d
+        }
+        let mut repos = vec![make()];
+        repos.push(make());
+        let moved = repos.remove(0);
+        assert_eq!(moved.quads().count(), 1);
+        assert_eq!(moved.with_connection(|c| c.quads().count()), 1);
+        assert_eq!(moved.fresh_connection().unwrap().quads().count(), 1);
+    }
+
+    #[test]
+    fn mutations_reuse_connection() {
+        use oxigraph::MemoryRepositoryConnection;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        #[derive(Default)]
+        struct CountingRepository {
+            inner: MemoryRepository,
+            connections: AtomicUsize,
+        }
+
+        impl<'a> Repository for &'a CountingRepository {
+            type Connection = MemoryRepositoryConnection<'a>;
+
+            fn connection(self) -> Result<Self::Connection, OxigraphError> {
+                self.connections.fetch_add(1, Ordering::SeqCst);
+                self.inner.connection()
+            }
+        }
+
+        let mut d = SophiaRepository::new(CountingRepository::default()).unwrap();
+        let p = Term::<&str>::new_iri("http://example.org/p").unwrap();
+        for i in 0..10_000 {
+            let s = Term::<String>::new_iri(format!("http://example.org/s{}", i)).unwrap();
+            d.insert(&s, &p, &p, None::<&Term<&str>>).unwrap();
+        }
+        let s = Term::<&str>::new_iri("http://example.org/s0").unwrap();
+        d.remove(&s, &p, &p, None::<&Term<&str>>).unwrap();
+        assert_eq!(d.quads().count(), 9_999);
+        assert_eq!(d.as_oxi().connections.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn retry_after_transient_error() {
+        let mut calls = 0;
+        let res: Result<usize, OxigraphError> = retry(1, is_transient, |attempt| {
+            calls += 1;
+            if attempt == 0 {
+                Err(io::Error::from(io::ErrorKind::Interrupted).into())
+            } else {
+                Ok(attempt)
+            }
+        });
+        assert_eq!(res.unwrap(), 1);
+        assert_eq!(calls, 2);
+    }
+
+    #[test]
+    fn no_retry_by_default() {
+        let mut calls = 0;
+        let res: Result<(), OxigraphError> = retry(0, is_transient, |_| {
+            calls += 1;
+            Err(io::Error::from(io::ErrorKind::Interrupted).into())
+        });
+        assert!(res.is_err());
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn retry_on_flaky_connection() {
+        use oxigraph::model::{NamedNode, NamedOrBlankNode, Quad, Term as OTerm};
+        use oxigraph::sparql::{GraphPattern, QueryOptions};
+        use oxigraph::{
+            DatasetSyntax, GraphSyntax, MemoryRepositoryConnection, RepositoryConnection,
+        };
+        use std::io::BufRead;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        /// A repository whose connections fail with a transient error
+        /// on `contains` and `insert`, as long as `failures` is positive;
+        /// connections numbered up to `broken` (starting at 1) always fail
+        #[derive(Default)]
+        struct FlakyRepository {
+            inner: MemoryRepository,
+            connections: AtomicUsize,
+            failures: AtomicUsize,
+            broken: AtomicUsize,
+        }
+
+        #[derive(Clone)]
+        struct FlakyConnection<'a> {
+            inner: MemoryRepositoryConnection<'a>,
+            id: usize,
+            failures: &'a AtomicUsize,
+            broken: &'a AtomicUsize,
+        }
+
+        impl<'a> FlakyConnection<'a> {
+            fn check(&self) -> Result<(), OxigraphError> {
+                if self.id <= self.broken.load(Ordering::SeqCst) {
+                    return Err(io::Error::from(io::ErrorKind::Interrupted).into());
+                }
+                if self.failures.load(Ordering::SeqCst) > 0 {
+                    self.failures.fetch_sub(1, Ordering::SeqCst);
+                    return Err(io::Error::from(io::ErrorKind::Interrupted).into());
+                }
+                Ok(())
+            }
+        }
+
+        impl<'a> RepositoryConnection for FlakyConnection<'a> {
+            type Transaction =
+                <MemoryRepositoryConnection<'a> as RepositoryConnection>::Transaction;
+            type PreparedQuery =
+                <MemoryRepositoryConnection<'a> as RepositoryConnection>::PreparedQuery;
+
+            fn prepare_query(
+                &self,
+                query: &str,
+                options: QueryOptions<'_>,
+            ) -> Result<Self::PreparedQuery, OxigraphError> {
+                self.inner.prepare_query(query, options)
+            }
+            fn quads_for_pattern<'b>(
+                &'b self,
+                subject: Option<&NamedOrBlankNode>,
+                predicate: Option<&NamedNode>,
+                object: Option<&OTerm>,
+                graph_name: Option<Option<&NamedOrBlankNode>>,
+            ) -> Box<dyn Iterator<Item = Result<Quad, OxigraphError>> + 'b>
+            where
+                Self: 'b,
+            {
+                self.inner
+                    .quads_for_pattern(subject, predicate, object, graph_name)
+            }
+            fn prepare_query_from_pattern(
+                &self,
+                graph_pattern: &GraphPattern,
+                options: QueryOptions<'_>,
+            ) -> Result<Self::PreparedQuery, OxigraphError> {
+                self.inner
+                    .prepare_query_from_pattern(graph_pattern, options)
+            }
+            fn transaction<E: From<OxigraphError>>(
+                &self,
+                f: impl FnOnce(&mut Self::Transaction) -> Result<(), E>,
+            ) -> Result<(), E> {
+                self.inner.transaction(f)
+            }
+            fn update(&self, update: &str) -> Result<(), OxigraphError> {
+                self.inner.update(update)
+            }
+            fn load_graph(
+                &mut self,
+                reader: impl BufRead,
+                syntax: GraphSyntax,
+                to_graph_name: Option<&NamedOrBlankNode>,
+                base_iri: Option<&str>,
+            ) -> Result<(), OxigraphError> {
+                self.inner
+                    .load_graph(reader, syntax, to_graph_name, base_iri)
+            }
+            fn load_dataset(
+                &mut self,
+                reader: impl BufRead,
+                syntax: DatasetSyntax,
+                base_iri: Option<&str>,
+            ) -> Result<(), OxigraphError> {
+                self.inner.load_dataset(reader, syntax, base_iri)
+            }
+            fn contains(&self, quad: &Quad) -> Result<bool, OxigraphError> {
+                self.check()?;
+                self.inner.contains(quad)
+            }
+            fn insert(&mut self, quad: &Quad) -> Result<(), OxigraphError> {
+                self.check()?;
+                self.inner.insert(quad)
+            }
+            fn remove(&mut self, quad: &Quad) -> Result<(), OxigraphError> {
+                self.inner.remove(quad)
+            }
+        }
+
+        impl<'a> Repository for &'a FlakyRepository {
+            type Connection = FlakyConnection<'a>;
+
+            fn connection(self) -> Result<Self::Connection, OxigraphError> {
+                let id = self.connections.fetch_add(1, Ordering::SeqCst) + 1;
+                Ok(FlakyConnection {
+                    inner: self.inner.connection()?,
+                    id,
+                    failures: &self.failures,
+                    broken: &self.broken,
+                })
+            }
+        }
+
+        let t = Term::<&str>::new_iri("http://example.org/").unwrap();
+        let mut d = SophiaRepository::new(FlakyRepository::default()).unwrap();
+
+        // by default, the transient error is reported
+        d.as_oxi().failures.store(1, Ordering::SeqCst);
+        assert!(d.contains(&t, &t, &t, None::<&Term<&str>>).is_err());
+        assert_eq!(d.as_oxi().connections.load(Ordering::SeqCst), 1);
+
+        // with retries, the operations succeed on a fresh connection
+        d.set_max_retries(1);
+        d.as_oxi().failures.store(1, Ordering::SeqCst);
+        assert!(d.insert(&t, &t, &t, None::<&Term<&str>>).unwrap());
+        assert_eq!(d.as_oxi().connections.load(Ordering::SeqCst), 2);
+        d.as_oxi().failures.store(1, Ordering::SeqCst);
+        assert!(d.contains(&t, &t, &t, None::<&Term<&str>>).unwrap());
+        assert_eq!(d.as_oxi().connections.load(Ordering::SeqCst), 3);
+
+        // retries are bounded
+        d.as_oxi().failures.store(2, Ordering::SeqCst);
+        assert!(d.contains(&t, &t, &t, None::<&Term<&str>>).is_err());
+        assert_eq!(d.as_oxi().connections.load(Ordering::SeqCst), 4);
+
+        // a broken cached connection is replaced by a fresh one after a successful mutation
+        d.as_oxi().failures.store(0, Ordering::SeqCst);
+        d.as_oxi().broken.store(4, Ordering::SeqCst);
+        let t2 = Term::<&str>::new_iri("http://example.org/2").unwrap();
+        assert!(d.insert(&t2, &t2, &t2, None::<&Term<&str>>).unwrap());
+        assert_eq!(d.as_oxi().connections.load(Ordering::SeqCst), 5);
+        assert!(d.contains(&t2, &t2, &t2, None::<&Term<&str>>).unwrap());
+        assert!(d.contains(&t, &t, &t, None::<&Term<&str>>).unwrap());
+        assert_eq!(d.as_oxi().connections.load(Ordering::SeqCst), 5);
+    }
+
+    /// Insert a quad in a store at `path`, drop it (after flushing it if `flush` is true),
+    /// reopen it and check the quad is still there.
+    #[cfg(any(feature = "rocksdb", feature = "sled"))]
+    fn check_persistence<R, F>(name: &str, open: F, flush: bool)
+    where
+        for<'x> &'x R: Repository,
+        F: Fn(&std::path::Path) -> Result<SophiaRepository<R>, RepositoryInitError>,
+    {
+        let path =
+            std::env::temp_dir().join(format!("sophia_oxigraph-{}-{}", name, std::process::id()));
+        let t = Term::<&str>::new_iri("http://example.org/").unwrap();
+        {
+            let mut d = open(&path).unwrap();
+            d.insert(&t, &t, &t, None::<&Term<&str>>).unwrap();
+            if flush {
+                d.flush().unwrap();
+            }
+        }
+        {
+            let d = open(&path).unwrap();
+            assert!(d.contains(&t, &t, &t, None::<&Term<&str>>).unwrap());
+            assert_eq!(d.quads().count(), 1);
+        }
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[cfg(feature = "rocksdb")]
+    #[test]
+    fn open_rocksdb() {
+        check_persistence(
+            "rocksdb",
+            |path| SophiaRepository::open_rocksdb(path),
+            false,
+        );
+    }
+
+    #[cfg(feature = "rocksdb")]
+    #[test]
+    fn flush_rocksdb() {
+        check_persistence(
+            "rocksdb-flush",
+            |path| SophiaRepository::open_rocksdb(path),
+            true,
+        );
+    }
+
+    #[cfg(feature = "sled")]
+    #[test]
+    fn open_sled() {
+        check_persistence("sled", |path| SophiaRepository::open_sled(path), false);
+    }
+
+    #[cfg(feature = "sled")]
+    #[test]
+    fn flush_sled() {
+        check_persistence("sled-flush", |path| SophiaRepository::open_sled(path), true);
+    }
+
+    #[test]
+    fn connection_open_error() {
+        struct FailingRepository;
+
+        impl<'a> Repository for &'a FailingRepository {
+            type Connection = oxigraph::MemoryRepositoryConnection<'a>;
+
+            fn connection(self) -> Result<Self::Connection, OxigraphError> {
+                Err(io::Error::new(io::ErrorKind::ConnectionRefused, "unavailable").into())
+            }
+        }
+
+        match SophiaRepository::new(FailingRepository) {
+            Err(RepositoryInitError::ConnectionOpen(err)) => {
+                assert!(err.to_string().contains("unavailable"))
+            }
+            Err(err) => panic!("unexpected error {}", err),
+            Ok(_) => panic!("no error"),
+        }
+    }
 }
@@ -0,0 +1,389 @@
+//! Clonable handles to a shared SophiaConnection
+use crate::connection::{oxi_quad_matcher, MutationError, SophiaConnection};
+use crate::quad::QuadBridge;
+use ouroboros::self_referencing;
+use oxigraph::model::Quad as OQuad;
+use oxigraph::{Error as OxigraphError, RepositoryConnection};
+use sophia::dataset::{DQuadSource, MDResult, MutableDataset};
+use sophia::quad::stream::QuadSource;
+use sophia::quad::streaming_mode::*;
+use sophia::triple::stream::StreamResult;
+use sophia_term::matcher::{GraphNameMatcher, TermMatcher};
+use sophia_term::{Term, TermData};
+use std::cell::RefCell;
+use std::iter::empty;
+use std::ops::Deref;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+/// A [`Shared`] connection, for use in a single thread
+///
+/// The connection is held in a `RefCell`.
+/// Each method borrows it for the duration of the call,
+/// and the quad sources returned by `quads*` keep it borrowed until they are dropped.
+/// Reading through several handles at the same time is fine,
+/// but a mutating method called on any handle while the connection is borrowed
+/// (e.g. removing quads while iterating over `quads()`,
+/// or from the quad source passed to `insert_all`) panics.
+///
+/// [`Shared`]: struct.Shared.html
+pub type SharedConnection<C> = Shared<Rc<RefCell<SophiaConnection<C>>>>;
+
+/// A [`Shared`] connection, that can be shared between threads if `C` allows it
+///
+/// The connection is held in a `Mutex`,
+/// so concurrent calls on different handles are serialized.
+/// The quad sources returned by `quads*` hold the lock until they are dropped.
+/// A method called on a handle by the thread already holding the lock
+/// (e.g. while iterating over `quads()`, or from the quad source passed to `insert_all`) deadlocks.
+///
+/// [`Shared`]: struct.Shared.html
+pub type SyncSharedConnection<C> = Shared<Arc<Mutex<SophiaConnection<C>>>>;
+
+/// A clonable handle to a [`SophiaConnection`]
+///
+/// All the clones of a handle use the same underlying connection,
+/// and implement `Dataset` and `MutableDataset` by delegating to it.
+/// The quads returned by `quads*` are retrieved lazily,
+/// so the connection stays borrowed until they are dropped.
+/// See [`SharedConnection`] and [`SyncSharedConnection`] for the consequences.
+///
+/// [`SophiaConnection`]: ../connection/struct.SophiaConnection.html
+/// [`SharedConnection`]: type.SharedConnection.html
+/// [`SyncSharedConnection`]: type.SyncSharedConnection.html
+#[derive(Clone, Debug)]
+pub struct Shared<K>(K);
+
+/// A cell holding a [`SophiaConnection`], and sharing it between its clones
+///
+/// [`SophiaConnection`]: ../connection/struct.SophiaConnection.html
+pub trait ConnectionCell: Clone {
+    /// The type of the underlying Oxigraph connection
+    type Connection: RepositoryConnection;
+
+    /// Borrow the shared connection until the returned guard is dropped
+    fn guard(&self) -> Box<dyn Deref<Target = SophiaConnection<Self::Connection>> + '_>;
+
+    /// Call `f` with the shared connection
+    fn with<T, F>(&self, f: F) -> T
+    where
+        F: FnOnce(&SophiaConnection<Self::Connection>) -> T;
+
+    /// Call `f` with the shared connection, borrowed mutably
+    fn with_mut<T, F>(&self, f: F) -> T
+    where
+        F: FnOnce(&mut SophiaConnection<Self::Connection>) -> T;
+}
+
+impl<C> ConnectionCell for Rc<RefCell<SophiaConnection<C>>>
+where
+    C: RepositoryConnection,
+{
+    type Connection = C;
+
+    fn guard(&self) -> Box<dyn Deref<Target = SophiaConnection<C>> + '_> {
+        Box::new(self.borrow())
+    }
+
+    fn with<T, F>(&self, f: F) -> T
+    where
+        F: FnOnce(&SophiaConnection<C>) -> T,
+    {
+        f(&self.borrow())
+    }
+
+    fn with_mut<T, F>(&self, f: F) -> T
+    where
+        F: FnOnce(&mut SophiaConnection<C>) -> T,
+    {
+        f(&mut self.borrow_mut())
+    }
+}
+
+impl<C> ConnectionCell for Arc<Mutex<SophiaConnection<C>>>
+where
+    C: RepositoryConnection,
+{
+    type Connection = C;
+
+    fn guard(&self) -> Box<dyn Deref<Target = SophiaConnection<C>> + '_> {
+        Box::new(Mutex::lock(self).unwrap())
+    }
+
+    fn with<T, F>(&self, f: F) -> T
+    where
+        F: FnOnce(&SophiaConnection<C>) -> T,
+    {
+        f(&self.lock().unwrap())
+    }
+
+    fn with_mut<T, F>(&self, f: F) -> T
+    where
+        F: FnOnce(&mut SophiaConnection<C>) -> T,
+    {
+        f(&mut self.lock().unwrap())
+    }
+}
+
+impl<C> SharedConnection<C>
+where
+    C: RepositoryConnection,
+{
+    /// Share `conn` between the clones of the returned handle
+    pub fn new(conn: SophiaConnection<C>) -> Self {
+        Shared(Rc::new(RefCell::new(conn)))
+    }
+}
+
+impl<C> SyncSharedConnection<C>
+where
+    C: RepositoryConnection,
+{
+    /// Share `conn` between the clones of the returned handle
+    pub fn new(conn: SophiaConnection<C>) -> Self {
+        Shared(Arc::new(Mutex::new(conn)))
+    }
+}
+
+impl<K> Shared<K>
+where
+    K: ConnectionCell,
+{
+    /// Call `f` with the shared connection
+    #[inline]
+    pub fn with<T, F>(&self, f: F) -> T
+    where
+        F: FnOnce(&SophiaConnection<K::Connection>) -> T,
+    {
+        self.0.with(f)
+    }
+
+    /// Call `f` with the shared connection, borrowed mutably
+    #[inline]
+    pub fn with_mut<T, F>(&self, f: F) -> T
+    where
+        F: FnOnce(&mut SophiaConnection<K::Connection>) -> T,
+    {
+        self.0.with_mut(f)
+    }
+
+    /// Iterate over the quads matching the given pattern (see `SophiaConnection::oxi_quads_with`).
+    fn quads_for<T, U, V, W>(
+        &self,
+        s: Option<&Term<T>>,
+        p: Option<&Term<U>>,
+        o: Option<&Term<V>>,
+        g: Option<Option<&Term<W>>>,
+    ) -> DQuadSource<Self>
+    where
+        T: TermData,
+        U: TermData,
+        V: TermData,
+        W: TermData,
+    {
+        let guard = self.0.guard();
+        match guard.oxi_pattern(s, p, o, g) {
+            Ok(pattern) => {
+                let quads = GuardedQuads::new(guard, |c| c.oxi_quads_for_pattern(&pattern));
+                Box::new(quads.map(|r| r.map(StreamedQuad::by_value)))
+            }
+            Err(_) => Box::new(empty()),
+        }
+    }
+
+    /// Iterate over the quads matching the given matchers (see `Dataset::quads_matching`).
+    fn quads_matching_for<'s, S, P, O, G>(
+        &'s self,
+        ms: &'s S,
+        mp: &'s P,
+        mo: &'s O,
+        mg: &'s G,
+    ) -> DQuadSource<'s, Self>
+    where
+        S: TermMatcher + ?Sized,
+        P: TermMatcher + ?Sized,
+        O: TermMatcher + ?Sized,
+        G: GraphNameMatcher + ?Sized,
+    {
+        let guard = self.0.guard();
+        match guard.oxi_matching_pattern(ms, mp, mo, mg) {
+            Ok(pattern) => {
+                let matches = oxi_quad_matcher(&pattern, ms, mp, mo, mg);
+                let quads = GuardedQuads::new(guard, |c| c.oxi_quads_for_pattern(&pattern));
+                Box::new(
+                    quads
+                        .filter(move |r| match r {
+                            Ok(q) => matches(q.as_oxi()),
+                            Err(_) => true,
+                        })
+                        .map(|r| r.map(StreamedQuad::by_value)),
+                )
+            }
+            Err(_) => Box::new(empty()),
+        }
+    }
+}
+
+delegate_dataset!(
+    impl[K] Shared<K> where [K: ConnectionCell],
+    pattern(Self::quads_for, Self::quads_matching_for, Self::with)
+);
+
+/// The quads of a shared connection, together with the guard keeping it borrowed
+#[self_referencing]
+struct GuardedQuads<'s, C: RepositoryConnection> {
+    guard: Box<dyn Deref<Target = SophiaConnection<C>> + 's>,
+    #[borrows(guard)]
+    #[covariant]
+    quads: Box<dyn Iterator<Item = Result<OQuad, OxigraphError>> + 'this>,
+}
+
+impl<'s, C> Iterator for GuardedQuads<'s, C>
+where
+    C: RepositoryConnection,
+{
+    type Item = Result<QuadBridge, OxigraphError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.with_mut(|fields| {
+            let conn = fields.guard;
+            fields.quads.next().map(|r| r.map(|q| conn.quad_bridge(q)))
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.with_quads(|quads| quads.size_hint())
+    }
+}
+
+impl<K> MutableDataset for Shared<K>
+where
+    K: ConnectionCell,
+{
+    type MutationError = MutationError;
+    #[inline]
+    fn insert<T, U, V, W>(
+        &mut self,
+        s: &Term<T>,
+        p: &Term<U>,
+        o: &Term<V>,
+        g: Option<&Term<W>>,
+    ) -> MDResult<Self, bool>
+    where
+        T: TermData,
+        U: TermData,
+        V: TermData,
+        W: TermData,
+    {
+        self.0.with_mut(|c| c.insert(s, p, o, g))
+    }
+    #[inline]
+    fn remove<T, U, V, W>(
+        &mut self,
+        s: &Term<T>,
+        p: &Term<U>,
+        o: &Term<V>,
+        g: Option<&Term<W>>,
+    ) -> MDResult<Self, bool>
+    where
+        T: TermData,
+        U: TermData,
+        V: TermData,
+        W: TermData,
+    {
+        self.0.with_mut(|c| c.remove(s, p, o, g))
+    }
+    #[inline]
+    fn insert_all<QS>(&mut self, src: QS) -> StreamResult<usize, QS::Error, MutationError>
+    where
+        QS: QuadSource,
+    {
+        self.0.with_mut(|c| c.insert_all(src))
+    }
+    #[inline]
+    fn remove_all<QS>(&mut self, src: QS) -> StreamResult<usize, QS::Error, MutationError>
+    where
+        QS: QuadSource,
+    {
+        self.0.with_mut(|c| c.remove_all(src))
+    }
+    #[inline]
+    fn remove_matching<S, P, O, G>(
+        &mut self,
+        ms: &S,
+        mp: &P,
+        mo: &O,
+        mg: &G,
+    ) -> MDResult<Self, usize>
+    where
+        S: TermMatcher + ?Sized,
+        P: TermMatcher + ?Sized,
+        O: TermMatcher + ?Sized,
+        G: GraphNameMatcher + ?Sized,
+    {
+        self.0.with_mut(|c| c.remove_matching(ms, mp, mo, mg))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{ex, DG};
+    use oxigraph::{MemoryRepository, Repository};
+    use sophia::dataset::Dataset;
+    use sophia::quad::Quad;
+    use sophia_term::matcher::ANY;
+
+    #[test]
+    fn shared() {
+        let repo = MemoryRepository::default();
+        let mut d1 = SharedConnection::new(SophiaConnection::new(repo.connection().unwrap()));
+        let mut d2 = d1.clone();
+        d1.insert(&ex("s"), &ex("p"), &ex("o1"), DG).unwrap();
+        d2.insert(&ex("s"), &ex("p"), &ex("o2"), DG).unwrap();
+        assert_eq!(d1.quads().count(), 2);
+        assert_eq!(d2.quads().count(), 2);
+        assert_eq!(d2.quads_with_o(&ex("o1")).count(), 1);
+
+        let o2 = |t: &Term<&str>| t.value().ends_with("o2");
+        assert_eq!(d2.quads_matching(&ANY, &ANY, &o2, &ANY).count(), 1);
+
+        // the connection can be read through another handle while the quads are iterated
+        for q in d1.quads() {
+            let q = q.unwrap();
+            assert!(d2.contains(q.s(), q.p(), q.o(), q.g()).unwrap());
+        }
+        let quads: Vec<_> = d1.quads().map(|q| q.unwrap()).collect();
+        for q in quads {
+            d2.remove(q.s(), q.p(), q.o(), q.g()).unwrap();
+        }
+        assert_eq!(d1.quads().count(), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn shared_mutation_while_iterating() {
+        let repo = MemoryRepository::default();
+        let mut d1 = SharedConnection::new(SophiaConnection::new(repo.connection().unwrap()));
+        let mut d2 = d1.clone();
+        d1.insert(&ex("s"), &ex("p"), &ex("o"), DG).unwrap();
+        for q in d1.quads() {
+            let q = q.unwrap();
+            d2.remove(q.s(), q.p(), q.o(), q.g()).unwrap();
+        }
+    }
+
+    #[test]
+    fn sync_shared() {
+        let repo = MemoryRepository::default();
+        let mut d1 = SyncSharedConnection::new(SophiaConnection::new(repo.connection().unwrap()));
+        let d2 = d1.clone();
+        d1.insert(&ex("s"), &ex("p"), &ex("o"), Some(&ex("g")))
+            .unwrap();
+        assert_eq!(d2.quads_with_g(Some(&ex("g"))).count(), 1);
+        assert!(d2
+            .contains(&ex("s"), &ex("p"), &ex("o"), Some(&ex("g")))
+            .unwrap());
+        assert_eq!(d2.with(|c| c.graph_count().unwrap()), 1);
+    }
+}
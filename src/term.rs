@@ -1,12 +1,15 @@
 //! Conversion between Sophia and Oxigraph Terms.
 use oxigraph::model::{
     BlankNode as OBlankNode, Literal as OLiteral, NamedNode, NamedOrBlankNode, Term as OTerm,
+    Triple as OTriple,
 };
 use sophia_term::blank_node::BlankNode as SBlankNode;
 use sophia_term::iri::Iri as SIri;
 use sophia_term::literal::Literal as SLiteral;
 use sophia_term::{Term as STerm, TermData};
-use std::io::Write;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::sync::Arc;
 use thiserror::Error;
 
 lazy_static::lazy_static! {
@@ -14,6 +17,12 @@ lazy_static::lazy_static! {
     pub static ref XSD_STRING: SIri<String> = SIri::new_unchecked("http://www.w3.org/2001/XMLSchema#string", true);
 }
 
+/// The rdf:langString IRI
+const RDF_LANG_STRING: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#langString";
+
+/// The XML Schema namespace
+const XSD_NS: &str = "http://www.w3.org/2001/XMLSchema#";
+
 /// Trait for converting to Sophia blank nodes
 pub trait AsSophiaBlankNode {
     /// Convert by simply borrowing the underlying text of self
@@ -92,6 +101,11 @@ pub trait AsSophiaLiteral {
         TD: TermData + From<String>;
 }
 
+/// The conversion always reflects the lexical form held by the Oxigraph literal.
+/// For literals read from a store, this is the form in which Oxigraph stored them,
+/// which is canonical for the datatypes that Oxigraph stores by value
+/// (e.g. numeric datatypes, see [`TryOxigraphize`](trait.TryOxigraphize.html)).
+/// The original lexical form of such literals can not be recovered.
 impl AsSophiaLiteral for OLiteral {
     fn as_sophia_l_ref(&self) -> SLiteral<&str> {
         match self.language() {
@@ -105,8 +119,7 @@ impl AsSophiaLiteral for OLiteral {
     {
         match self.language() {
             None => SLiteral::new_dt(self.value(), self.datatype().as_sophia_i::<TD>()),
-            Some(tag) => SLiteral::new_lang(self.value(), tag)
-                .unwrap_or_else(|_| SLiteral::new_lang_unchecked(self.value(), "und")),
+            Some(tag) => SLiteral::new_lang_unchecked(self.value(), tag),
         }
     }
     fn into_sophia_l<TD>(self) -> SLiteral<TD>
@@ -211,6 +224,273 @@ impl AsSophiaTerm for NamedNode {
     }
 }
 
+/// Trait for converting to Sophia triples
+///
+/// Triples are converted to arrays of terms, which implement Sophia's `Triple` trait.
+pub trait AsSophiaTriple {
+    /// Convert by simply borrowing the underlying text of self
+    fn as_sophia_t_ref(&self) -> [STerm<&str>; 3];
+    /// Convert by copying the underlying text of self
+    fn as_sophia_t<TD>(&self) -> [STerm<TD>; 3]
+    where
+        TD: TermData + for<'x> From<&'x str>;
+    /// Convert by consuming the underlying text of self
+    fn into_sophia_t<TD>(self) -> [STerm<TD>; 3]
+    where
+        TD: TermData + From<String>;
+}
+
+impl AsSophiaTriple for OTriple {
+    fn as_sophia_t_ref(&self) -> [STerm<&str>; 3] {
+        [
+            self.subject().as_sophia_ref(),
+            self.predicate().as_sophia_ref(),
+            self.object().as_sophia_ref(),
+        ]
+    }
+    fn as_sophia_t<TD>(&self) -> [STerm<TD>; 3]
+    where
+        TD: TermData + for<'x> From<&'x str>,
+    {
+        [
+            self.subject().as_sophia(),
+            self.predicate().as_sophia(),
+            self.object().as_sophia(),
+        ]
+    }
+    fn into_sophia_t<TD>(self) -> [STerm<TD>; 3]
+    where
+        TD: TermData + From<String>,
+    {
+        let (s, p, o) = self.destruct();
+        [s.into_sophia(), p.into_sophia(), o.into_sophia()]
+    }
+}
+
+/// A namespace, producing both Sophia and Oxigraph IRIs
+///
+/// ```
+/// # use sophia_oxigraph::term::Namespace;
+/// let ex = Namespace::new("http://example.org/").unwrap();
+/// let (s_iri, o_iri) = ex.get("foo").unwrap();
+/// assert_eq!(o_iri.as_str(), "http://example.org/foo");
+/// ```
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Namespace(String);
+
+impl Namespace {
+    /// Build a namespace from its base IRI,
+    /// failing with [`ConversionError::InvalidIri`] if `base` is not an absolute IRI.
+    ///
+    /// [`ConversionError::InvalidIri`]: enum.ConversionError.html#variant.InvalidIri
+    pub fn new(base: &str) -> Result<Self, ConversionError> {
+        NamedNode::new(base).map_err(|_| ConversionError::InvalidIri(base.to_string()))?;
+        Ok(Namespace(base.to_string()))
+    }
+
+    /// The base IRI of this namespace
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The IRI made of this namespace followed by `suffix`,
+    /// both as a Sophia IRI and as an Oxigraph named node.
+    ///
+    /// The IRI is checked only once,
+    /// failing with [`ConversionError::InvalidIri`] if `suffix` makes it invalid.
+    ///
+    /// [`ConversionError::InvalidIri`]: enum.ConversionError.html#variant.InvalidIri
+    pub fn get(&self, suffix: &str) -> Result<(SIri<String>, NamedNode), ConversionError> {
+        let iri = format!("{}{}", self.0, suffix);
+        let node = NamedNode::new(iri.as_str()).map_err(|_| ConversionError::InvalidIri(iri))?;
+        Ok((node.as_sophia_i(), node))
+    }
+}
+
+/// Deduplicates the text of converted terms,
+/// so that repeated IRIs, datatypes or literals share the same storage.
+///
+/// Interned strings are never released, until the interner itself is dropped.
+#[derive(Clone, Debug, Default)]
+pub struct Interner(HashSet<Arc<str>>);
+
+impl Interner {
+    /// Return the shared copy of `txt`, creating it if necessary
+    pub fn intern(&mut self, txt: &str) -> Arc<str> {
+        match self.0.get(txt) {
+            Some(shared) => shared.clone(),
+            None => {
+                let shared: Arc<str> = Arc::from(txt);
+                self.0.insert(shared.clone());
+                shared
+            }
+        }
+    }
+
+    /// Convert a borrowed Sophia term into a term whose text is interned
+    ///
+    /// # Errors
+    /// Variables are rejected with [`ConversionError::Variable`],
+    /// as they are never produced by Oxigraph.
+    ///
+    /// [`ConversionError::Variable`]: enum.ConversionError.html#variant.Variable
+    pub fn term(&mut self, t: STerm<&str>) -> Result<STerm<Arc<str>>, ConversionError> {
+        let value = match &t {
+            STerm::Variable(v) => return Err(ConversionError::Variable(v.as_str().to_string())),
+            _ => self.intern(&t.value()),
+        };
+        Ok(match &t {
+            STerm::Literal(l) => STerm::Literal(match l.lang() {
+                Some(tag) => SLiteral::new_lang_unchecked(value, self.intern(tag)),
+                None => {
+                    let dt = SIri::new_unchecked(self.intern(&l.dt().value()), true);
+                    SLiteral::new_dt(value, dt)
+                }
+            }),
+            STerm::BNode(_) => STerm::BNode(SBlankNode::new_unchecked(value)),
+            _ => STerm::Iri(SIri::new_unchecked(value, true)),
+        })
+    }
+
+    /// The number of distinct strings interned so far
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether no string has been interned yet
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// The native value of a literal, as returned by [`LiteralValue::as_native_value`].
+///
+/// [`LiteralValue::as_native_value`]: trait.LiteralValue.html#tymethod.as_native_value
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NativeValue {
+    /// An `xsd:boolean`
+    Boolean(bool),
+    /// An `xsd:integer` or one of its derived datatypes (if it fits in an `i128`)
+    Integer(i128),
+    /// An `xsd:double`, `xsd:float` or `xsd:decimal`
+    Double(f64),
+}
+
+/// Trait for interpreting a literal as a native Rust value
+pub trait LiteralValue {
+    /// The native value of this literal,
+    /// or `None` if its datatype is not supported,
+    /// its lexical form is not valid for its datatype,
+    /// or its value is out of the range of its datatype.
+    ///
+    /// Date and time datatypes are not supported.
+    fn as_native_value(&self) -> Option<NativeValue>;
+}
+
+impl LiteralValue for OLiteral {
+    fn as_native_value(&self) -> Option<NativeValue> {
+        let dt = self.datatype().as_str();
+        if !dt.starts_with(XSD_NS) {
+            return None;
+        }
+        let dt = &dt[XSD_NS.len()..];
+        let value = self.value().trim();
+        match dt {
+            "boolean" => match value {
+                "true" | "1" => Some(NativeValue::Boolean(true)),
+                "false" | "0" => Some(NativeValue::Boolean(false)),
+                _ => None,
+            },
+            "double" | "float" => match value {
+                "INF" | "+INF" => Some(NativeValue::Double(f64::INFINITY)),
+                "-INF" => Some(NativeValue::Double(f64::NEG_INFINITY)),
+                "NaN" => Some(NativeValue::Double(f64::NAN)),
+                _ if is_double_lexical(value) => value.parse().ok().map(NativeValue::Double),
+                _ => None,
+            },
+            "decimal" if is_decimal_lexical(value) => value.parse().ok().map(NativeValue::Double),
+            _ => {
+                let (min, max) = integer_range(dt)?;
+                if !is_integer_lexical(value) {
+                    return None;
+                }
+                let n: i128 = value.parse().ok()?;
+                if n < min || n > max {
+                    return None;
+                }
+                Some(NativeValue::Integer(n))
+            }
+        }
+    }
+}
+
+/// The range of values of `xsd:{dt}`, if it is `xsd:integer` or one of its derived datatypes.
+///
+/// The range of unbounded datatypes is truncated to that of `i128`.
+fn integer_range(dt: &str) -> Option<(i128, i128)> {
+    Some(match dt {
+        "integer" => (i128::MIN, i128::MAX),
+        "nonNegativeInteger" => (0, i128::MAX),
+        "positiveInteger" => (1, i128::MAX),
+        "nonPositiveInteger" => (i128::MIN, 0),
+        "negativeInteger" => (i128::MIN, -1),
+        "long" => (i64::MIN.into(), i64::MAX.into()),
+        "int" => (i32::MIN.into(), i32::MAX.into()),
+        "short" => (i16::MIN.into(), i16::MAX.into()),
+        "byte" => (i8::MIN.into(), i8::MAX.into()),
+        "unsignedLong" => (0, u64::MAX.into()),
+        "unsignedInt" => (0, u32::MAX.into()),
+        "unsignedShort" => (0, u16::MAX.into()),
+        "unsignedByte" => (0, u8::MAX.into()),
+        _ => return None,
+    })
+}
+
+/// Strip the optional sign of a numeric lexical form
+fn unsigned(value: &str) -> &str {
+    if value.starts_with('+') || value.starts_with('-') {
+        &value[1..]
+    } else {
+        value
+    }
+}
+
+fn all_digits(txt: &str) -> bool {
+    txt.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Whether `value` is in the lexical space of `xsd:integer`
+fn is_integer_lexical(value: &str) -> bool {
+    let digits = unsigned(value);
+    !digits.is_empty() && all_digits(digits)
+}
+
+/// Whether `value` is in the lexical space of `xsd:decimal`
+/// (which has neither exponent nor special values)
+fn is_decimal_lexical(value: &str) -> bool {
+    let mut parts = unsigned(value).splitn(2, '.');
+    let int = parts.next().unwrap_or("");
+    let frac = parts.next();
+    all_digits(int)
+        && frac.map_or(true, all_digits)
+        && (!int.is_empty() || frac.map_or(false, |f| !f.is_empty()))
+}
+
+/// Whether `value` is a finite number in the lexical space of `xsd:double`
+fn is_double_lexical(value: &str) -> bool {
+    let mantissa = match value.find(|c| c == 'e' || c == 'E') {
+        Some(i) => {
+            if !is_integer_lexical(&value[i + 1..]) {
+                return false;
+            }
+            &value[..i]
+        }
+        None => value,
+    };
+    is_decimal_lexical(mantissa)
+}
+
 /// Trait for converting to Oxigraph term
 pub trait TryOxigraphize<T> {
     /// Convert to an Oxigraph term type
@@ -220,14 +500,18 @@ pub trait TryOxigraphize<T> {
 impl<TD: TermData> TryOxigraphize<OBlankNode> for SBlankNode<TD> {
     fn try_oxigraphize(&self) -> Result<OBlankNode, ConversionError> {
         let value = self.value();
+        // preserve the label whenever Oxigraph accepts it
+        if let Ok(b) = OBlankNode::new(value.to_string()) {
+            return Ok(b);
+        }
         if let Ok(id) = u128::from_str_radix(&value, 16) {
             return Ok(OBlankNode::new_from_unique_id(id));
         }
         if value.len() <= 16 {
+            // pad the label with zeros, and read it in a platform-independent way
             let mut id = [0_u8; 16];
-            write!(&mut id[..], "{}", value).unwrap();
-            let id = unsafe { std::mem::transmute(id) };
-            return Ok(OBlankNode::new_from_unique_id(id));
+            id[..value.len()].copy_from_slice(value.as_bytes());
+            return Ok(OBlankNode::new_from_unique_id(u128::from_le_bytes(id)));
         }
         Err(ConversionError::IncompatibleBnodeId(
             self.value().to_string(),
@@ -235,30 +519,142 @@ impl<TD: TermData> TryOxigraphize<OBlankNode> for SBlankNode<TD> {
     }
 }
 
+/// A bidirectional map between blank node labels rejected by Oxigraph
+/// and the synthetic Oxigraph blank nodes standing for them.
+///
+/// See [`SophiaConnection::with_bnode_cache`](../connection/struct.SophiaConnection.html#method.with_bnode_cache).
+#[derive(Clone, Debug, Default)]
+pub(crate) struct BnodeCache {
+    bnodes: HashMap<String, OBlankNode>,
+    labels: HashMap<OBlankNode, String>,
+}
+
+impl BnodeCache {
+    /// Convert `b`, assigning it a synthetic blank node if Oxigraph can not represent it.
+    pub(crate) fn oxigraphize<TD: TermData>(&mut self, b: &SBlankNode<TD>) -> OBlankNode {
+        let label = b.value();
+        if let Ok(b) = OBlankNode::new(label.to_string()) {
+            return b;
+        }
+        if let Some(b) = self.bnodes.get(&*label) {
+            return b.clone();
+        }
+        let synthetic = OBlankNode::default();
+        self.bnodes.insert(label.to_string(), synthetic.clone());
+        self.labels.insert(synthetic.clone(), label.to_string());
+        synthetic
+    }
+
+    /// The original label of `b`, if it is a synthetic blank node.
+    pub(crate) fn label(&self, b: &OBlankNode) -> Option<&str> {
+        self.labels.get(b).map(String::as_str)
+    }
+}
+
 impl<TD: TermData> TryOxigraphize<NamedNode> for SIri<TD> {
     fn try_oxigraphize(&self) -> Result<NamedNode, ConversionError> {
         let value = self.value().to_string();
         if !self.is_absolute() {
-            Err(ConversionError::RelativeIriRef(value))
-        } else {
-            Ok(NamedNode::new_unchecked(value))
+            return Err(ConversionError::RelativeIriRef(value));
         }
+        NamedNode::new(value.as_str()).map_err(|_| ConversionError::InvalidIri(value))
     }
 }
 
+/// Note that Oxigraph stores literals of some well-known datatypes
+/// (e.g. `xsd:dateTime` or `xsd:decimal`) by value rather than by lexical form.
+/// Such literals are therefore read back from a store in their canonical form
+/// (e.g. `"1.0"^^xsd:decimal` is read back as `"1"^^xsd:decimal`);
+/// their original lexical form is not stored, so it can not be preserved.
+/// For `xsd:dateTime`, the timezone offset is preserved,
+/// except that a zero offset (`+00:00`) is read back as `Z`.
+///
+/// Language-tagged literals are converted based on their tag only,
+/// as their datatype is necessarily `rdf:langString`.
+/// The case of the tag is preserved.
+/// Conversely, a literal with datatype `rdf:langString` but no language tag
+/// is rejected with [`ConversionError::MissingLanguageTag`],
+/// and a literal whose language tag is malformed
+/// is rejected with [`ConversionError::InvalidLanguageTag`].
+///
+/// [`ConversionError::MissingLanguageTag`]: enum.ConversionError.html#variant.MissingLanguageTag
+/// [`ConversionError::InvalidLanguageTag`]: enum.ConversionError.html#variant.InvalidLanguageTag
 impl<TD: TermData> TryOxigraphize<OLiteral> for SLiteral<TD> {
     fn try_oxigraphize(&self) -> Result<OLiteral, ConversionError> {
-        let value = self.value().to_string();
-        Ok(match self.lang() {
-            None => OLiteral::new_typed_literal(value, self.dt().try_oxigraphize()?),
-            Some(tag) => OLiteral::new_language_tagged_literal_unchecked(
-                value,
-                tag.as_ref().to_ascii_lowercase(),
-            ),
-        })
+        oxigraphize_literal(self, true)
     }
 }
 
+/// Convert a Sophia literal to an Oxigraph literal, without validating its language tag.
+///
+/// This is only safe if the language tags of the data are known to be valid.
+/// See also [`SophiaConnection::with_unchecked_language_tags`](../connection/struct.SophiaConnection.html#method.with_unchecked_language_tags).
+pub fn oxigraphize_literal_unchecked<TD: TermData>(
+    lit: &SLiteral<TD>,
+) -> Result<OLiteral, ConversionError> {
+    oxigraphize_literal(lit, false)
+}
+
+/// The datatypes (in the XSD namespace) whose literals Oxigraph stores by value
+const BY_VALUE_DATATYPES: &[&str] = &[
+    "boolean",
+    "float",
+    "double",
+    "integer",
+    "decimal",
+    "date",
+    "time",
+    "dateTime",
+    "duration",
+    "yearMonthDuration",
+    "dayTimeDuration",
+];
+
+/// Whether an Oxigraph term is a literal that Oxigraph stores by value,
+/// and may therefore read back in a different (canonical) lexical form.
+pub(crate) fn is_stored_by_value(t: &OTerm) -> bool {
+    match t {
+        OTerm::Literal(lit) => {
+            let dt = lit.datatype().as_str();
+            dt.starts_with(XSD_NS) && BY_VALUE_DATATYPES.contains(&&dt[XSD_NS.len()..])
+        }
+        _ => false,
+    }
+}
+
+fn oxigraphize_literal<TD: TermData>(
+    lit: &SLiteral<TD>,
+    check_lang: bool,
+) -> Result<OLiteral, ConversionError> {
+    let value = lit.value().to_string();
+    Ok(match lit.lang() {
+        None if *lit.dt().value() == *RDF_LANG_STRING => {
+            return Err(ConversionError::MissingLanguageTag(value))
+        }
+        // xsd:string literals are simple literals for Oxigraph
+        None if *lit.dt().value() == *XSD_STRING.value() => OLiteral::new_simple_literal(value),
+        None => OLiteral::new_typed_literal(value, lit.dt().try_oxigraphize()?),
+        Some(tag) => {
+            let tag = tag.as_ref();
+            if check_lang && !is_valid_language_tag(tag) {
+                return Err(ConversionError::InvalidLanguageTag(tag.to_string()));
+            }
+            OLiteral::new_language_tagged_literal_unchecked(value, tag)
+        }
+    })
+}
+
+/// Whether `tag` has the shape of a BCP-47 language tag,
+/// i.e. a primary subtag of 1 to 8 letters,
+/// followed by any number of subtags of 1 to 8 letters or digits, separated by `-`.
+fn is_valid_language_tag(tag: &str) -> bool {
+    let mut subtags = tag.split('-');
+    let primary = subtags.next().unwrap_or("");
+    let valid =
+        |t: &str, f: fn(&u8) -> bool| (1..=8).contains(&t.len()) && t.as_bytes().iter().all(f);
+    valid(primary, u8::is_ascii_alphabetic) && subtags.all(|t| valid(t, u8::is_ascii_alphanumeric))
+}
+
 impl<TD: TermData> TryOxigraphize<OTerm> for STerm<TD> {
     fn try_oxigraphize(&self) -> Result<OTerm, ConversionError> {
         match self {
@@ -292,6 +688,41 @@ impl<TD: TermData> TryOxigraphize<NamedNode> for STerm<TD> {
     }
 }
 
+/// Customizable conversion of Sophia terms to Oxigraph terms.
+///
+/// The default implementation of each method relies on [`TryOxigraphize`],
+/// so implementors only need to override the positions they want to customize
+/// (e.g. to rewrite some IRIs).
+///
+/// [`TryOxigraphize`]: trait.TryOxigraphize.html
+pub trait OxiTermConverter: Debug {
+    /// Convert a term in subject position
+    fn subject(&self, t: &STerm<&str>) -> Result<NamedOrBlankNode, ConversionError> {
+        t.try_oxigraphize()
+    }
+    /// Convert a term in predicate position
+    fn predicate(&self, t: &STerm<&str>) -> Result<NamedNode, ConversionError> {
+        t.try_oxigraphize()
+    }
+    /// Convert a term in object position
+    fn object(&self, t: &STerm<&str>) -> Result<OTerm, ConversionError> {
+        t.try_oxigraphize()
+    }
+    /// Convert a graph name
+    fn graph_name(&self, t: &STerm<&str>) -> Result<NamedOrBlankNode, ConversionError> {
+        t.try_oxigraphize()
+    }
+}
+
+/// The default [`OxiTermConverter`], relying on [`TryOxigraphize`].
+///
+/// [`OxiTermConverter`]: trait.OxiTermConverter.html
+/// [`TryOxigraphize`]: trait.TryOxigraphize.html
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultConverter;
+
+impl OxiTermConverter for DefaultConverter {}
+
 /// This error is raised when a Sophia term can not be converted to Oxigraph
 #[derive(Debug, Error)]
 pub enum ConversionError {
@@ -304,6 +735,18 @@ pub enum ConversionError {
     /// The sophia term is a literal used in subject or predicate position
     #[error("Oxigraph only supports literals in object position '{0}'")]
     Literal(String),
+    /// The literal has datatype rdf:langString but no language tag
+    #[error("Literal with datatype rdf:langString has no language tag '{0}'")]
+    MissingLanguageTag(String),
+    /// The language tag of the literal is malformed
+    #[error("Invalid language tag '{0}'")]
+    InvalidLanguageTag(String),
+    /// The literal is longer than the maximum length configured on the connection
+    #[error("Literal of length {0} exceeds the maximum length {1}")]
+    LiteralTooLong(usize, usize),
+    /// The IRI is absolute but rejected by Oxigraph's IRI parser
+    #[error("Invalid IRI '{0}'")]
+    InvalidIri(String),
     /// The IRI reference is relative
     #[error("Oxigraph does not support relatife IRIrefs '{0}'")]
     RelativeIriRef(String),
@@ -311,3 +754,232 @@ pub enum ConversionError {
     #[error("Oxigraph does not variables as terms '{0}'")]
     Variable(String),
 }
+
+impl ConversionError {
+    /// Whether this error comes from a term that is only allowed in generalized RDF
+    /// (a blank node in predicate position, or a literal elsewhere than in object position).
+    pub fn is_generalized(&self) -> bool {
+        matches!(
+            self,
+            ConversionError::BlankNode(_) | ConversionError::Literal(_)
+        )
+    }
+}
+
+/// How quads that are only valid in generalized RDF are handled when inserted in bulk
+///
+/// Oxigraph does not support generalized RDF,
+/// so such quads can not be inserted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConversionMode {
+    /// Fail with a [`ConversionError`](enum.ConversionError.html) (the default)
+    Strict,
+    /// Skip the quad, logging a warning
+    Lenient,
+}
+
+impl Default for ConversionMode {
+    fn default() -> Self {
+        ConversionMode::Strict
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sophia::ns::{rdf, xsd};
+
+    #[test]
+    fn lang_string() {
+        let hello = STerm::<&str>::new_literal_lang("hello", "en").unwrap();
+        let ohello: OTerm = hello.try_oxigraphize().unwrap();
+        match ohello {
+            OTerm::Literal(lit) => {
+                assert_eq!(lit.value(), "hello");
+                assert_eq!(lit.language(), Some("en"));
+                assert_eq!(lit.datatype().as_str(), RDF_LANG_STRING);
+            }
+            other => panic!("unexpected term {}", other),
+        }
+
+        let bad = STerm::<&str>::new_literal_dt("hello", rdf::langString).unwrap();
+        match TryOxigraphize::<OTerm>::try_oxigraphize(&bad) {
+            Err(ConversionError::MissingLanguageTag(value)) => assert_eq!(value, "hello"),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn bnode_id() {
+        for label in &["abc", "xyz", "0123456789abcdef", "ghijklmnopqrstuv"] {
+            let b = SBlankNode::new(*label).unwrap();
+            let ob1: OBlankNode = b.try_oxigraphize().unwrap();
+            let ob2: OBlankNode = b.try_oxigraphize().unwrap();
+            assert_eq!(ob1, ob2);
+        }
+        let ob: OBlankNode = SBlankNode::new("xyz").unwrap().try_oxigraphize().unwrap();
+        assert_eq!(ob.as_str(), "xyz");
+    }
+
+    #[test]
+    fn interner() {
+        let mut interner = Interner::default();
+        let iri = STerm::<&str>::new_iri("http://example.org/foo").unwrap();
+        let t1 = interner.term(iri.clone()).unwrap();
+        let t2 = interner.term(iri).unwrap();
+        assert_eq!(t1, t2);
+        assert_eq!(interner.len(), 1);
+
+        let var = STerm::<&str>::new_variable("x").unwrap();
+        match interner.term(var) {
+            Err(ConversionError::Variable(v)) => assert_eq!(v, "x"),
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert_eq!(interner.len(), 1);
+    }
+
+    #[test]
+    fn language_tag_validation() {
+        for tag in &["en", "en-US", "zh-Hant-TW", "de-CH-1996", "x-private"] {
+            let lit = SLiteral::new_lang_unchecked("x", *tag);
+            let olit: OLiteral = lit.try_oxigraphize().unwrap();
+            assert_eq!(olit.language(), Some(*tag));
+        }
+        for tag in &["", "en_US", "english-language", "en--US", "1en"] {
+            let lit = SLiteral::new_lang_unchecked("x", *tag);
+            match TryOxigraphize::<OLiteral>::try_oxigraphize(&lit) {
+                Err(ConversionError::InvalidLanguageTag(t)) => assert_eq!(t, *tag),
+                other => panic!("unexpected result {:?}", other),
+            }
+            let olit = oxigraphize_literal_unchecked(&lit).unwrap();
+            assert_eq!(olit.language(), Some(*tag));
+        }
+    }
+
+    #[test]
+    fn invalid_iri() {
+        let iri = STerm::Iri(SIri::new_unchecked("http://example.org/ space", true));
+        let res: Result<NamedNode, _> = iri.try_oxigraphize();
+        match res {
+            Err(ConversionError::InvalidIri(i)) => assert_eq!(i, "http://example.org/ space"),
+            _ => panic!("expected InvalidIri, got {:?}", res),
+        }
+        let res: Result<OTerm, _> = iri.try_oxigraphize();
+        assert!(matches!(res, Err(ConversionError::InvalidIri(_))));
+
+        let iri = STerm::<&str>::new_iri("http://example.org/ok").unwrap();
+        let res: Result<NamedNode, _> = iri.try_oxigraphize();
+        assert_eq!(res.unwrap().as_str(), "http://example.org/ok");
+    }
+
+    #[test]
+    fn native_value() {
+        let int = STerm::<&str>::new_literal_dt("42", xsd::integer).unwrap();
+        let int: OLiteral = match int {
+            STerm::Literal(lit) => lit.try_oxigraphize().unwrap(),
+            _ => unreachable!(),
+        };
+        assert_eq!(int.as_native_value(), Some(NativeValue::Integer(42)));
+
+        let t = OLiteral::new_typed_literal(
+            "true",
+            NamedNode::new_unchecked(format!("{}boolean", XSD_NS)),
+        );
+        assert_eq!(t.as_native_value(), Some(NativeValue::Boolean(true)));
+
+        let d = OLiteral::new_typed_literal(
+            "2.5",
+            NamedNode::new_unchecked(format!("{}double", XSD_NS)),
+        );
+        assert_eq!(d.as_native_value(), Some(NativeValue::Double(2.5)));
+
+        assert_eq!(OLiteral::new_simple_literal("42").as_native_value(), None);
+        let bad = OLiteral::new_typed_literal(
+            "foo",
+            NamedNode::new_unchecked(format!("{}integer", XSD_NS)),
+        );
+        assert_eq!(bad.as_native_value(), None);
+    }
+
+    #[test]
+    fn native_value_lexical_forms() {
+        let typed = |value: &str, dt: &str| {
+            OLiteral::new_typed_literal(
+                value,
+                NamedNode::new_unchecked(format!("{}{}", XSD_NS, dt)),
+            )
+            .as_native_value()
+        };
+        assert_eq!(typed("1e5", "double"), Some(NativeValue::Double(1e5)));
+        assert_eq!(
+            typed("-INF", "float"),
+            Some(NativeValue::Double(f64::NEG_INFINITY))
+        );
+        match typed("NaN", "double") {
+            Some(NativeValue::Double(d)) => assert!(d.is_nan()),
+            other => panic!("unexpected value {:?}", other),
+        }
+        assert_eq!(typed("inf", "double"), None);
+        assert_eq!(typed("nan", "double"), None);
+        assert_eq!(typed("infinity", "float"), None);
+
+        assert_eq!(typed("1.", "decimal"), Some(NativeValue::Double(1.0)));
+        assert_eq!(typed("-.5", "decimal"), Some(NativeValue::Double(-0.5)));
+        assert_eq!(typed("1e5", "decimal"), None);
+        assert_eq!(typed("INF", "decimal"), None);
+        assert_eq!(typed("NaN", "decimal"), None);
+
+        assert_eq!(
+            typed("18446744073709551615", "unsignedLong"),
+            Some(NativeValue::Integer(u64::MAX.into()))
+        );
+        assert_eq!(typed("18446744073709551616", "unsignedLong"), None);
+        assert_eq!(typed("-1", "unsignedInt"), None);
+        assert_eq!(typed("128", "byte"), None);
+        assert_eq!(typed("0", "positiveInteger"), None);
+        assert_eq!(typed("+7", "integer"), Some(NativeValue::Integer(7)));
+        assert_eq!(typed("1.0", "integer"), None);
+    }
+
+    #[test]
+    fn triple() {
+        use sophia::triple::Triple;
+
+        let s = NamedNode::new("http://example.org/s").unwrap();
+        let o = OLiteral::new_language_tagged_literal("chat", "fr").unwrap();
+        let t = OTriple::new(OBlankNode::default(), s, o);
+        let expected = [
+            t.subject().as_sophia_ref(),
+            STerm::<&str>::new_iri("http://example.org/s").unwrap(),
+            STerm::<&str>::new_literal_lang("chat", "fr").unwrap(),
+        ];
+
+        let by_ref = t.as_sophia_t_ref();
+        assert!(matches!(by_ref.s(), STerm::BNode(_)));
+        assert_eq!(by_ref, expected);
+        let copied: [STerm<String>; 3] = t.as_sophia_t();
+        assert_eq!(copied.p(), &expected[1]);
+        assert_eq!(copied, expected);
+        let moved: [STerm<Box<str>>; 3] = t.clone().into_sophia_t();
+        assert_eq!(moved.o(), &expected[2]);
+        assert_eq!(moved, expected);
+    }
+
+    #[test]
+    fn namespace() {
+        let ex = Namespace::new("http://example.org/").unwrap();
+        assert_eq!(ex.as_str(), "http://example.org/");
+        let (s_iri, o_iri) = ex.get("foo").unwrap();
+        assert_eq!(
+            STerm::Iri(s_iri),
+            STerm::<&str>::new_iri("http://example.org/foo").unwrap()
+        );
+        assert_eq!(o_iri, NamedNode::new("http://example.org/foo").unwrap());
+
+        assert!(matches!(ex.get("a b"), Err(ConversionError::InvalidIri(_))));
+        assert!(matches!(
+            Namespace::new("foo"),
+            Err(ConversionError::InvalidIri(_))
+        ));
+    }
+}
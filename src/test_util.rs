@@ -0,0 +1,16 @@
+//! Fixtures shared by the tests of this crate
+use sophia::ns::xsd;
+use sophia_term::Term;
+
+/// The default graph, as a graph name
+pub(crate) const DG: Option<&'static Term<&'static str>> = None;
+
+/// An IRI in the `http://example.org/` namespace
+pub(crate) fn ex(suffix: &str) -> Term<String> {
+    Term::new_iri(format!("http://example.org/{}", suffix)).unwrap()
+}
+
+/// A literal of type `xsd:string`
+pub(crate) fn lit(txt: &str) -> Term<String> {
+    Term::new_literal_dt(txt.to_string(), xsd::string).unwrap()
+}
@@ -0,0 +1,149 @@
+//! Atomic sequences of mutations on a SophiaConnection
+use crate::connection::{MutationError, SophiaConnection};
+use oxigraph::model::Quad as OQuad;
+use oxigraph::RepositoryConnection;
+use sophia_term::{Term, TermData};
+
+/// A handle for inserting and removing quads as a unit.
+///
+/// It is passed to the closure given to [`SophiaConnection::transaction`].
+/// Mutations are buffered, and only applied (in a single Oxigraph transaction)
+/// if that closure succeeds.
+///
+/// [`SophiaConnection::transaction`]: ../connection/struct.SophiaConnection.html#method.transaction
+pub struct Txn<'a, C: RepositoryConnection> {
+    conn: &'a mut SophiaConnection<C>,
+    changes: Vec<(OQuad, bool)>,
+}
+
+impl<'a, C> Txn<'a, C>
+where
+    C: RepositoryConnection,
+{
+    pub(crate) fn new(conn: &'a mut SophiaConnection<C>) -> Self {
+        Txn {
+            conn,
+            changes: vec![],
+        }
+    }
+
+    /// Insert a quad when the transaction is committed.
+    ///
+    /// Terms are converted as by [`MutableDataset::insert`] on the connection,
+    /// substituting placeholders if it has been configured to;
+    /// conversion errors are reported immediately.
+    ///
+    /// [`MutableDataset::insert`]: ../connection/struct.SophiaConnection.html#method.insert
+    pub fn insert<T, U, V, W>(
+        &mut self,
+        s: &Term<T>,
+        p: &Term<U>,
+        o: &Term<V>,
+        g: Option<&Term<W>>,
+    ) -> Result<(), MutationError>
+    where
+        T: TermData,
+        U: TermData,
+        V: TermData,
+        W: TermData,
+    {
+        let quad = self.conn.oxi_quad_or_placeholders(s, p, o, g)?;
+        self.changes.push((quad, true));
+        Ok(())
+    }
+
+    /// Remove a quad when the transaction is committed.
+    ///
+    /// Quads that can not be converted can not be in the store,
+    /// so they are silently ignored.
+    pub fn remove<T, U, V, W>(
+        &mut self,
+        s: &Term<T>,
+        p: &Term<U>,
+        o: &Term<V>,
+        g: Option<&Term<W>>,
+    ) -> Result<(), MutationError>
+    where
+        T: TermData,
+        U: TermData,
+        V: TermData,
+        W: TermData,
+    {
+        if let Ok(quad) = self.conn.oxi_quad(s, p, o, g) {
+            self.changes.push((quad, false));
+        }
+        Ok(())
+    }
+
+    /// The number of mutations buffered so far.
+    pub fn pending(&self) -> usize {
+        self.changes.len()
+    }
+
+    pub(crate) fn commit(self) -> Result<(), MutationError> {
+        Ok(self.conn.apply_oxi_changes(self.changes)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{ex, DG};
+    use oxigraph::{MemoryRepository, Repository};
+    use sophia::dataset::{Dataset, MutableDataset};
+
+    #[test]
+    fn commit() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        d.insert(&ex("s"), &ex("p"), &ex("o0"), DG).unwrap();
+        d.transaction(|txn| {
+            txn.insert(&ex("s"), &ex("p"), &ex("o1"), DG)?;
+            txn.insert(&ex("s"), &ex("p"), &ex("o2"), Some(&ex("g")))?;
+            txn.remove(&ex("s"), &ex("p"), &ex("o0"), DG)?;
+            assert_eq!(txn.pending(), 3);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(d.quads().count(), 2);
+        assert!(d
+            .contains(&ex("s"), &ex("p"), &ex("o2"), Some(&ex("g")))
+            .unwrap());
+    }
+
+    #[test]
+    fn rollback() {
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap());
+        let lit = Term::<String>::new_literal_lang("x", "en").unwrap();
+        let res = d.transaction(|txn| {
+            txn.insert(&ex("s"), &ex("p"), &ex("o1"), DG)?;
+            txn.insert(&ex("s"), &ex("p"), &ex("o2"), DG)?;
+            // a literal can not be a subject
+            txn.insert(&lit, &ex("p"), &ex("o3"), DG)?;
+            Ok(())
+        });
+        assert!(res.is_err());
+        assert_eq!(d.quads().count(), 0);
+    }
+
+    #[test]
+    fn placeholders() {
+        use sophia::quad::Quad;
+
+        let repo = MemoryRepository::default();
+        let mut d = SophiaConnection::new(repo.connection().unwrap())
+            .with_max_literal_len(3)
+            .with_placeholders("http://example.org/placeholder/")
+            .unwrap();
+        let long = Term::<String>::new_literal_dt("too long", ex("dt")).unwrap();
+        d.transaction(|txn| {
+            txn.insert(&ex("s"), &ex("p"), &long, DG)?;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(d.substitutions().len(), 1);
+        let q = d.quads().next().unwrap().unwrap();
+        assert!(q.o().value().starts_with("http://example.org/placeholder/"));
+    }
+}
@@ -0,0 +1,42 @@
+//! Sophia Triple implementation of Oxigraph Quad
+use crate::quad::QuadBridge;
+use oxigraph::model::Quad as OQuad;
+use sophia::quad::Quad as SQuad;
+use sophia::triple::Triple as STriple;
+use sophia_term::Term as STerm;
+
+/// Wraps an Oxigraph Quad into a Sophia Triple, ignoring its graph name.
+///
+/// Terms are converted lazily, as in [`QuadBridge`](../quad/struct.QuadBridge.html).
+pub struct TripleBridge(QuadBridge);
+
+impl TripleBridge {
+    /// Construct TripleBridge around Oxigraph Quad
+    pub fn new(q: OQuad) -> TripleBridge {
+        TripleBridge(QuadBridge::new(q))
+    }
+
+    /// Borrow the underlying Oxigraph Quad
+    pub fn as_oxi(&self) -> &OQuad {
+        self.0.as_oxi()
+    }
+}
+
+impl From<QuadBridge> for TripleBridge {
+    fn from(q: QuadBridge) -> TripleBridge {
+        TripleBridge(q)
+    }
+}
+
+impl STriple for TripleBridge {
+    type TermData = String;
+    fn s(&self) -> &STerm<String> {
+        self.0.s()
+    }
+    fn p(&self) -> &STerm<String> {
+        self.0.p()
+    }
+    fn o(&self) -> &STerm<String> {
+        self.0.o()
+    }
+}